@@ -3,16 +3,31 @@ extern crate env_logger;
 
 use twig::operator::Operator;
 use twig::environment::Environment;
+use twig::extension::Extension;
 use twig::tokens::Lexer;
 use twig::nodes::{ Parser, Parse, Module };
 
+struct NewOpExtension {
+    precedence: u16,
+}
+
+impl Extension for NewOpExtension {
+    fn name(&self) -> &'static str {
+        "newop"
+    }
+
+    fn operators(&self) -> Vec<Operator> {
+        vec![
+            Operator::new_binary_left("newop", self.precedence, |_, _| unimplemented!()),
+        ]
+    }
+}
+
 fn main() {
     env_logger::init().unwrap();
 
     let mut env = Environment::default();
-    env.push_operators(vec![
-        Operator::new_binary_left("newop", 10, |_, _| unimplemented!()),
-    ]);
+    env.add_extension(Box::new(NewOpExtension { precedence: 10 }));
 
     let env = env.init_all();
 
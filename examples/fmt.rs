@@ -0,0 +1,30 @@
+extern crate twig;
+
+use twig::environment::Environment;
+use twig::format::format;
+use twig::error::{ Error, Style };
+use std::fs::File;
+use std::io::Read;
+use std::env;
+
+/// Prints template from the path given as the first argument in normalized form.
+fn main() {
+    let path = env::args().nth(1)
+        .expect("usage: cargo run --example fmt -- <template path>");
+
+    let mut f = File::open(&path)
+        .ok()
+        .expect(&format!("failed to open template at {:?}", path));
+    let mut template = String::new();
+    f.read_to_string(&mut template).unwrap();
+
+    let env = Environment::default().init_all();
+
+    match format(&env, &template) {
+        Ok(formatted) => print!("{}", formatted),
+        Err(e) => match Error::from(e).diagnostic(&path) {
+            Some(diagnostic) => eprint!("{}", diagnostic.render(&template, Style::Ansi)),
+            None => unreachable!("syntax error always has a diagnostic"),
+        },
+    }
+}
@@ -0,0 +1,39 @@
+extern crate twig;
+
+use twig::environment::Environment;
+use twig::tokens::Lexer;
+use twig::nodes::parse_recovering;
+use twig::error::{ Error, Style };
+use std::fs::File;
+use std::io::Read;
+use std::env;
+use std::process;
+
+/// Prints all syntax errors of template from the path given as the first argument.
+fn main() {
+    let path = env::args().nth(1)
+        .expect("usage: cargo run --example lint -- <template path>");
+
+    let mut f = File::open(&path)
+        .ok()
+        .expect(&format!("failed to open template at {:?}", path));
+    let mut template = String::new();
+    f.read_to_string(&mut template).unwrap();
+
+    let env = Environment::default().init_all();
+    let lexer = Lexer::default(&env.lexing);
+    let mut tokens = lexer.tokens(&template).recovering();
+    let (_, errors) = parse_recovering(&env.parsing, &mut tokens);
+
+    for e in &errors {
+        match Error::from(e.clone()).diagnostic(&path) {
+            Some(diagnostic) => eprintln!("{}", diagnostic.render(&template, Style::Ansi)),
+            None => unreachable!("syntax error always has a diagnostic"),
+        }
+    }
+
+    if errors.len() > 0 {
+        eprintln!("{} errors found", errors.len());
+        process::exit(1);
+    }
+}
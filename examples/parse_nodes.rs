@@ -1,5 +1,6 @@
 extern crate twig;
 extern crate env_logger;
+#[cfg(feature = "serde")] extern crate serde_json;
 
 use twig::environment::Environment;
 use twig::tokens::Lexer;
@@ -18,4 +19,12 @@ fn main() {
     );
 
     println!("{:#?}", maybe_module);
+
+    // Run with `--features serde` to also print the tree as JSON.
+    #[cfg(feature = "serde")]
+    {
+        if let Ok(ref module) = maybe_module {
+            println!("{}", serde_json::to_string_pretty(module).unwrap());
+        }
+    }
 }
@@ -0,0 +1,202 @@
+use std::collections::{ HashMap, HashSet };
+use std::sync::Arc;
+use std::time::SystemTime;
+use error::Result;
+use loader::Loader;
+use instructions::Program;
+
+/// Compiled template kept in engine cache.
+struct Entry {
+    /// Template name, as given to the loader.
+    name: String,
+    program: Arc<Program>,
+    /// Time when template source was loaded for compilation.
+    compiled_at: SystemTime,
+    /// Cache keys of templates this template depends on (parents,
+    /// included and imported templates).
+    dependencies: HashSet<String>,
+}
+
+/// Compiled template cache, keyed by loader cache key.
+pub struct Cache {
+    entries: HashMap<String, Entry>,
+}
+
+impl Cache {
+    pub fn new() -> Cache {
+        Cache {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns cached program if it and all its dependencies are still fresh.
+    ///
+    /// Stale entries are evicted together with all entries that depend on them.
+    pub fn get<L: Loader>(&mut self, key: &str, loader: &L) -> Result<Option<Arc<Program>>> {
+        if !try!(self.check(key, loader, &mut HashSet::new())) {
+            return Ok(None);
+        }
+        Ok(self.entries.get(key).map(|e| e.program.clone()))
+    }
+
+    /// Returns cached program without checking if it is fresh.
+    pub fn peek(&self, key: &str) -> Option<Arc<Program>> {
+        self.entries.get(key).map(|e| e.program.clone())
+    }
+
+    pub fn insert(&mut self, key: &str, name: &str, program: Arc<Program>, compiled_at: SystemTime) {
+        self.entries.insert(key.into(), Entry {
+            name: name.into(),
+            program: program,
+            compiled_at: compiled_at,
+            dependencies: HashSet::new(),
+        });
+    }
+
+    /// Records that template `key` depends on template `dependency`.
+    pub fn add_dependency(&mut self, key: &str, dependency: &str) {
+        if key == dependency {
+            return;
+        }
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.dependencies.insert(dependency.into());
+        }
+    }
+
+    /// Removes entry and every entry that depends on it.
+    pub fn evict(&mut self, key: &str) {
+        if let None = self.entries.remove(key) {
+            return;
+        }
+
+        let dependents: Vec<String> = self.entries.iter()
+            .filter(|&(_, e)| e.dependencies.contains(key))
+            .map(|(k, _)| k.clone())
+            .collect();
+
+        for dependent in dependents {
+            self.evict(&dependent);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Checks if entry and its dependencies are fresh, evicts it if not.
+    fn check<L: Loader>(&mut self, key: &str, loader: &L, visited: &mut HashSet<String>) -> Result<bool> {
+        if !visited.insert(key.into()) {
+            return Ok(self.entries.contains_key(key));
+        }
+
+        let (name, compiled_at, dependencies) = match self.entries.get(key) {
+            Some(entry) => (entry.name.clone(), entry.compiled_at, entry.dependencies.clone()),
+            None => return Ok(false),
+        };
+
+        let mut fresh = try!(loader.is_fresh(&name, compiled_at));
+        for dependency in dependencies {
+            if !fresh {
+                break;
+            }
+            fresh = try!(self.check(&dependency, loader, visited));
+        }
+
+        if !fresh {
+            self.evict(key);
+        }
+
+        Ok(fresh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+    use error::Result;
+    use loader::Loader;
+    use instructions::Program;
+    use super::Cache;
+
+    struct StaleLoader {
+        stale: HashSet<&'static str>,
+    }
+
+    impl Loader for StaleLoader {
+        fn get_source(&self, _name: &str) -> Result<String> {
+            Ok(String::new())
+        }
+
+        fn is_fresh(&self, name: &str, _time: SystemTime) -> Result<bool> {
+            Ok(!self.stale.contains(name))
+        }
+    }
+
+    fn loader(stale: Vec<&'static str>) -> StaleLoader {
+        StaleLoader { stale: stale.into_iter().collect() }
+    }
+
+    fn cache(keys: Vec<&'static str>) -> Cache {
+        let mut cache = Cache::new();
+        for key in keys {
+            cache.insert(key, key, Arc::new(Program::new()), SystemTime::now());
+        }
+        cache
+    }
+
+    #[test]
+    fn returns_fresh_entries() {
+        let mut cache = cache(vec!["a"]);
+        assert!(cache.get("a", &loader(vec![])).unwrap().is_some());
+        assert!(cache.get("b", &loader(vec![])).unwrap().is_none());
+    }
+
+    #[test]
+    fn evicts_stale_entries() {
+        let mut cache = cache(vec!["a"]);
+        assert!(cache.get("a", &loader(vec!["a"])).unwrap().is_none());
+        assert!(!cache.contains("a"));
+    }
+
+    #[test]
+    fn evicts_entries_with_stale_dependencies() {
+        let mut cache = cache(vec!["page", "layout", "row", "other"]);
+        cache.add_dependency("page", "layout");
+        cache.add_dependency("layout", "row");
+
+        assert!(cache.get("page", &loader(vec!["row"])).unwrap().is_none());
+        assert!(!cache.contains("page"));
+        assert!(!cache.contains("layout"));
+        assert!(!cache.contains("row"));
+        assert!(cache.contains("other"));
+    }
+
+    #[test]
+    fn evicts_all_dependents() {
+        let mut cache = cache(vec!["a", "b", "row"]);
+        cache.add_dependency("a", "row");
+        cache.add_dependency("b", "row");
+
+        cache.evict("row");
+        assert!(!cache.contains("a"));
+        assert!(!cache.contains("b"));
+    }
+
+    #[test]
+    fn handles_dependency_cycles() {
+        let mut cache = cache(vec!["a", "b"]);
+        cache.add_dependency("a", "b");
+        cache.add_dependency("b", "a");
+
+        assert!(cache.get("a", &loader(vec![])).unwrap().is_some());
+        assert!(cache.get("a", &loader(vec!["b"])).unwrap().is_none());
+        assert!(!cache.contains("b"));
+    }
+}
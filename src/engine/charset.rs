@@ -0,0 +1,86 @@
+use std::io::{ self, Write };
+use std::str;
+
+/// Output charset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Charset {
+    Utf8,
+    Latin1,
+}
+
+impl Charset {
+    pub fn from_name(name: &str) -> Option<Charset> {
+        match &name.to_uppercase()[..] {
+            "UTF-8" | "UTF8" => Some(Charset::Utf8),
+            "ISO-8859-1" | "LATIN1" => Some(Charset::Latin1),
+            _ => None,
+        }
+    }
+}
+
+/// Converts UTF-8 output to ISO-8859-1.
+///
+/// Characters that can not be represented are replaced with "?".
+pub struct Latin1Writer<'w> {
+    output: &'w mut Write,
+    /// Start of multibyte character split between writes.
+    pending: Vec<u8>,
+}
+
+impl<'w> Latin1Writer<'w> {
+    pub fn new(output: &'w mut Write) -> Latin1Writer<'w> {
+        Latin1Writer {
+            output: output,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<'w> Write for Latin1Writer<'w> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+
+        let (valid, rest) = match str::from_utf8(&self.pending) {
+            Ok(_) => (self.pending.len(), self.pending.len()),
+            Err(e) => match e.error_len() {
+                Some(len) => (e.valid_up_to(), e.valid_up_to() + len),
+                None => (e.valid_up_to(), e.valid_up_to()),
+            },
+        };
+
+        let mut encoded: Vec<u8> = str::from_utf8(&self.pending[..valid])
+            .expect("valid prefix")
+            .chars()
+            .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+            .collect();
+        if rest > valid {
+            encoded.push(b'?');
+        }
+        try!(self.output.write_all(&encoded));
+
+        self.pending.drain(..rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use super::*;
+
+    #[test]
+    fn encodes_characters_split_between_writes() {
+        let mut output = Vec::new();
+        {
+            let mut writer = Latin1Writer::new(&mut output);
+            let bytes = "a\u{e9}\u{20ac}".as_bytes();
+            writer.write_all(&bytes[..2]).unwrap();
+            writer.write_all(&bytes[2..]).unwrap();
+        }
+        assert_eq!(output, vec![b'a', 0xe9, b'?']);
+    }
+}
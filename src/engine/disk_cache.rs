@@ -0,0 +1,99 @@
+use std::fs::{ self, File };
+use std::io::{ BufReader, BufWriter, Write };
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use sha1::Sha1;
+use little::bytecode::Serializer;
+use error::{ Result, EngineError };
+use instructions::{ Program, FORMAT_VERSION };
+
+/// Counter that makes temporary file names unique between threads.
+static NEXT_TMP_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Compiled template storage in the filesystem.
+///
+/// Entries are stored at `<dir>/v<format version>/<environment fingerprint>/<source hash>`,
+/// so a changed template source, environment or binary format never picks up
+/// a stale entry.
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> DiskCache {
+        DiskCache {
+            dir: dir.into(),
+        }
+    }
+
+    /// Returns file path for the template source compiled in environment
+    /// with specified fingerprint.
+    pub fn path(&self, env_fingerprint: &str, source: &str) -> PathBuf {
+        let mut hasher = Sha1::new();
+        hasher.update(source.as_bytes());
+        let hash = hasher.hexdigest();
+
+        self.dir
+            .join(format!("v{}", FORMAT_VERSION))
+            .join(env_fingerprint)
+            .join(&hash[..2])
+            .join(&hash[2..])
+    }
+
+    /// Loads compiled template from file.
+    ///
+    /// Missing, unreadable or invalid files are treated as cache misses.
+    pub fn load(&self, path: &Path) -> Option<Program> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return None,
+        };
+
+        match Program::deserialize(&mut BufReader::new(file)) {
+            Ok((_, program)) => Some(program),
+            Err(e) => {
+                debug!("ignoring invalid compiled template {:?}: {:?}", path, e);
+                None
+            },
+        }
+    }
+
+    /// Writes compiled template to file.
+    ///
+    /// The program is first written to a temporary file which is then renamed,
+    /// so other readers never see a partially written entry.
+    pub fn store(&self, path: &Path, program: &Program) -> Result<()> {
+        let failed = |reason: String| EngineError::CacheWriteFailed {
+            path: path.to_path_buf(),
+            reason: reason,
+        };
+
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent).map_err(|e| failed(e.to_string())));
+        }
+
+        let tmp = path.with_extension(format!(
+            "tmp{}-{}",
+            ::std::process::id(),
+            NEXT_TMP_ID.fetch_add(1, Ordering::SeqCst)
+        ));
+        {
+            let file = try!(File::create(&tmp).map_err(|e| failed(e.to_string())));
+            let mut writer = BufWriter::new(file);
+            let written = program.serialize(&mut writer)
+                .map_err(|e| format!("{:?}", e))
+                .and_then(|_| writer.flush().map_err(|e| e.to_string()));
+            if let Err(reason) = written {
+                let _ = fs::remove_file(&tmp);
+                return Err(failed(reason).into());
+            }
+        }
+
+        if let Err(e) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(failed(e.to_string()).into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+/*!
+Escaping strategies used for autoescaped output.
+*/
+
+use std::fmt::Write;
+
+/// Escaping strategy.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Strategy {
+    Html,
+    HtmlAttr,
+    Js,
+    Css,
+    Url,
+}
+
+impl Strategy {
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        Some(match name {
+            "html" => Strategy::Html,
+            "html_attr" => Strategy::HtmlAttr,
+            "js" => Strategy::Js,
+            "css" => Strategy::Css,
+            "url" => Strategy::Url,
+            _ => return None,
+        })
+    }
+}
+
+pub fn escape(value: &str, strategy: Strategy) -> String {
+    let mut out = String::with_capacity(value.len());
+    match strategy {
+        Strategy::Html => for c in value.chars() {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&#039;"),
+                c => out.push(c),
+            }
+        },
+        Strategy::HtmlAttr => for c in value.chars() {
+            match c {
+                c if c.is_ascii_alphanumeric() || ",.-_".contains(c) => out.push(c),
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                c if (c as u32) < 0x20 && c != '\t' && c != '\n' && c != '\r' => out.push_str("&#xFFFD;"),
+                c => { let _ = write!(out, "&#x{:02X};", c as u32); },
+            }
+        },
+        Strategy::Js => for c in value.chars() {
+            match c {
+                c if c.is_ascii_alphanumeric() || ",._".contains(c) => out.push(c),
+                c if (c as u32) < 0x80 => { let _ = write!(out, "\\x{:02X}", c as u32); },
+                c => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units).iter() {
+                        let _ = write!(out, "\\u{:04X}", unit);
+                    }
+                },
+            }
+        },
+        Strategy::Css => for c in value.chars() {
+            match c {
+                c if c.is_ascii_alphanumeric() => out.push(c),
+                c => { let _ = write!(out, "\\{:X} ", c as u32); },
+            }
+        },
+        Strategy::Url => for byte in value.bytes() {
+            match byte {
+                byte if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) => out.push(byte as char),
+                byte => { let _ = write!(out, "%{:02X}", byte); },
+            }
+        },
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_with_each_strategy() {
+        assert_eq!(escape("<a href='x'>&</a>", Strategy::Html), "&lt;a href=&#039;x&#039;&gt;&amp;&lt;/a&gt;");
+        assert_eq!(escape("a b\"", Strategy::HtmlAttr), "a&#x20;b&quot;");
+        assert_eq!(escape("a'b\u{e9}", Strategy::Js), "a\\x27b\\u00E9");
+        assert_eq!(escape("a b", Strategy::Css), "a\\20 b");
+        assert_eq!(escape("a b/\u{e9}", Strategy::Url), "a%20b%2F%C3%A9");
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+use error::Result;
+use loader::Loader;
+use value::Value;
+use instructions::Program;
+use super::Engine;
+
+/// Compiled standalone expression, created with `Engine::compile_expression`.
+///
+/// The expression is parsed and compiled once, and can be evaluated against
+/// many contexts.
+pub struct Expression<'e, L: 'e> {
+    engine: &'e Engine<L>,
+    source: String,
+    program: Arc<Program>,
+}
+
+impl<'e, L: Loader> Expression<'e, L> {
+    pub(super) fn new(engine: &'e Engine<L>, source: String, program: Arc<Program>) -> Expression<'e, L> {
+        Expression {
+            engine: engine,
+            source: source,
+            program: program,
+        }
+    }
+
+    /// Evaluates the expression against the context.
+    pub fn evaluate<D: Into<Value>>(&self, data: D) -> Result<Value> {
+        self.engine.evaluate(&self.program, data.into())
+    }
+
+    /// Returns source of the expression.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
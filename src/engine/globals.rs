@@ -0,0 +1,48 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use global::Global;
+use value::Value;
+
+/// Environment globals available during a single render.
+///
+/// Lazy globals are evaluated when a template reads them for the first
+/// time, and the value is reused by included templates and blocks.
+pub struct RenderGlobals<'e> {
+    globals: &'e HashMap<String, Global>,
+    evaluated: RefCell<HashMap<String, Value>>,
+}
+
+impl<'e> RenderGlobals<'e> {
+    pub fn new(globals: &'e HashMap<String, Global>) -> RenderGlobals<'e> {
+        RenderGlobals {
+            globals: globals,
+            evaluated: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns global value, or `None` if there is no such global.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.globals.get(name) {
+            Some(&Global::Value(ref value)) => Some(value.clone()),
+            Some(&Global::Lazy(ref compute)) => {
+                if let Some(value) = self.evaluated.borrow().get(name) {
+                    return Some(value.clone());
+                }
+                let value = compute();
+                self.evaluated.borrow_mut().insert(name.into(), value.clone());
+                Some(value)
+            },
+            None => None,
+        }
+    }
+
+    /// Returns true if there is a global with the name, without evaluating it.
+    pub fn contains(&self, name: &str) -> bool {
+        self.globals.contains_key(name)
+    }
+
+    /// Returns names of all globals.
+    pub fn names(&self) -> Vec<String> {
+        self.globals.keys().cloned().collect()
+    }
+}
@@ -0,0 +1,120 @@
+use std::io::Write;
+use std::sync::Arc;
+use error::{ Result, Error, TraceEntry };
+use loader::Loader;
+use value::Value;
+use instructions::Program;
+use super::{ Engine, Frame, Child, traced, output_failed };
+use super::charset::{ Charset, Latin1Writer };
+
+/// Compiled template, loaded with `Engine::load`.
+///
+/// The handle can render the whole template or any of its blocks many times.
+/// It keeps the program it was loaded with, even if the template changes later.
+pub struct TemplateHandle<'e, L: 'e> {
+    engine: &'e Engine<L>,
+    frame: Frame,
+    program: Arc<Program>,
+    /// Templates this one extends, the nearest first.
+    parents: Vec<Child>,
+}
+
+impl<'e, L: Loader> TemplateHandle<'e, L> {
+    pub(super) fn new(engine: &'e Engine<L>, template: Child, parents: Vec<Child>) -> TemplateHandle<'e, L> {
+        TemplateHandle {
+            engine: engine,
+            frame: Frame::new(template.name, template.cache_key),
+            program: template.program,
+            parents: parents,
+        }
+    }
+
+    /// Renders the template.
+    pub fn render<D: Into<Value>>(&self, data: D) -> Result<String> {
+        let mut output = Vec::new();
+        try!(self.engine.execute(&self.engine.globals(), &[self.frame.clone()], &self.program, &self.program, data.into(), &mut output)
+            .map_err(|e| self.traced(e)));
+
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Renders the template to the writer, encoded in the configured charset.
+    pub fn render_to<D: Into<Value>, W: Write>(&self, data: D, output: &mut W) -> Result<()> {
+        let frames = [self.frame.clone()];
+        try!(match try!(self.engine.charset()) {
+            Charset::Utf8 => self.engine.execute(&self.engine.globals(), &frames, &self.program, &self.program, data.into(), output),
+            Charset::Latin1 => {
+                let mut output = Latin1Writer::new(output);
+                self.engine.execute(&self.engine.globals(), &frames, &self.program, &self.program, data.into(), &mut output)
+            },
+        }.map_err(|e| self.traced(e)));
+
+        output.flush().map_err(output_failed)
+    }
+
+    /// Renders a single block of the template.
+    ///
+    /// Blocks that are only defined in templates this one extends are
+    /// rendered from the parent, with blocks of this template replacing
+    /// blocks of the parent.
+    pub fn render_block<D: Into<Value>>(&self, name: &str, data: D) -> Result<String> {
+        let (frame, program) = match self.parents.last() {
+            Some(root) if self.has_block(name) => {
+                let mut children = vec![Child {
+                    name: self.frame.name.clone(),
+                    cache_key: self.frame.cache_key.clone(),
+                    program: self.program.clone(),
+                }];
+                children.extend(self.parents[..self.parents.len() - 1].iter().cloned());
+                let frame = Frame {
+                    name: root.name.clone(),
+                    cache_key: root.cache_key.clone(),
+                    children: children,
+                };
+                (frame, root.program.clone())
+            },
+            _ => (self.frame.clone(), self.program.clone()),
+        };
+
+        let mut output = Vec::new();
+        try!(self.engine.render_block(&self.engine.globals(), &[frame], &program, name, data.into(), &mut output)
+            .map_err(|e| self.traced(e)));
+
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Returns true if the template or any template it extends defines block
+    /// with specified name.
+    pub fn has_block(&self, name: &str) -> bool {
+        self.program.block(name).is_some()
+            || self.parents.iter().any(|parent| parent.program.block(name).is_some())
+    }
+
+    /// Returns names of blocks defined in the template and templates it
+    /// extends, in definition order, starting with the root template.
+    pub fn block_names(&self) -> Vec<&str> {
+        let programs = self.parents.iter().rev()
+            .map(|parent| &parent.program)
+            .chain(Some(&self.program));
+
+        let mut names: Vec<&str> = Vec::new();
+        for program in programs {
+            for &(ref name, _) in &program.blocks {
+                if !names.contains(&&name[..]) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Returns resolved name of the template.
+    pub fn source_name(&self) -> &str {
+        &self.frame.name
+    }
+
+    /// Adds the template to the stack trace of runtime error.
+    fn traced(&self, e: Error) -> Error {
+        traced(e, vec![TraceEntry::SourceFile { target: self.frame.name.clone() }])
+    }
+}
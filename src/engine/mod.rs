@@ -1,19 +1,48 @@
-use std::mem;
 use std::fmt;
 use std::collections::HashMap;
 use environment::{ Environment, CompiledEnvironment };
-use error::Result;
-use tokens::Lexer;
-use loader::Loader;
-use nodes::parse;
+use std::cell::RefCell;
+use std::sync::{ Arc, Mutex, MutexGuard };
+use std::time::SystemTime;
+use error::{ Result, Error, EngineError, RuntimeError, Caused, Location, TraceEntry };
+use tokens::{ Lexer, Span };
+use loader::{ Loader, resolve_template_name };
+use nodes::{ parse, parse_standalone_expression };
 use value::{ Value, HashKey };
-use instructions::compile;
-use std::io::{ Read, Write };
-use std::error::Error;
+use function::Callable;
+use instructions::{ compile, compile_expression, Program };
+use std::io::{ self, Read, Write };
 use little::interpreter::{ Interpreter };
-use little::{ Fingerprint, Sha1Hasher, IdentifyValue, Template, Function, LittleValue, Build, Execute };
+use little::{ Fingerprint, Sha1Hasher, IdentifyValue, Function, LittleValue, LittleResult, LittleError, BuildError, Build, Execute };
 use sha1::Sha1;
+use byteorder::{ ByteOrder, LittleEndian };
 use std::result;
+use self::cache::Cache;
+use self::disk_cache::DiskCache;
+
+use self::strings::{ StringTemplates, Sources };
+use self::charset::{ Charset, Latin1Writer };
+use self::escape::{ escape, Strategy };
+use self::globals::RenderGlobals;
+
+pub use self::handle::TemplateHandle;
+pub use self::expression::Expression;
+
+mod cache;
+mod charset;
+mod disk_cache;
+pub mod escape;
+mod globals;
+mod handle;
+mod expression;
+mod runtime;
+mod strings;
+
+/// Name of the template frame used to evaluate standalone expressions.
+const EXPRESSION_NAME: &'static str = "__expression__";
+
+/// Number of interpreter output bytes collected before they are written.
+const PENDING_OUTPUT_LENGTH: usize = 1024;
 
 impl LittleValue for Value { }
 
@@ -57,9 +86,12 @@ impl IdentifyValue for Value {
             Value::Null => {
                 hasher.write(b"n");
             },
+            Value::Bool(v) => {
+                hasher.write(if v { b"t" } else { b"f" });
+            },
             Value::Int(ref v) => {
                 hasher.write(b"i");
-                hasher.write_i64(*v);
+                write_i64(hasher, *v);
             },
             Value::Float(_) => return Err(()),
             Value::Str(ref v) => {
@@ -78,7 +110,7 @@ impl IdentifyValue for Value {
                     match *k {
                         HashKey::Int(ref v) => {
                             hasher.write(b"i");
-                            hasher.write_i64(*v);
+                            write_i64(hasher, *v);
                         },
                         HashKey::Str(ref v) => {
                             hasher.write(b"s");
@@ -94,6 +126,16 @@ impl IdentifyValue for Value {
     }
 }
 
+/// Writes integer to the hasher.
+///
+/// The `Sha1Hasher::write_i64` does not write anything, so integer
+/// constants would get the same fingerprint.
+fn write_i64<H: Sha1Hasher>(hasher: &mut H, value: i64) {
+    let mut buf = [0; 8];
+    LittleEndian::write_i64(&mut buf, value);
+    hasher.write(&buf);
+}
+
 impl Default for Value {
     fn default() -> Value {
         Value::Null
@@ -103,6 +145,7 @@ impl Default for Value {
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Value::Bool(true) => write!(f, "1"),
             Value::Int(ref v) => write!(f, "{}", v),
             Value::Float(v) => write!(f, "{}", v),
             Value::Str(ref v) => write!(f, "{}", v),
@@ -111,87 +154,995 @@ impl fmt::Display for Value {
     }
 }
 
+/// Template that is currently being rendered.
+#[derive(Clone)]
+struct Frame {
+    name: String,
+    cache_key: String,
+    /// Templates that extend this one, the most derived first.
+    ///
+    /// Their blocks replace blocks of this template.
+    children: Vec<Child>,
+}
+
+impl Frame {
+    fn new(name: String, cache_key: String) -> Frame {
+        Frame {
+            name: name,
+            cache_key: cache_key,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Template that extends the rendered one, with its compiled program.
+#[derive(Clone)]
+struct Child {
+    name: String,
+    cache_key: String,
+    program: Arc<Program>,
+}
+
 /// Twig Engine.
 ///
 /// Given the specified environment settings, converts templates
 /// to output string.
+///
+/// Engine can be shared between threads and used to render templates
+/// concurrently.
+///
+/// Compiled templates are cached and reused until the loader reports that
+/// the template or any template it depends on has changed. If the environment
+/// is configured with a cache directory, compiled templates are also stored
+/// there and reused by other engine instances.
 pub struct Engine<L> {
     loader: L,
     env: CompiledEnvironment,
-    lexer: Option<Lexer>,
-    functions: HashMap<&'static str, Box<Function<Value>>>,
+    lexer: Lexer,
+    cache: Mutex<Cache>,
+    disk_cache: Option<DiskCache>,
+    strings: StringTemplates,
 }
 
 impl<L: Loader> Engine<L> {
     pub fn new(loader: L, env: Environment) -> Engine<L> {
-        let mut engine = Engine {
+        let env = env.init_all();
+        let disk_cache = env.config.cache.as_ref().map(DiskCache::new);
+
+        Engine {
             loader: loader,
-            env: env.init_all(),
-            lexer: None,
-            functions: HashMap::new(),
-        };
+            lexer: Lexer::default(&env.lexing),
+            env: env,
+            cache: Mutex::new(Cache::new()),
+            disk_cache: disk_cache,
+            strings: StringTemplates::new(),
+        }
+    }
 
-        engine.lexer = Some(Lexer::default(&engine.env.lexing));
+    pub fn get<D: Into<Value>>(&self, name: &str, data: D)
+        -> Result<String>
+    {
+        let mut output = Vec::new();
+        try!(self.render(&self.globals(), &[], name, data.into(), &mut output));
 
-        engine
+        Ok(String::from_utf8_lossy(&output).into_owned())
     }
 
-    pub fn get<D: Into<Value>>(&mut self, name: &str, data: D)
-        -> Result<String>
+    /// Renders template to the writer as the output is produced.
+    ///
+    /// The `flush` tag flushes the writer, also in included templates, and
+    /// the writer is flushed when the template is rendered. Output is encoded
+    /// in the configured charset.
+    pub fn render_to<D: Into<Value>, W: Write>(&self, name: &str, data: D, output: &mut W)
+        -> Result<()>
     {
-        let lexer = self.take_lexer();
+        try!(match try!(self.charset()) {
+            Charset::Utf8 => self.render(&self.globals(), &[], name, data.into(), output),
+            Charset::Latin1 => self.render(&self.globals(), &[], name, data.into(), &mut Latin1Writer::new(output)),
+        });
+
+        output.flush().map_err(output_failed)
+    }
+
+    /// Loads and compiles template, and returns a handle to render it.
+    ///
+    /// The handle keeps the compiled template, so rendering it does not
+    /// go through name resolution and cache checks again. Templates it
+    /// extends are loaded too, so it can render their blocks.
+    pub fn load<'e>(&'e self, name: &str) -> Result<TemplateHandle<'e, L>> {
+        let name = try!(resolve_template_name(None, name));
+        let cache_key = try!(self.sources().get_cache_key(&name));
+        let program = try!(self.get_program(&name, &cache_key));
+        let template = Child { name: name, cache_key: cache_key, program: program };
+        let parents = try!(self.load_parents(&template));
+
+        Ok(TemplateHandle::new(self, template, parents))
+    }
+
+    /// Renders template created from the source string.
+    ///
+    /// The template gets a synthetic name derived from the source, and can
+    /// include templates from the loader. Only a limited number of templates
+    /// created from strings is kept, the least recently used are forgotten.
+    pub fn render_str<D: Into<Value>>(&self, source: &str, data: D) -> Result<String> {
+        let name = try!(self.add_string_template(source));
+        self.get(&name, data)
+    }
+
+    /// Evaluates standalone expression against the context.
+    ///
+    /// Use `compile_expression` to evaluate the same expression many times.
+    pub fn eval_expression<D: Into<Value>>(&self, source: &str, data: D) -> Result<Value> {
+        try!(self.compile_expression(source)).evaluate(data)
+    }
+
+    /// Compiles standalone expression, and returns a handle to evaluate it.
+    ///
+    /// The expression has the same syntax as the one between variable
+    /// delimiters, i.e. `user.age >= 18 and 'admin' in user.roles`.
+    pub fn compile_expression<'e>(&'e self, source: &str) -> Result<Expression<'e, L>> {
+        let variable = &self.env.lexing.options.tag_variable;
+        let wrapped = format!("{} {} {}", variable.start, source, variable.end);
+        let mut tokens = self.lexer.tokens(&wrapped);
+        let expr = try!(parse_standalone_expression(&self.env.parsing, &mut tokens));
+        let program = try!(compile_expression(&self.env, &expr));
+
+        Ok(Expression::new(self, source.into(), Arc::new(program)))
+    }
+
+    /// Removes all compiled templates from cache.
+    ///
+    /// Templates created from strings are forgotten too.
+    pub fn clear_cache(&self) {
+        self.cache().clear();
+        self.strings.clear();
+    }
+
+    /// Returns the template loader.
+    pub fn loader(&self) -> &L {
+        &self.loader
+    }
+
+    /// Returns the template loader for modification.
+    ///
+    /// Changed templates are recompiled if loader reports them as not fresh.
+    pub fn loader_mut(&mut self) -> &mut L {
+        &mut self.loader
+    }
+
+    /// Renders template `name`, resolved relative to the last template in `parents`.
+    ///
+    /// `parents` is the chain of templates that lead to this template
+    /// being rendered (i.e. over nested includes).
+    fn render(&self, globals: &RenderGlobals, parents: &[Frame], name: &str, data: Value, output: &mut Write)
+        -> Result<()>
+    {
+        let name = try!(resolve_template_name(parents.last().map(|p| &p.name[..]), name));
+        let cache_key = try!(self.sources().get_cache_key(&name));
+        let program = try!(self.get_program(&name, &cache_key));
+
+        if let Some(parent) = parents.last() {
+            self.cache().add_dependency(&parent.cache_key, &cache_key);
+        }
+
+        let mut frames = parents.to_vec();
+        frames.push(Frame::new(name.clone(), cache_key));
+
+        try!(self.execute(globals, &frames, &program, &program, data, output)
+            .map_err(|e| traced(e, vec![TraceEntry::SourceFile { target: name }])));
+
+        Ok(())
+    }
+
+    /// Evaluates compiled standalone expression.
+    ///
+    /// Positions in the expression are left out of the stack trace, they
+    /// point into the source wrapped in variable delimiters.
+    fn evaluate(&self, program: &Arc<Program>, data: Value) -> Result<Value> {
+        let frame = Frame::new(EXPRESSION_NAME.into(), String::new());
+        self.execute(&self.globals(), &[frame], program, program, data, &mut io::sink())
+            .map_err(|e| match e {
+                Error::Runtime(mut e) => {
+                    e.stack_trace.retain(|entry| match *entry {
+                        TraceEntry::Position { .. } => false,
+                        _ => true,
+                    });
+                    Error::Runtime(e)
+                },
+                other => other,
+            })
+    }
+
+    /// Executes `code` that belongs to the `template`, the last one in `frames`.
+    ///
+    /// The `code` is either the template itself or one of its blocks.
+    /// Returns the value passed to the "__return" call, or null.
+    fn execute(&self, globals: &RenderGlobals, frames: &[Frame], template: &Arc<Program>, code: &Program, data: Value, output: &mut Write)
+        -> Result<Value>
+    {
+        let template_name = &frames[frames.len() - 1].name;
+        let strategy = match self.env.config.autoescape.strategy_for(template_name) {
+            None => None,
+            Some(name) => match Strategy::from_name(&name) {
+                Some(strategy) => Some(strategy),
+                None => return Err(EngineError::UnknownEscapeStrategy {
+                    strategy: name,
+                    template: template_name.clone(),
+                }.into()),
+            },
+        };
+
+        let failure = RefCell::new(None);
+        let fail = |e: Error| -> LittleResult<Value> {
+            let mut failure = failure.borrow_mut();
+            if failure.is_none() {
+                *failure = Some(e);
+            }
+            Ok(Value::Null)
+        };
+
+        // Other templates are written to the output, or returned as a string
+        // when the result is used in an expression.
+        let sink = RefCell::new(Sink::new(output));
+        let nested = |printed: bool, render: &Fn(&mut Write) -> Result<()>| -> Result<Value> {
+            if printed {
+                let mut sink = sink.borrow_mut();
+                let output = try!(sink.writer());
+                render(output).map(|_| Value::Null)
+            } else {
+                let mut output = Vec::new();
+                try!(render(&mut output));
+                Ok(Value::Str(String::from_utf8_lossy(&output).into_owned()))
+            }
+        };
+
+        // Include and block references get the position of the tag last, it
+        // is added to the stack trace of errors inside.
+        let include = |args: &[Value]| -> LittleResult<Value> {
+            if failure.borrow().is_some() {
+                return Ok(Value::Null);
+            }
+            let (include_args, loc) = match args {
+                [ref name, ref context, ref variables, ref ignore_missing, ref line, ref column, ref start, ref end] => {
+                    ([name.clone(), context.clone(), variables.clone(), ignore_missing.clone()], position(line, column, start, end))
+                },
+                _ => unreachable!("twig bug: include expects 8 arguments"),
+            };
+            match nested(true, &|output| self.render_include(globals, frames, &include_args, output)) {
+                Ok(value) => Ok(value),
+                Err(e) => fail(traced(e, vec![TraceEntry::Position { from: loc }])),
+            }
+        };
+
+        // The `include` function gets the current context and the "printed"
+        // flag before its own arguments: template, variables, with_context
+        // and ignore_missing.
+        let include_function = |args: &[Value]| -> LittleResult<Value> {
+            if failure.borrow().is_some() {
+                return Ok(Value::Null);
+            }
+            if args.len() < 3 || args.len() > 6 {
+                return fail(RuntimeError::InvalidArgumentCount { defined: 4, given: args.len() - 2 }.at(Vec::new()).into());
+            }
+            let printed = args[1].is_truthy();
+            let with_context = args.get(4).map_or(true, |v| v.is_truthy());
+            let ignore_missing = args.get(5).map_or(false, |v| v.is_truthy());
+            let include_args = [
+                args[2].clone(),
+                if with_context { args[0].clone() } else { Value::Null },
+                args.get(3).cloned().unwrap_or(Value::Null),
+                Value::Int(ignore_missing as i64),
+            ];
+            match nested(printed, &|output| self.render_include(globals, frames, &include_args, output)) {
+                Ok(value) => Ok(value),
+                Err(e) => fail(e),
+            }
+        };
+
+        let block = |args: &[Value]| -> LittleResult<Value> {
+            if failure.borrow().is_some() {
+                return Ok(Value::Null);
+            }
+            let (name, context, loc) = match args {
+                [ref name, ref context, ref line, ref column, ref start, ref end] => {
+                    (name.to_string(), context.clone(), position(line, column, start, end))
+                },
+                _ => unreachable!("twig bug: block expects 6 arguments"),
+            };
+            match nested(true, &|output| self.render_block(globals, frames, template, &name, context.clone(), output)) {
+                Ok(value) => Ok(value),
+                Err(e) => fail(traced(e, vec![TraceEntry::Position { from: loc }])),
+            }
+        };
+
+        let extends = |args: &[Value]| -> LittleResult<Value> {
+            if failure.borrow().is_some() {
+                return Ok(Value::Null);
+            }
+            let (name, context, loc) = match args {
+                [ref name, ref context, ref line, ref column, ref start, ref end] => {
+                    (name.to_string(), context.clone(), position(line, column, start, end))
+                },
+                _ => unreachable!("twig bug: extends expects 6 arguments"),
+            };
+            match nested(true, &|output| self.render_parent(globals, frames, template, &name, context.clone(), output)) {
+                Ok(value) => Ok(value),
+                Err(e) => fail(traced(e, vec![TraceEntry::Position { from: loc }])),
+            }
+        };
+
+        // Imported templates by import target, with the name of the imported
+        // macro. Macros of templates imported with the `import` tag are
+        // called by the alias and the macro name.
+        let imports: RefCell<HashMap<String, (String, Option<String>)>> = RefCell::new(HashMap::new());
+        let import = |args: &[Value]| -> LittleResult<Value> {
+            if failure.borrow().is_some() {
+                return Ok(Value::Null);
+            }
+            let (target, source, symbol, loc) = match args {
+                [ref target, ref source, ref symbol, ref line, ref column, ref start, ref end] => {
+                    (target.to_string(), source, symbol, position(line, column, start, end))
+                },
+                _ => unreachable!("twig bug: import expects 7 arguments"),
+            };
+            let symbol = match *symbol {
+                Value::Null => None,
+                ref symbol => Some(symbol.to_string()),
+            };
+            match self.load_import(frames, source) {
+                Ok(name) => {
+                    imports.borrow_mut().insert(target, (name, symbol));
+                    Ok(Value::Null)
+                },
+                Err(e) => fail(traced(e, vec![TraceEntry::Position { from: loc }])),
+            }
+        };
+
+        let call_macro = |args: &[Value]| -> LittleResult<Value> {
+            if failure.borrow().is_some() {
+                return Ok(Value::Null);
+            }
+            if args.len() < 7 {
+                unreachable!("twig bug: macro expects at least 7 arguments");
+            }
+            let (target, alias) = (args[0].to_string(), args[1].to_string());
+            let loc = position(&args[2], &args[3], &args[4], &args[5]);
+            let printed = args[6].is_truthy();
+            let arguments = &args[7..];
+            let (name, symbol) = match imports.borrow().get(&target) {
+                Some(&(ref name, ref symbol)) => (name.clone(), symbol.clone()),
+                None => return fail(traced(EngineError::MacroNotFound {
+                    name: alias,
+                    template: template_name.clone(),
+                }.into(), vec![TraceEntry::Position { from: loc }])),
+            };
+            let symbol = symbol.unwrap_or_else(|| alias.rsplit('.').next().unwrap_or("").into());
+            match nested(printed, &|output| self.render_macro(globals, frames, &name, &symbol, arguments, output)) {
+                Ok(value) => Ok(value),
+                Err(e) => fail(traced(e, vec![TraceEntry::Position { from: loc }])),
+            }
+        };
+
+        let template_from_string = |args: &[Value]| -> LittleResult<Value> {
+            match args {
+                [ref source] => match self.add_string_template(&source.to_string()) {
+                    Ok(name) => Ok(Value::Str(name)),
+                    Err(e) => fail(e),
+                },
+                _ => fail(RuntimeError::InvalidArgumentCount { defined: 1, given: args.len() }.at(Vec::new()).into()),
+            }
+        };
+
+        // Lookups get the position and the "ignore strict check" flag, so an
+        // undefined value fails only in strict variables mode.
+        let strict = |ignore_strict_check: &Value| {
+            self.env.config.strict_variables && *ignore_strict_check != Value::Bool(true)
+        };
+
+        let name = |args: &[Value]| -> LittleResult<Value> {
+            let (context, name, loc, ignore_strict_check) = match args {
+                [ref context, ref name, ref line, ref column, ref start, ref end, ref ignore] => {
+                    (context, name.to_string(), position(line, column, start, end), ignore)
+                },
+                _ => unreachable!("twig bug: name expects 7 arguments"),
+            };
+            match runtime::name(context, &name).map(|found| found.or_else(|| globals.get(&name))) {
+                Ok(Some(value)) => Ok(value),
+                Ok(None) if strict(ignore_strict_check) => fail(RuntimeError::UndefinedVariable {
+                    name: name,
+                    template: template_name.clone(),
+                    line: loc.line,
+                    available: {
+                        let mut available = runtime::keys(context);
+                        available.extend(globals.names());
+                        available.sort();
+                        available.dedup();
+                        available
+                    },
+                }.at(vec![TraceEntry::Position { from: loc }]).into()),
+                Ok(None) => Ok(Value::Null),
+                Err(e) => fail(e.at(vec![TraceEntry::Position { from: loc }]).into()),
+            }
+        };
+
+        let attr = |args: &[Value]| -> LittleResult<Value> {
+            let (value, key, loc, ignore_strict_check, arguments) = match args {
+                [ref value, ref key, ref line, ref column, ref start, ref end, ref ignore] => {
+                    (value, key, position(line, column, start, end), ignore, None)
+                },
+                [ref value, ref key, ref line, ref column, ref start, ref end, ref ignore, Value::Array(ref arguments)] => {
+                    (value, key, position(line, column, start, end), ignore, Some(&arguments[..]))
+                },
+                _ => unreachable!("twig bug: attr expects 7 or 8 arguments"),
+            };
+            match runtime::find_attr(value, key, arguments) {
+                Ok(Some(found)) => Ok(found),
+                Ok(None) if strict(ignore_strict_check) => fail(RuntimeError::UndefinedAttribute {
+                    name: key.to_string(),
+                    template: template_name.clone(),
+                    line: loc.line,
+                    available: runtime::keys(value),
+                }.at(vec![TraceEntry::Position { from: loc }]).into()),
+                Ok(None) => Ok(Value::Null),
+                Err(e) => fail(e.at(vec![TraceEntry::Position { from: loc }]).into()),
+            }
+        };
+
+        let defined_name = |args: &[Value]| -> LittleResult<Value> {
+            match args {
+                [ref context, ref name] => {
+                    let name = name.to_string();
+                    match runtime::name(context, &name) {
+                        Ok(found) => Ok(Value::Bool(found.is_some() || globals.contains(&name))),
+                        Err(e) => fail(e.at(Vec::new()).into()),
+                    }
+                },
+                _ => unreachable!("twig bug: defined_name expects 2 arguments"),
+            }
+        };
+
+        let defined = |args: &[Value]| -> LittleResult<Value> {
+            match args {
+                [ref value, ref key] => match runtime::find_attr(value, key, None) {
+                    Ok(found) => Ok(Value::Bool(found.is_some())),
+                    Err(e) => fail(e.at(Vec::new()).into()),
+                },
+                _ => unreachable!("twig bug: defined expects 2 arguments"),
+            }
+        };
+
+
+        let array = |args: &[Value]| -> LittleResult<Value> {
+            Ok(Value::Array(args.to_vec()))
+        };
+
+        let hash = |args: &[Value]| -> LittleResult<Value> {
+            Ok(runtime::hash(args))
+        };
 
-        let compiled_template = try!(self.get_compiled_template(&lexer, name));
+        // Operators, filters and tests get the callable name and the position
+        // as the first arguments.
+        let dispatch = |callables: &HashMap<&'static str, Callable>, args: &[Value]| -> LittleResult<Value> {
+            let name = args[0].to_string();
+            let loc = position(&args[1], &args[2], &args[3], &args[4]);
+            match callables.get(&name[..]) {
+                Some(&Callable::Dynamic(ref callable)) => callable(&args[5..])
+                    .or_else(|e| fail(e.at(vec![TraceEntry::Position { from: loc }]).into())),
+                _ => unreachable!("twig bug: {:?} can not be called at runtime", name),
+            }
+        };
+        let unary = |args: &[Value]| dispatch(&self.env.runtime.unary_operators, args);
+        let binary = |args: &[Value]| dispatch(&self.env.runtime.binary_operators, args);
+        let filter = |args: &[Value]| dispatch(&self.env.runtime.filters, args);
+        let test = |args: &[Value]| dispatch(&self.env.runtime.tests, args);
+
+        let functions: Vec<(&str, Box<Fn(&[Value]) -> LittleResult<Value>>)> = self.env.runtime.functions.iter()
+            .filter_map(|(name, callable)| match *callable {
+                Callable::Dynamic(ref callable) => Some((*name, Box::new(move |args: &[Value]| {
+                    callable(args).or_else(|e| fail(e.at(Vec::new()).into()))
+                }) as Box<Fn(&[Value]) -> LittleResult<Value>>)),
+                Callable::Static { .. } => None,
+            })
+            .collect();
+
+        let escape_value = |args: &[Value]| -> LittleResult<Value> {
+            let value = args[0].to_string();
+            Ok(Value::Str(match strategy {
+                Some(strategy) => escape(&value, strategy),
+                None => value,
+            }))
+        };
+
+        // Without arguments, dumps the whole context.
+        let context = if self.env.config.debug { data.clone() } else { Value::Null };
+        let dump = |args: &[Value]| -> LittleResult<Value> {
+            let dumped = match args {
+                [] => format!("{:?}", context),
+                args => args.iter().map(|v| format!("{:?}", v)).collect::<Vec<_>>().join(""),
+            };
+            Ok(Value::Str(dumped))
+        };
+
+        let to_bool = |args: &[Value]| -> LittleResult<Value> {
+            Ok(Value::Bool(args[0].is_truthy()))
+        };
+
+        let returned = RefCell::new(Value::Null);
+        let return_value = |args: &[Value]| -> LittleResult<Value> {
+            *returned.borrow_mut() = args[0].clone();
+            Ok(Value::Null)
+        };
+
+        let mut funs: HashMap<&str, &Function<Value>> = HashMap::new();
+        for &(name, ref function) in &functions {
+            funs.insert(name, function);
+        }
+        funs.insert("__name", &name);
+        funs.insert("__attr", &attr);
+        funs.insert("__defined", &defined);
+        funs.insert("__defined_name", &defined_name);
+        funs.insert("__filter", &filter);
+        funs.insert("__test", &test);
+        funs.insert("__array", &array);
+        funs.insert("__hash", &hash);
+        funs.insert("__unary", &unary);
+        funs.insert("__binary", &binary);
+        funs.insert("__bool", &to_bool);
+        funs.insert("__escape", &escape_value);
+        funs.insert("__return", &return_value);
+        funs.insert("__include", &include);
+        funs.insert("__block", &block);
+        funs.insert("__extends", &extends);
+        funs.insert("__import", &import);
+        funs.insert("__macro", &call_macro);
+        funs.insert("include", &include_function);
+        funs.insert("template_from_string", &template_from_string);
+        if self.env.config.debug {
+            funs.insert("dump", &dump);
+        }
 
-        let funs = HashMap::new();
         let mut i = Interpreter::new();
-        let p = match i.build("", compiled_template, &funs) {
+        let p = match i.build(&frames[frames.len() - 1].name, code.template(), &funs) {
             Ok(p) => p,
-            Err(e) => panic!("not implemented - handle build_processor error {:?}", e),
+            Err(e) => match e.downcast_ref::<BuildError>() {
+                Some(&BuildError::FunctionNotFound { ref required }) => return Err(EngineError::FunctionNotFound {
+                    name: required.clone(),
+                    template: frames[frames.len() - 1].name.clone(),
+                }.into()),
+                _ => panic!("not implemented - handle build_processor error {:?}", e),
+            },
         };
 
-        let mut res = String::new();
-        let mut interpreter = p.execute(Value::Null);
-        loop {
-            match interpreter.read_to_string(&mut res) {
-                Err(e) => {
-                    match e.description() {
-                        "interupt" => {
-                            unreachable!("unimplemented interupt handling");
-                        },
-                        e => unreachable!("unimplemented other error {:?}", e),
-                    };
-                },
-                Ok(_) => break,
+        let written = write_output(&frames[frames.len() - 1].name, &mut p.execute(data), &sink);
+        drop(functions);
+
+        match failure.into_inner() {
+            Some(e) => Err(e),
+            None => written.map(|_| returned.into_inner()),
+        }
+    }
+
+    /// Renders block of the `template`, the last one in `frames`.
+    ///
+    /// The block of the most derived template that extends it and defines
+    /// the block is rendered instead, if there is one.
+    fn render_block(&self, globals: &RenderGlobals, frames: &[Frame], template: &Arc<Program>, name: &str, data: Value, output: &mut Write)
+        -> Result<()>
+    {
+        let frame = &frames[frames.len() - 1];
+        let overridden = frame.children.iter()
+            .filter_map(|child| child.program.block(name).map(|block| (child, block)))
+            .next();
+
+        match overridden {
+            Some((child, block)) => {
+                let mut frames = frames.to_vec();
+                frames.push(Frame {
+                    name: child.name.clone(),
+                    cache_key: child.cache_key.clone(),
+                    children: frame.children.clone(),
+                });
+                self.execute(globals, &frames, &child.program, block, data, output)
+                    .map(|_| ())
+                    .map_err(|e| traced(e, vec![
+                        TraceEntry::SourceFile { target: child.name.clone() },
+                        TraceEntry::Block { name: name.into() },
+                    ]))
+            },
+            None => match template.block(name) {
+                Some(block) => self.execute(globals, frames, template, block, data, output)
+                    .map(|_| ())
+                    .map_err(|e| traced(e, vec![TraceEntry::Block { name: name.into() }])),
+                None => Err(EngineError::BlockNotFound {
+                    name: name.into(),
+                    template: frame.name.clone(),
+                }.into()),
+            },
+        }
+    }
+
+    /// Renders template `name` that the `template`, the last one in `frames`,
+    /// extends.
+    ///
+    /// The name is resolved relative to the extending template, and blocks
+    /// of extending templates replace blocks of the rendered one.
+    fn render_parent(&self, globals: &RenderGlobals, frames: &[Frame], template: &Arc<Program>, name: &str, data: Value, output: &mut Write)
+        -> Result<()>
+    {
+        let child = &frames[frames.len() - 1];
+        let name = try!(resolve_template_name(Some(&child.name), name));
+        if name == child.name || child.children.iter().any(|c| c.name == name) {
+            return Err(EngineError::ExtendsCycle { template: name }.into());
+        }
+
+        let cache_key = try!(self.sources().get_cache_key(&name));
+        let program = try!(self.get_program(&name, &cache_key));
+        self.cache().add_dependency(&child.cache_key, &cache_key);
+
+        let mut children = child.children.clone();
+        children.push(Child {
+            name: child.name.clone(),
+            cache_key: child.cache_key.clone(),
+            program: template.clone(),
+        });
+
+        let mut frames = frames.to_vec();
+        frames.push(Frame {
+            name: name.clone(),
+            cache_key: cache_key,
+            children: children,
+        });
+
+        self.execute(globals, &frames, &program, &program, data, output)
+            .map(|_| ())
+            .map_err(|e| traced(e, vec![TraceEntry::SourceFile { target: name }]))
+    }
+
+    /// Loads templates that the `template` extends, the nearest first.
+    ///
+    /// Only templates with constant parent names are followed.
+    fn load_parents(&self, template: &Child) -> Result<Vec<Child>> {
+        let mut parents: Vec<Child> = Vec::new();
+        let mut current = template.clone();
+        while let Some(parent) = current.program.parent.clone() {
+            let name = try!(resolve_template_name(Some(&current.name), &parent));
+            if name == template.name || parents.iter().any(|p| p.name == name) {
+                return Err(EngineError::ExtendsCycle { template: name }.into());
             }
+
+            let cache_key = try!(self.sources().get_cache_key(&name));
+            let program = try!(self.get_program(&name, &cache_key));
+            self.cache().add_dependency(&current.cache_key, &cache_key);
+
+            current = Child { name: name, cache_key: cache_key, program: program };
+            parents.push(current.clone());
+        }
+
+        Ok(parents)
+    }
+
+    /// Loads template imported by the last one in `frames`, and returns its
+    /// name, resolved relative to the importing template.
+    ///
+    /// Null source imports the importing template itself.
+    fn load_import(&self, frames: &[Frame], source: &Value) -> Result<String> {
+        let current = &frames[frames.len() - 1];
+        if *source == Value::Null {
+            return Ok(current.name.clone());
+        }
+
+        let name = try!(resolve_template_name(Some(&current.name), &source.to_string()));
+        let cache_key = try!(self.sources().get_cache_key(&name));
+        try!(self.get_program(&name, &cache_key));
+        self.cache().add_dependency(&current.cache_key, &cache_key);
+
+        Ok(name)
+    }
+
+    /// Renders macro of the imported template `name` with the call arguments.
+    ///
+    /// The macro gets only its arguments as the context, arguments that are
+    /// not given get default values, and extra ones are in "varargs".
+    fn render_macro(&self, globals: &RenderGlobals, parents: &[Frame], name: &str, symbol: &str, args: &[Value], output: &mut Write)
+        -> Result<()>
+    {
+        let cache_key = try!(self.sources().get_cache_key(name));
+        let program = try!(self.get_program(name, &cache_key));
+        let found = match program.find_macro(symbol) {
+            Some(found) => found,
+            None => return Err(EngineError::MacroNotFound {
+                name: symbol.into(),
+                template: name.into(),
+            }.into()),
+        };
+
+        let mut context = HashMap::new();
+        for (i, &(ref argument, ref default)) in found.arguments.iter().enumerate() {
+            let value = args.get(i).cloned().unwrap_or_else(|| default.clone());
+            context.insert(HashKey::Str(argument.clone()), value);
+        }
+        let varargs = args.iter().skip(found.arguments.len()).cloned().collect();
+        context.insert(HashKey::Str("varargs".into()), Value::Array(varargs));
+
+        let mut frames = parents.to_vec();
+        frames.push(Frame::new(name.into(), cache_key));
+
+        self.execute(globals, &frames, &program, &found.program, Value::Hash(context), output)
+            .map(|_| ())
+            .map_err(|e| traced(e, vec![
+                TraceEntry::SourceFile { target: name.into() },
+                TraceEntry::Macro { name: symbol.into() },
+            ]))
+    }
+
+    /// Renders included template for the `include` runtime call.
+    ///
+    /// Arguments are template name, current context, additional variables and
+    /// "ignore missing" flag.
+    fn render_include(&self, globals: &RenderGlobals, parents: &[Frame], args: &[Value], output: &mut Write)
+        -> Result<()>
+    {
+        let (name, context, variables, ignore_missing) = match args {
+            [ref name, ref context, ref variables, ref ignore_missing] => (name, context, variables, ignore_missing),
+            _ => unreachable!("twig bug: include expects 4 arguments"),
+        };
+
+        let name = name.to_string();
+        let data = match (context, variables) {
+            (_, &Value::Null) => context.clone(),
+            (&Value::Hash(ref context), &Value::Hash(ref variables)) => {
+                let mut merged = context.clone();
+                merged.extend(variables.clone());
+                Value::Hash(merged)
+            },
+            (_, variables) => variables.clone(),
+        };
+
+        if *ignore_missing != Value::Int(0) {
+            let resolved = try!(resolve_template_name(parents.last().map(|p| &p.name[..]), &name));
+            match self.render(globals, parents, &name, data, output) {
+                Err(Error::Engine(Caused { err: EngineError::TemplateNotFound { name: ref missing, .. }, .. }))
+                    if *missing == resolved => Ok(()),
+                other => other,
+            }
+        } else {
+            self.render(globals, parents, &name, data, output)
+        }
+    }
+
+    /// Returns compiled template from cache, or compiles and caches it.
+    fn get_program(&self, name: &str, cache_key: &str)
+        -> Result<Arc<Program>>
+    {
+        let cached = if self.env.config.auto_reload {
+            try!(self.cache().get(cache_key, &self.sources()))
+        } else {
+            self.cache().peek(cache_key)
+        };
+        if let Some(program) = cached {
+            return Ok(program);
         }
 
-        self.return_lexer(lexer);
+        let compiled_at = SystemTime::now();
+        let source = try!(self.sources().get_source(name));
 
-        Ok(res)
+        self.store_program(name, cache_key, &source, compiled_at)
     }
 
-    fn get_compiled_template<'r>(&mut self, lexer: &'r Lexer, name: &'r str)
-        -> Result<Template<Value>>
+    /// Compiles the source, or loads it from disk cache, and stores the
+    /// program in the cache.
+    fn store_program(&self, name: &str, cache_key: &str, source: &str, compiled_at: SystemTime)
+        -> Result<Arc<Program>>
     {
-        let source = try!(self.loader.get_source(name));
-        let mut tokens = lexer.tokens(&source);
+        let program = Arc::new(match self.disk_cache {
+            Some(ref disk_cache) => {
+                let path = disk_cache.path(&self.env.fingerprint, source);
+                match disk_cache.load(&path) {
+                    Some(program) => program,
+                    None => {
+                        let program = try!(self.compile_source(source));
+                        try!(disk_cache.store(&path, &program));
+                        program
+                    },
+                }
+            },
+            None => try!(self.compile_source(source)),
+        });
+
+        self.cache().insert(cache_key, name, program.clone(), compiled_at);
+
+        Ok(program)
+    }
+
+    /// Stores and compiles template created from the source string, and
+    /// returns its synthetic name.
+    ///
+    /// Compiled templates of forgotten string templates are evicted too. The
+    /// template is compiled from the given source, so it does not matter if
+    /// another thread makes it forgotten in the meantime.
+    fn add_string_template(&self, source: &str) -> Result<String> {
+        let (name, evicted) = self.strings.add(source);
+        let cached = {
+            let mut cache = self.cache();
+            for evicted in evicted {
+                cache.evict(&evicted);
+            }
+            cache.peek(&name).is_some()
+        };
+        if !cached {
+            try!(self.store_program(&name, &name, source, SystemTime::now()));
+        }
+
+        Ok(name)
+    }
+
+    fn compile_source(&self, source: &str) -> Result<Program> {
+        let mut tokens = self.lexer.tokens(source);
         let module = try!(parse(&self.env.parsing, &mut tokens));
-        Ok(try!(compile((), &module)))
+        compile(&self.env, &module)
     }
 
-    fn take_lexer(&mut self) -> Lexer {
-        let mut ninja_lexer = None;
-        mem::swap(&mut ninja_lexer, &mut self.lexer);
+    /// Returns environment globals for a new render.
+    fn globals<'r>(&'r self) -> RenderGlobals<'r> {
+        RenderGlobals::new(&self.env.runtime.globals)
+    }
+
+    /// Returns the configured output charset.
+    fn charset(&self) -> Result<Charset> {
+        match Charset::from_name(&self.env.config.charset) {
+            Some(charset) => Ok(charset),
+            None => Err(EngineError::UnsupportedCharset {
+                charset: self.env.config.charset.clone(),
+            }.into()),
+        }
+    }
+
+    /// Returns loader that also finds templates created from strings.
+    fn sources<'r>(&'r self) -> Sources<'r, L> {
+        Sources {
+            loader: &self.loader,
+            strings: &self.strings,
+        }
+    }
+
+    /// Locks the compiled template cache.
+    ///
+    /// A panic in another rendering thread can not leave the cache in an
+    /// inconsistent state, so a poisoned lock is recovered.
+    fn cache<'r>(&'r self) -> MutexGuard<'r, Cache> {
+        match self.cache.lock() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Output of executed template, shared with runtime calls that write other
+/// templates to it.
+///
+/// Interpreter output waits in `pending` until enough of it is collected,
+/// and it is always written before output of runtime calls.
+struct Sink<'w> {
+    pending: Vec<u8>,
+    writer: &'w mut Write,
+}
 
-        match ninja_lexer {
-            None => unreachable!("lexer is gone"),
-            Some(lexer) => lexer,
+impl<'w> Sink<'w> {
+    fn new(writer: &'w mut Write) -> Sink<'w> {
+        Sink {
+            pending: Vec::new(),
+            writer: writer,
         }
     }
 
-    fn return_lexer(&mut self, lexer: Lexer) {
-        let mut ninja_lexer = Some(lexer);
-        mem::swap(&mut ninja_lexer, &mut self.lexer);
+    /// Writes pending interpreter output, and returns the writer.
+    fn writer(&mut self) -> Result<&mut Write> {
+        try!(self.writer.write_all(&self.pending).map_err(output_failed));
+        self.pending.clear();
+        Ok(&mut *self.writer)
+    }
+}
+
+/// Copies interpreter output to the sink, and flushes the writer on the
+/// interrupt of the `flush` tag.
+///
+/// The interpreter buffers output until the read buffer is full, so it is
+/// read byte by byte. That way none of it is left in the interpreter when
+/// a runtime call writes to the sink or the writer is flushed.
+fn write_output<R: Read>(template: &str, stream: &mut R, sink: &RefCell<Sink>) -> Result<()> {
+    let mut buf = [0; 1];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(_) => {
+                let mut sink = sink.borrow_mut();
+                sink.pending.push(buf[0]);
+                if sink.pending.len() >= PENDING_OUTPUT_LENGTH {
+                    try!(sink.writer());
+                }
+            },
+            Err(ref e) if is_interupt(e) => {
+                try!(try!(sink.borrow_mut().writer()).flush().map_err(output_failed));
+            },
+            Err(e) => {
+                try!(sink.borrow_mut().writer());
+                return Err(EngineError::ExecutionFailed {
+                    template: template.into(),
+                    reason: e.to_string(),
+                }.into());
+            },
+        }
+    }
+
+    sink.borrow_mut().writer().map(|_| ())
+}
+
+fn is_interupt(e: &io::Error) -> bool {
+    match e.get_ref().and_then(|e| e.downcast_ref::<LittleError>()) {
+        Some(&LittleError::Interupt) => true,
+        _ => false,
+    }
+}
+
+fn output_failed(e: io::Error) -> Error {
+    EngineError::OutputFailed { reason: e.to_string() }.into()
+}
+
+/// Adds entries to the start of runtime error stack trace, as the error
+/// leaves a template, block or include.
+fn traced(e: Error, entries: Vec<TraceEntry>) -> Error {
+    match e {
+        Error::Runtime(e) => Error::Runtime(e.within(entries)),
+        other => other,
+    }
+}
+
+/// Reads source position passed to runtime calls as constants.
+fn position(line: &Value, column: &Value, start: &Value, end: &Value) -> Location {
+    let number = |value: &Value| match *value {
+        Value::Int(n) => n as usize,
+        _ => 0,
+    };
+    Location::spanning(number(line), number(column), Span::new(number(start), number(end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ self, Read };
+    use little::LittleError;
+    use error::{ Error, EngineError, ErrorKind };
+    use std::cell::RefCell;
+    use super::{ write_output, Sink };
+
+    /// Writes some output, then fails like the interpreter on a broken call.
+    struct Failing {
+        written: bool,
+    }
+
+    impl Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.written {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, LittleError::StackUnderflow));
+            }
+            self.written = true;
+            buf[0] = b'a';
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn returns_interpreter_error() {
+        let mut output = Vec::new();
+        let result = write_output("index.twig", &mut Failing { written: false }, &RefCell::new(Sink::new(&mut output)));
+
+        assert_eq!(output, b"a");
+        match result {
+            Err(Error::Engine(ref e)) => match e.err {
+                EngineError::ExecutionFailed { ref template, .. } => assert_eq!(template, "index.twig"),
+                ref other => panic!("expected execution error, got {:?}", other),
+            },
+            other => panic!("expected engine error, got {:?}", other),
+        }
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Runtime);
     }
 }
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use error::{ RuntimeResult, RuntimeError };
+use value::{ Value, HashKey };
+
+/// Returns variable from the context, or `None` if it is not defined.
+pub fn name(context: &Value, name: &str) -> RuntimeResult<Option<Value>> {
+    match *context {
+        Value::Hash(ref hash) => Ok(hash.get(&HashKey::Str(name.into())).cloned()),
+        Value::Obj(_) => attr(context, &Value::Str(name.into()), None).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Returns attribute of the value, or null if it does not exist.
+///
+/// Method arguments are given when the attribute is called, i.e. `user.name()`.
+pub fn attr(value: &Value, key: &Value, arguments: Option<&[Value]>) -> RuntimeResult<Value> {
+    find_attr(value, key, arguments).map(|found| found.unwrap_or(Value::Null))
+}
+
+/// Returns attribute of the value, or `None` if it does not exist.
+pub fn find_attr(value: &Value, key: &Value, arguments: Option<&[Value]>) -> RuntimeResult<Option<Value>> {
+    Ok(match *value {
+        Value::Hash(ref hash) => {
+            let key = hash_key(key);
+            match hash.get(&key) {
+                Some(found) => Some(found.clone()),
+                // String keys of data may contain numbers.
+                None => match key {
+                    HashKey::Int(v) => hash.get(&HashKey::Str(v.to_string())).cloned(),
+                    HashKey::Str(_) => None,
+                },
+            }
+        },
+        Value::Array(ref items) => match hash_key(key) {
+            HashKey::Int(index) if index >= 0 => items.get(index as usize).cloned(),
+            HashKey::Str(ref index) => index.parse::<usize>().ok().and_then(|i| items.get(i).cloned()),
+            _ => None,
+        },
+        Value::Obj(ref object) => {
+            let name = key.to_string();
+            let result = match arguments {
+                Some(arguments) => match object.write() {
+                    Ok(mut object) => object.call(&name, arguments),
+                    Err(poisoned) => poisoned.into_inner().call(&name, arguments),
+                },
+                None => match object.read() {
+                    Ok(object) => object.get(&name),
+                    Err(poisoned) => poisoned.into_inner().get(&name),
+                },
+            };
+            match result {
+                Ok(found) => Some(found),
+                Err(RuntimeError::ObjectHasNoProperty(_)) | Err(RuntimeError::ObjectHasNoMethod(_)) => None,
+                Err(e) => return Err(e),
+            }
+        },
+        _ => None,
+    })
+}
+
+/// Returns keys that can be read from the value, sorted.
+///
+/// Object properties can not be listed, so objects have no keys.
+pub fn keys(value: &Value) -> Vec<String> {
+    let mut keys: Vec<String> = match *value {
+        Value::Hash(ref hash) => hash.keys()
+            .map(|k| match *k {
+                HashKey::Int(v) => v.to_string(),
+                HashKey::Str(ref v) => v.clone(),
+            })
+            .collect(),
+        Value::Array(ref items) => (0..items.len()).map(|i| i.to_string()).collect(),
+        _ => Vec::new(),
+    };
+    keys.sort();
+    keys
+}
+
+/// Returns hash built from key and value pairs.
+pub fn hash(pairs: &[Value]) -> Value {
+    let mut hash = HashMap::new();
+    for pair in pairs.chunks(2) {
+        if let [ref key, ref value] = *pair {
+            hash.insert(hash_key(key), value.clone());
+        }
+    }
+    Value::Hash(hash)
+}
+
+/// Converts value to hash key, the same way as PHP converts array keys.
+pub fn hash_key(value: &Value) -> HashKey {
+    match *value {
+        Value::Null => HashKey::Str("".into()),
+        Value::Bool(v) => HashKey::Int(v as i64),
+        Value::Int(v) => HashKey::Int(v),
+        Value::Float(v) => HashKey::Int(v as i64),
+        Value::Str(ref v) => match v.parse::<i64>() {
+            Ok(i) if i.to_string() == *v => HashKey::Int(i),
+            _ => HashKey::Str(v.clone()),
+        },
+        ref other => HashKey::Str(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use value::{ Value, HashKey };
+    use super::*;
+
+    #[test]
+    fn finds_hash_values_by_numeric_string_keys() {
+        let mut data = HashMap::new();
+        data.insert(HashKey::Str("1".into()), Value::Str("one".into()));
+
+        assert_eq!(attr(&Value::Hash(data.clone()), &Value::Int(1), None).unwrap(), Value::Str("one".into()));
+        assert_eq!(attr(&Value::Hash(data), &Value::Str("1".into()), None).unwrap(), Value::Str("one".into()));
+    }
+
+    #[test]
+    fn returns_null_for_missing_attributes() {
+        let items = Value::Array(vec![Value::Int(1)]);
+
+        assert_eq!(attr(&items, &Value::Int(0), None).unwrap(), Value::Int(1));
+        assert_eq!(attr(&items, &Value::Int(1), None).unwrap(), Value::Null);
+        assert_eq!(attr(&Value::Null, &Value::Str("a".into()), None).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn lists_sorted_keys() {
+        let mut data = HashMap::new();
+        data.insert(HashKey::Str("b".into()), Value::Null);
+        data.insert(HashKey::Int(1), Value::Null);
+        data.insert(HashKey::Str("a".into()), Value::Null);
+
+        assert_eq!(keys(&Value::Hash(data)), vec!["1", "a", "b"]);
+        assert_eq!(keys(&Value::Array(vec![Value::Null, Value::Null])), vec!["0", "1"]);
+        assert!(keys(&Value::Null).is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::{ Mutex, MutexGuard };
+use std::time::SystemTime;
+use sha1::Sha1;
+use error::Result;
+use loader::Loader;
+
+/// Prefix of synthetic names given to templates created from strings.
+const NAME_PREFIX: &'static str = "__string_template__";
+
+/// Number of templates created from strings that are kept by default.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Template source with the time it was last used.
+struct Entry {
+    source: String,
+    used: u64,
+}
+
+struct Entries {
+    items: HashMap<String, Entry>,
+    /// Incremented on every use, orders entries by their last use.
+    clock: u64,
+}
+
+/// Templates created from strings, by synthetic name.
+///
+/// Only `capacity` templates are kept, the least recently used ones are
+/// forgotten when new ones are added.
+pub struct StringTemplates {
+    entries: Mutex<Entries>,
+    capacity: usize,
+}
+
+impl StringTemplates {
+    pub fn new() -> StringTemplates {
+        StringTemplates::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> StringTemplates {
+        StringTemplates {
+            entries: Mutex::new(Entries { items: HashMap::new(), clock: 0 }),
+            capacity: capacity,
+        }
+    }
+
+    /// Stores template source and returns its synthetic name, together with
+    /// names of templates that were forgotten to make room for it.
+    ///
+    /// The name is derived from the source, so the same source always gets
+    /// the same name and compiled template.
+    pub fn add(&self, source: &str) -> (String, Vec<String>) {
+        let mut hasher = Sha1::new();
+        hasher.update(source.as_bytes());
+        let name = format!("{}{}", NAME_PREFIX, hasher.hexdigest());
+
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let used = entries.clock;
+        entries.items.entry(name.clone())
+            .or_insert_with(|| Entry { source: source.into(), used: used })
+            .used = used;
+
+        let mut evicted = Vec::new();
+        while entries.items.len() > self.capacity {
+            let oldest = match entries.items.iter().min_by_key(|&(_, e)| e.used) {
+                Some((oldest, _)) => oldest.clone(),
+                None => break,
+            };
+            entries.items.remove(&oldest);
+            evicted.push(oldest);
+        }
+
+        (name, evicted)
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        if !name.starts_with(NAME_PREFIX) {
+            return None;
+        }
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let used = entries.clock;
+        entries.items.get_mut(name).map(|entry| {
+            entry.used = used;
+            entry.source.clone()
+        })
+    }
+
+    /// Checks if the template is kept, without marking it as used.
+    pub fn contains(&self, name: &str) -> bool {
+        name.starts_with(NAME_PREFIX) && self.lock().items.contains_key(name)
+    }
+
+    pub fn clear(&self) {
+        self.lock().items.clear();
+    }
+
+    fn lock<'r>(&'r self) -> MutexGuard<'r, Entries> {
+        match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Loader that finds templates created from strings, and delegates
+/// everything else to the engine loader.
+pub struct Sources<'a, L: 'a> {
+    pub loader: &'a L,
+    pub strings: &'a StringTemplates,
+}
+
+impl<'a, L: Loader> Loader for Sources<'a, L> {
+    fn get_source(&self, name: &str) -> Result<String> {
+        match self.strings.get(name) {
+            Some(source) => Ok(source),
+            None => self.loader.get_source(name),
+        }
+    }
+
+    fn get_cache_key(&self, name: &str) -> Result<String> {
+        if self.strings.contains(name) {
+            Ok(name.into())
+        } else {
+            self.loader.get_cache_key(name)
+        }
+    }
+
+    fn is_fresh(&self, name: &str, time: SystemTime) -> Result<bool> {
+        if self.strings.contains(name) {
+            // Source of string template never changes.
+            Ok(true)
+        } else {
+            self.loader.is_fresh(name, time)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StringTemplates;
+
+    #[test]
+    fn gives_same_name_to_same_source() {
+        let strings = StringTemplates::new();
+        let (a, _) = strings.add("a");
+
+        assert_eq!(strings.add("a").0, a);
+        assert!(strings.add("b").0 != a);
+        assert_eq!(strings.get(&a), Some("a".to_string()));
+    }
+
+    #[test]
+    fn forgets_least_recently_used_templates() {
+        let strings = StringTemplates::with_capacity(2);
+        let (a, _) = strings.add("a");
+        let (b, _) = strings.add("b");
+        strings.get(&a);
+        assert!(strings.contains(&b));
+
+        let (c, evicted) = strings.add("c");
+
+        assert_eq!(evicted, vec![b.clone()]);
+        assert!(strings.get(&b).is_none());
+        assert_eq!(strings.get(&a), Some("a".to_string()));
+        assert_eq!(strings.get(&c), Some("c".to_string()));
+    }
+}
@@ -1,50 +1,345 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::fmt;
+use sha1::Sha1;
 
 use extension::{ Extension, CoreExtension };
 use operator::{ Operator, OperatorKind, OperatorOptions };
-use function::Function;
-use nodes::{ TokenParser, TokenParserExtension };
+use function::{ Function, Filter, Test, Callable };
+use global::Global;
+use value::Value;
+use nodes::{ TokenParser, TokenParserExtension, NodeVisitor };
+use tokens::{ LexerOptions, Delimiters };
+use error::{ Result, EngineError };
+
+/// Disables all optimizations.
+pub const OPTIMIZE_NONE: i32 = 0;
+/// Enables all optimizations.
+pub const OPTIMIZE_ALL: i32 = -1;
+/// Merges adjacent text output into a single output.
+pub const OPTIMIZE_OUTPUT: i32 = 1;
+
+/// Escaping applied to printed values.
+#[derive(Clone)]
+pub enum Autoescape {
+    /// Printed values are not escaped.
+    Disabled,
+    /// Escapes with the named strategy: "html", "html_attr", "js", "css" or "url".
+    ///
+    /// The "filename" strategy picks one by the template file extension.
+    Strategy(String),
+    /// Returns strategy name for the template name, `None` disables escaping.
+    Callback(Arc<Fn(&str) -> Option<String> + Send + Sync>),
+}
+
+impl Autoescape {
+    /// Returns escaping strategy name for the template.
+    pub fn strategy_for(&self, template: &str) -> Option<String> {
+        match *self {
+            Autoescape::Disabled => None,
+            Autoescape::Strategy(ref strategy) if strategy == "filename" => {
+                let name = if template.ends_with(".twig") {
+                    &template[..template.len() - ".twig".len()]
+                } else {
+                    template
+                };
+                match name.rsplit('.').next() {
+                    Some("js") => Some("js".into()),
+                    Some("css") => Some("css".into()),
+                    Some("txt") => None,
+                    _ => Some("html".into()),
+                }
+            },
+            Autoescape::Strategy(ref strategy) => Some(strategy.clone()),
+            Autoescape::Callback(ref callback) => callback(template),
+        }
+    }
+}
+
+impl fmt::Debug for Autoescape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Autoescape::Disabled => write!(f, "Disabled"),
+            Autoescape::Strategy(ref strategy) => write!(f, "Strategy({:?})", strategy),
+            Autoescape::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
 
 /// Environment configuration.
+#[derive(Clone, Debug)]
 pub struct Config {
-    pub autoescape: String,
+    /// Enables the `dump` function.
+    pub debug: bool,
+    /// Fails when template reads undefined variable, instead of using null.
+    pub strict_variables: bool,
+    /// Recompiles cached templates when their source changes.
+    pub auto_reload: bool,
+    /// Charset of rendered output, "UTF-8" or "ISO-8859-1".
+    pub charset: String,
+    /// Directory for compiled templates, `None` disables the disk cache.
+    pub cache: Option<PathBuf>,
+    /// Enabled optimizations, a combination of `OPTIMIZE_*` flags.
+    pub optimizations: i32,
+    pub autoescape: Autoescape,
+    /// Delimiters of tags and comments, and whitespace control.
+    pub lexer: LexerOptions,
 }
 
 impl Config {
     pub fn default() -> Config {
         Config {
-            autoescape: "html".into()
+            debug: false,
+            strict_variables: false,
+            auto_reload: true,
+            charset: "UTF-8".into(),
+            cache: None,
+            optimizations: OPTIMIZE_ALL,
+            autoescape: Autoescape::Strategy("html".into()),
+            lexer: LexerOptions::default(),
         }
     }
 
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+
+    /// Creates configuration from string values, i.e. from fixture files.
+    ///
+    /// Boolean options accept "true", "false", "1", "0" and empty string.
+    /// The "false" autoescape value disables escaping. Delimiters are given
+    /// as start and end separated by whitespace, i.e. "[[ ]]".
     pub fn from_hashmap(map: HashMap<String, String>) -> Config {
-        let default = Config::default();
+        let mut builder = ConfigBuilder::new();
 
-        Config {
-            autoescape: map.get("autoescape").cloned().unwrap_or(default.autoescape),
+        if let Some(v) = map.get("debug") {
+            builder = builder.debug(parse_bool(v));
+        }
+        if let Some(v) = map.get("strict_variables") {
+            builder = builder.strict_variables(parse_bool(v));
+        }
+        if let Some(v) = map.get("auto_reload") {
+            builder = builder.auto_reload(parse_bool(v));
+        }
+        if let Some(v) = map.get("charset") {
+            builder = builder.charset(&v[..]);
+        }
+        if let Some(dir) = map.get("cache") {
+            if dir.len() > 0 && dir != "false" {
+                builder = builder.cache(dir);
+            }
+        }
+        if let Some(v) = map.get("optimizations") {
+            if let Ok(v) = v.trim().parse() {
+                builder = builder.optimizations(v);
+            }
+        }
+        if let Some(v) = map.get("autoescape") {
+            builder = match &v[..] {
+                "" | "false" | "0" => builder.autoescape(Autoescape::Disabled),
+                "true" | "1" => builder.autoescape(Autoescape::Strategy("html".into())),
+                strategy => builder.autoescape(Autoescape::Strategy(strategy.into())),
+            };
+        }
+        if let Some(v) = map.get("whitespace_trim") {
+            builder = builder.whitespace_trim(v.trim());
+        }
+        if let Some(v) = map.get("whitespace_line_trim") {
+            builder = builder.whitespace_line_trim(v.trim());
+        }
+        if let Some(v) = map.get("trim_blocks") {
+            builder = builder.trim_blocks(parse_bool(v));
+        }
+        if let Some(v) = map.get("lstrip_blocks") {
+            builder = builder.lstrip_blocks(parse_bool(v));
+        }
+        if let Some(v) = map.get("line_statement_prefix") {
+            builder = builder.line_statement_prefix(v.trim());
+        }
+        if let Some(v) = map.get("line_comment_prefix") {
+            builder = builder.line_comment_prefix(v.trim());
+        }
+        if let Some(delimiters) = map.get("tag_comment").and_then(|v| parse_delimiters(v)) {
+            builder.config.lexer.tag_comment = delimiters;
+        }
+        if let Some(delimiters) = map.get("tag_block").and_then(|v| parse_delimiters(v)) {
+            builder.config.lexer.tag_block = delimiters;
+        }
+        if let Some(delimiters) = map.get("tag_variable").and_then(|v| parse_delimiters(v)) {
+            builder.config.lexer.tag_variable = delimiters;
+        }
+
+        builder.build()
+    }
+}
+
+fn parse_bool(value: &str) -> bool {
+    match value.trim() {
+        "" | "0" | "false" => false,
+        _ => true,
+    }
+}
+
+fn parse_delimiters(value: &str) -> Option<Delimiters> {
+    let parts: Vec<_> = value.split_whitespace().collect();
+    match parts[..] {
+        [start, end] => Some(Delimiters::new(start, end)),
+        _ => None,
+    }
+}
+
+/// Builds environment configuration.
+///
+/// Options that are not set keep default values.
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Config::default(),
         }
     }
+
+    pub fn debug(mut self, debug: bool) -> ConfigBuilder {
+        self.config.debug = debug;
+        self
+    }
+
+    pub fn strict_variables(mut self, strict_variables: bool) -> ConfigBuilder {
+        self.config.strict_variables = strict_variables;
+        self
+    }
+
+    pub fn auto_reload(mut self, auto_reload: bool) -> ConfigBuilder {
+        self.config.auto_reload = auto_reload;
+        self
+    }
+
+    pub fn charset<S: Into<String>>(mut self, charset: S) -> ConfigBuilder {
+        self.config.charset = charset.into();
+        self
+    }
+
+    pub fn cache<P: Into<PathBuf>>(mut self, dir: P) -> ConfigBuilder {
+        self.config.cache = Some(dir.into());
+        self
+    }
+
+    pub fn optimizations(mut self, optimizations: i32) -> ConfigBuilder {
+        self.config.optimizations = optimizations;
+        self
+    }
+
+    pub fn autoescape(mut self, autoescape: Autoescape) -> ConfigBuilder {
+        self.config.autoescape = autoescape;
+        self
+    }
+
+    /// Sets delimiters of comments, `{# #}` by default.
+    pub fn tag_comment<S: Into<String>, E: Into<String>>(mut self, start: S, end: E) -> ConfigBuilder {
+        self.config.lexer.tag_comment = Delimiters::new(start, end);
+        self
+    }
+
+    /// Sets delimiters of tags, `{% %}` by default.
+    pub fn tag_block<S: Into<String>, E: Into<String>>(mut self, start: S, end: E) -> ConfigBuilder {
+        self.config.lexer.tag_block = Delimiters::new(start, end);
+        self
+    }
+
+    /// Sets delimiters of print statements, `{{ }}` by default.
+    pub fn tag_variable<S: Into<String>, E: Into<String>>(mut self, start: S, end: E) -> ConfigBuilder {
+        self.config.lexer.tag_variable = Delimiters::new(start, end);
+        self
+    }
+
+    /// Sets whitespace control modifier, `-` by default.
+    pub fn whitespace_trim<S: Into<String>>(mut self, whitespace_trim: S) -> ConfigBuilder {
+        self.config.lexer.whitespace_trim = whitespace_trim.into();
+        self
+    }
+
+    /// Sets whitespace control modifier that keeps newlines, `~` by default.
+    pub fn whitespace_line_trim<S: Into<String>>(mut self, whitespace_line_trim: S) -> ConfigBuilder {
+        self.config.lexer.whitespace_line_trim = whitespace_line_trim.into();
+        self
+    }
+
+    /// Removes the first newline after a block tag or comment, enabled by default.
+    pub fn trim_blocks(mut self, trim_blocks: bool) -> ConfigBuilder {
+        self.config.lexer.trim_blocks = trim_blocks;
+        self
+    }
+
+    /// Strips spaces and tabs from the start of a line up to a block tag
+    /// or comment, disabled by default.
+    pub fn lstrip_blocks(mut self, lstrip_blocks: bool) -> ConfigBuilder {
+        self.config.lexer.lstrip_blocks = lstrip_blocks;
+        self
+    }
+
+    /// Enables line statements, lines starting with the prefix are lexed
+    /// as block tags.
+    pub fn line_statement_prefix<S: Into<String>>(mut self, prefix: S) -> ConfigBuilder {
+        self.config.lexer.line_statement_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Enables line comments, lines starting with the prefix are removed.
+    pub fn line_comment_prefix<S: Into<String>>(mut self, prefix: S) -> ConfigBuilder {
+        self.config.lexer.line_comment_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets a callback that returns escaping strategy for the template name.
+    pub fn autoescape_callback<F: 'static>(self, callback: F) -> ConfigBuilder
+        where F: Fn(&str) -> Option<String> + Send + Sync
+    {
+        self.autoescape(Autoescape::Callback(Arc::new(callback)))
+    }
+
+    pub fn build(self) -> Config {
+        self.config
+    }
 }
 
 /// Project configuration container.
+///
+/// Items pushed directly to the environment are added after the items of
+/// extensions, so they replace extension items with the same name.
 #[derive(Debug)]
 pub struct Environment {
+    pub config: Config,
+    pub extensions: Vec<Box<Extension>>,
     pub operators: Vec<Operator>,
     pub token_parsers: Vec<TokenParser>,
     pub functions: Vec<Function>,
+    pub filters: Vec<Filter>,
+    pub tests: Vec<Test>,
+    pub globals: HashMap<String, Global>,
+    pub node_visitors: Vec<Box<NodeVisitor>>,
 }
 
 impl Environment {
 
     pub fn new(config: Config) -> Environment {
         let mut staged = Environment {
+            config: config,
+            extensions: Vec::new(),
             operators: Vec::new(),
             token_parsers: Vec::new(),
             functions: Vec::new(),
+            filters: Vec::new(),
+            tests: Vec::new(),
+            globals: HashMap::new(),
+            node_visitors: Vec::new(),
         };
 
-        CoreExtension::apply(&mut staged);
+        staged.add_extension(Box::new(CoreExtension));
 
         staged
     }
@@ -53,41 +348,226 @@ impl Environment {
         Environment::new(Config::default())
     }
 
+    /// Registers extension.
+    ///
+    /// Extensions are initialized in registration order, except that
+    /// dependencies of an extension are initialized before it.
+    pub fn add_extension(&mut self, extension: Box<Extension>) {
+        self.extensions.push(extension);
+    }
+
+    /// Returns true if extension with the name is registered.
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|e| e.name() == name)
+    }
+
+    /// Applies extensions and creates environment used by the engine.
+    ///
+    /// Panics if extension dependencies can not be resolved, use
+    /// `try_init_all` to get the error instead.
     pub fn init_all(self) -> CompiledEnvironment {
-        CompiledEnvironment {
-            lexing: LexingEnvironment {
-                operators: {
-                    self.operators.iter()
-                        .filter_map(|i| match i.options.kind {
-                            OperatorKind::Unary { value, .. } => Some(value),
-                            OperatorKind::Binary { value, .. } => Some(value),
-                            OperatorKind::Other => None,
-                        })
-                        .collect()
-                },
+        match self.try_init_all() {
+            Ok(env) => env,
+            Err(e) => panic!("failed to initialize environment: {}", e),
+        }
+    }
+
+    /// Applies extensions and creates environment used by the engine.
+    ///
+    /// Fails if an extension depends on extension that is not registered,
+    /// if dependencies are circular, or if configured delimiters are not valid.
+    pub fn try_init_all(mut self) -> Result<CompiledEnvironment> {
+        try!(self.config.lexer.validate());
+        try!(self.apply_extensions());
+        let fingerprint = self.fingerprint();
+
+        let lexing = LexingEnvironment {
+            options: self.config.lexer.clone(),
+            operators: {
+                self.operators.iter()
+                    .filter_map(|i| match i.options.kind {
+                        OperatorKind::Unary { value, .. } => Some(value),
+                        OperatorKind::Binary { value, .. } => Some(value),
+                        OperatorKind::Other => None,
+                    })
+                    .collect()
             },
-            parsing: ParsingEnvironment {
-                operators: {
-                    self.operators.into_iter()
-                        .filter_map(|i| match i.options.kind {
-                            OperatorKind::Unary { value, .. } => Some((value, i.options)),
-                            OperatorKind::Binary { value, .. } => Some((value, i.options)),
-                            OperatorKind::Other => None,
-                        })
-                        .collect()
+        };
+
+        let mut operators = HashMap::new();
+        let mut runtime = RuntimeEnvironment {
+            unary_operators: HashMap::new(),
+            binary_operators: HashMap::new(),
+            functions: HashMap::new(),
+            filters: HashMap::new(),
+            tests: HashMap::new(),
+            globals: self.globals,
+        };
+        for op in self.operators {
+            match op.options.kind {
+                OperatorKind::Unary { value, .. } => {
+                    operators.insert(value, op.options);
+                    runtime.unary_operators.insert(value, op.callable);
                 },
+                OperatorKind::Binary { value, .. } => {
+                    operators.insert(value, op.options);
+                    runtime.binary_operators.insert(value, op.callable);
+                },
+                OperatorKind::Other => (),
+            }
+        }
+
+        let mut functions = HashSet::new();
+        for function in self.functions {
+            functions.insert(function.name);
+            runtime.functions.insert(function.name, function.callable);
+        }
+        let mut filters = HashSet::new();
+        for filter in self.filters {
+            filters.insert(filter.name);
+            runtime.filters.insert(filter.name, filter.callable);
+        }
+        let mut tests = HashSet::new();
+        for test in self.tests {
+            tests.insert(test.name);
+            runtime.tests.insert(test.name, test.callable);
+        }
+
+        let mut node_visitors = self.node_visitors;
+        node_visitors.sort_by_key(|v| v.priority());
+
+        Ok(CompiledEnvironment {
+            config: self.config,
+            fingerprint: fingerprint,
+            lexing: lexing,
+            parsing: ParsingEnvironment {
+                operators: operators,
                 handlers: {
                     self.token_parsers.into_iter()
                         .map(|i| (i.tag, i.extension))
                         .collect()
                 },
-                functions: {
-                    self.functions.iter()
-                        .map(|f| f.name)
-                        .collect()
-                }
+                functions: functions,
+                filters: filters,
+                tests: tests,
+                node_visitors: node_visitors,
             },
+            runtime: runtime,
+        })
+    }
+
+    /// Adds items of all extensions, in initialization order.
+    fn apply_extensions(&mut self) -> Result<()> {
+        let order = try!(self.extension_order());
+
+        let mut operators = Vec::new();
+        let mut token_parsers = Vec::new();
+        let mut functions = Vec::new();
+        let mut filters = Vec::new();
+        let mut tests = Vec::new();
+        let mut globals = HashMap::new();
+        let mut node_visitors = Vec::new();
+        for index in order {
+            let extension = &self.extensions[index];
+            operators.extend(extension.operators());
+            token_parsers.extend(extension.token_parsers());
+            functions.extend(extension.functions());
+            filters.extend(extension.filters());
+            tests.extend(extension.tests());
+            globals.extend(extension.globals());
+            node_visitors.extend(extension.node_visitors());
+        }
+
+        operators.extend(self.operators.drain(..));
+        token_parsers.extend(self.token_parsers.drain(..));
+        functions.extend(self.functions.drain(..));
+        filters.extend(self.filters.drain(..));
+        tests.extend(self.tests.drain(..));
+        globals.extend(self.globals.drain());
+        node_visitors.extend(self.node_visitors.drain(..));
+
+        self.operators = operators;
+        self.token_parsers = token_parsers;
+        self.functions = functions;
+        self.filters = filters;
+        self.tests = tests;
+        self.globals = globals;
+        self.node_visitors = node_visitors;
+
+        Ok(())
+    }
+
+    /// Returns extension indices in initialization order.
+    fn extension_order(&self) -> Result<Vec<usize>> {
+        let mut order = Vec::new();
+        let mut visiting = Vec::new();
+        for index in 0..self.extensions.len() {
+            try!(self.visit_extension(index, &mut visiting, &mut order));
+        }
+        Ok(order)
+    }
+
+    fn visit_extension(&self, index: usize, visiting: &mut Vec<usize>, order: &mut Vec<usize>) -> Result<()> {
+        if order.contains(&index) {
+            return Ok(());
+        }
+        let extension = &self.extensions[index];
+        if visiting.contains(&index) {
+            return Err(EngineError::ExtensionDependencyCycle {
+                extension: extension.name().into(),
+            }.into());
+        }
+
+        visiting.push(index);
+        for dependency in extension.dependencies() {
+            match self.extensions.iter().position(|e| e.name() == dependency) {
+                Some(dependency) => try!(self.visit_extension(dependency, visiting, order)),
+                None => return Err(EngineError::ExtensionDependencyMissing {
+                    extension: extension.name().into(),
+                    dependency: dependency.into(),
+                }.into()),
+            }
+        }
+        visiting.pop();
+
+        order.push(index);
+        Ok(())
+    }
+
+    /// Returns hex digest of everything that affects how templates are compiled.
+    ///
+    /// Compiled templates stored on disk are only valid for the environment
+    /// with the same fingerprint.
+    pub fn fingerprint(&self) -> String {
+        let mut items = Vec::new();
+        for op in &self.operators {
+            items.push(format!("op:{:?}:{:?}", op.options.kind, op.options.precedence));
+        }
+        for parser in &self.token_parsers {
+            items.push(format!("tag:{}", parser.tag));
         }
+        for function in &self.functions {
+            items.push(format!("fn:{}", function.name));
+        }
+        for filter in &self.filters {
+            items.push(format!("filter:{}", filter.name));
+        }
+        for test in &self.tests {
+            items.push(format!("test:{}", test.name));
+        }
+        for visitor in &self.node_visitors {
+            items.push(format!("visitor:{}:{}:{}", visitor.name(), visitor.version(), visitor.priority()));
+        }
+        items.push(format!("optimizations:{}", self.config.optimizations));
+        items.push(format!("lexer:{:?}", self.config.lexer));
+        items.sort();
+
+        let mut hasher = Sha1::new();
+        for item in items {
+            hasher.update(item.as_bytes());
+            hasher.update(b"\n");
+        }
+        hasher.hexdigest()
     }
 
     pub fn push_operators<I: IntoIterator<Item=Operator>>(&mut self, ops: I) {
@@ -101,9 +581,37 @@ impl Environment {
     pub fn push_functions<I: IntoIterator<Item=Function>>(&mut self, funs: I) {
         self.functions.extend(funs);
     }
+
+    pub fn push_filters<I: IntoIterator<Item=Filter>>(&mut self, filters: I) {
+        self.filters.extend(filters);
+    }
+
+    pub fn push_tests<I: IntoIterator<Item=Test>>(&mut self, tests: I) {
+        self.tests.extend(tests);
+    }
+
+    pub fn push_node_visitors<I: IntoIterator<Item=Box<NodeVisitor>>>(&mut self, visitors: I) {
+        self.node_visitors.extend(visitors);
+    }
+
+    /// Adds a global variable visible in all templates.
+    ///
+    /// Variables passed to the template shadow globals with the same name.
+    pub fn add_global<S: Into<String>, V: Into<Value>>(&mut self, name: S, value: V) {
+        self.globals.insert(name.into(), Global::Value(value.into()));
+    }
+
+    /// Adds a global variable computed on first use, at most once per render.
+    pub fn add_lazy_global<S: Into<String>, F: 'static>(&mut self, name: S, compute: F)
+        where F: Fn() -> Value + Send + Sync
+    {
+        self.globals.insert(name.into(), Global::Lazy(Box::new(compute)));
+    }
 }
 
 pub struct LexingEnvironment {
+    /// Delimiters and whitespace control from the configuration.
+    pub options: LexerOptions,
     pub operators: HashSet<&'static str>,
 }
 
@@ -111,12 +619,30 @@ pub struct ParsingEnvironment {
     pub operators: HashMap<&'static str, OperatorOptions>,
     pub handlers: HashMap<&'static str, Box<TokenParserExtension>>,
     pub functions: HashSet<&'static str>,
+    pub filters: HashSet<&'static str>,
+    pub tests: HashSet<&'static str>,
+    /// Node visitors of all extensions, sorted by priority.
+    pub node_visitors: Vec<Box<NodeVisitor>>,
+}
+
+/// Callables and values used when templates are executed.
+pub struct RuntimeEnvironment {
+    pub unary_operators: HashMap<&'static str, Callable>,
+    pub binary_operators: HashMap<&'static str, Callable>,
+    pub functions: HashMap<&'static str, Callable>,
+    pub filters: HashMap<&'static str, Callable>,
+    pub tests: HashMap<&'static str, Callable>,
+    pub globals: HashMap<String, Global>,
 }
 
 /// Project configuration container with all extensions applied.
 pub struct CompiledEnvironment {
+    pub config: Config,
+    /// Environment fingerprint, see `Environment::fingerprint`.
+    pub fingerprint: String,
     pub lexing: LexingEnvironment,
     pub parsing: ParsingEnvironment,
+    pub runtime: RuntimeEnvironment,
 }
 
 impl CompiledEnvironment {
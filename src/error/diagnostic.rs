@@ -0,0 +1,246 @@
+use std::cmp;
+use error::{ Error, Location, RuntimeError, TraceEntry };
+
+/// Output style of rendered diagnostic.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Style {
+    /// Plain text, for logs and test runners.
+    Plain,
+    /// Text colored with ANSI escape codes, for terminals.
+    Ansi,
+}
+
+/// Error message pinned to template source, rendered with source snippet.
+///
+/// ```text
+/// error: Unknown "nope" filter
+///  --> index.twig:2:6
+///   |
+/// 1 | {{ a }}
+/// 2 | {{ b|nope }}
+///   |      ^^^^
+/// ```
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub template: String,
+    pub message: String,
+    pub loc: Location,
+    /// Additional information about the error.
+    pub notes: Vec<String>,
+    /// Suggestions to fix the error.
+    pub help: Vec<String>,
+    /// Number of source lines displayed before the error line.
+    pub context: usize,
+}
+
+impl Diagnostic {
+    pub fn new<T: Into<String>, M: Into<String>>(template: T, message: M, loc: Location) -> Diagnostic {
+        Diagnostic {
+            template: template.into(),
+            message: message.into(),
+            loc: loc,
+            notes: Vec::new(),
+            help: Vec::new(),
+            context: 2,
+        }
+    }
+
+    pub fn with_note<N: Into<String>>(mut self, note: N) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help<H: Into<String>>(mut self, help: H) -> Diagnostic {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Renders the diagnostic for the source of the template it points to.
+    ///
+    /// Source lines are found by byte span, or by line when the span is not
+    /// known, in which case carets are not displayed.
+    pub fn render(&self, source: &str, style: Style) -> String {
+        let paint = Paint { style: style };
+        let lines: Vec<(usize, &str)> = line_starts(source).into_iter()
+            .map(|start| (start, line_at(source, start)))
+            .collect();
+
+        let (first, last) = match self.loc.span {
+            Some(span) => (line_index(&lines, span.start), line_index(&lines, span.end.saturating_sub(1).max(span.start))),
+            None => {
+                let index = cmp::min(self.loc.line.saturating_sub(1), lines.len() - 1);
+                (index, index)
+            },
+        };
+        let shown_from = first.saturating_sub(self.context);
+        let width = (last + 1).to_string().len();
+        let gutter = " ".repeat(width);
+
+        let mut out = format!("{}: {}\n", paint.error("error"), paint.bold(&self.message));
+        out.push_str(&format!("{}{} {}:{}", gutter, paint.blue("-->"), self.template, self.loc.line));
+        if let Some(column) = self.loc.column {
+            out.push_str(&format!(":{}", column));
+        }
+        out.push_str(&format!("\n{} {}\n", gutter, paint.blue("|")));
+
+        for index in shown_from .. last + 1 {
+            let (start, text) = lines[index];
+            let number = format!("{:>1$}", index + 1, width);
+            out.push_str(&format!("{} {} {}\n", paint.blue(&number), paint.blue("|"), text));
+            if let Some(span) = self.loc.span {
+                if index >= first {
+                    let from = cmp::min(cmp::max(span.start, start) - start, text.len());
+                    let to = cmp::min(span.end.saturating_sub(start), text.len());
+                    let (padding, carets) = marker(text, from, to);
+                    out.push_str(&format!("{} {} {}{}\n", gutter, paint.blue("|"), padding, paint.error(&carets)));
+                }
+            }
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("{} {} {}: {}\n", gutter, paint.blue("="), paint.bold("note"), note));
+        }
+        for help in &self.help {
+            out.push_str(&format!("{} {} {}: {}\n", gutter, paint.blue("="), paint.bold("help"), help));
+        }
+
+        out
+    }
+}
+
+impl Error {
+    /// Returns diagnostic for template syntax and runtime errors that point to
+    /// the source of specified template.
+    ///
+    /// Runtime errors point to the last position in their stack trace, in
+    /// the template of the source file entry before it, which may be a
+    /// template included by the specified one.
+    pub fn diagnostic(&self, template: &str) -> Option<Diagnostic> {
+        match *self {
+            Error::Template(ref e) => Some(Diagnostic::new(template, e.err.to_string(), e.loc)),
+            Error::Engine(_) => None,
+            Error::Runtime(ref e) => {
+                let (index, loc) = match e.stack_trace.iter().enumerate().rev()
+                    .filter_map(|(i, entry)| match *entry {
+                        TraceEntry::Position { from } => Some((i, from)),
+                        _ => None,
+                    })
+                    .next()
+                {
+                    Some(found) => found,
+                    None => return None,
+                };
+                let template = e.stack_trace[.. index].iter().rev()
+                    .filter_map(|entry| match *entry {
+                        TraceEntry::SourceFile { ref target } => Some(&target[..]),
+                        _ => None,
+                    })
+                    .next()
+                    .unwrap_or(template);
+                let diagnostic = Diagnostic::new(template, e.message.to_string(), loc);
+                match e.message {
+                    RuntimeError::UndefinedVariable { ref name, ref available, .. }
+                    | RuntimeError::UndefinedAttribute { ref name, ref available, .. } => {
+                        match suggest(name, available) {
+                            Some(similar) => Some(diagnostic.with_help(format!("did you mean {:?}?", similar))),
+                            None => Some(diagnostic),
+                        }
+                    },
+                    _ => Some(diagnostic),
+                }
+            },
+        }
+    }
+}
+
+/// Finds the candidate closest to misspelled name, if it is close enough.
+pub fn suggest<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let max_distance = cmp::max(1, name.chars().count() / 3);
+    candidates.iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|&(d, _)| d <= max_distance)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, candidate)| &candidate[..])
+}
+
+/// Edit distance between strings, counting swapped neighbour characters as
+/// one edit.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0 .. a.len() + 1 {
+        d[i][0] = i;
+    }
+    for j in 0 .. b.len() + 1 {
+        d[0][j] = j;
+    }
+    for i in 1 .. a.len() + 1 {
+        for j in 1 .. b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = cmp::min(d[i - 1][j - 1] + cost, cmp::min(d[i - 1][j], d[i][j - 1]) + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = cmp::min(d[i][j], d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+fn line_starts(source: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+    starts
+}
+
+fn line_at(source: &str, start: usize) -> &str {
+    let rest = &source[start ..];
+    let line = match rest.find('\n') {
+        Some(end) => &rest[.. end],
+        None => rest,
+    };
+    line.trim_right_matches('\r')
+}
+
+fn line_index(lines: &[(usize, &str)], offset: usize) -> usize {
+    match lines.iter().rposition(|&(start, _)| start <= offset) {
+        Some(index) => index,
+        None => 0,
+    }
+}
+
+/// Returns padding and carets under `from..to` bytes of the line, at least
+/// one caret.
+fn marker(line: &str, from: usize, to: usize) -> (String, String) {
+    // tabs are kept so carets line up with the source
+    let padding = line[.. from].chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = cmp::max(1, line[from .. cmp::max(from, to)].chars().count());
+    (padding, "^".repeat(width))
+}
+
+struct Paint {
+    style: Style,
+}
+
+impl Paint {
+    fn paint(&self, code: &str, text: &str) -> String {
+        match self.style {
+            Style::Plain => text.to_string(),
+            Style::Ansi => format!("\x1b[{}m{}\x1b[0m", code, text),
+        }
+    }
+
+    fn error(&self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+
+    fn blue(&self, text: &str) -> String {
+        self.paint("1;34", text)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+}
@@ -1,13 +1,52 @@
 use std::fmt;
 use std::path::PathBuf;
-use error::{ Error, Caused };
+use std::error::Error as StdError;
+use error::{ Error, ErrorKind, Caused };
 
 #[derive(Clone, Debug)]
 pub enum EngineError {
     TemplateNotFound { name: String, search_paths: Vec<PathBuf> },
+    TemplateOutsideOfRoot { name: String, from: Option<String> },
+    CacheWriteFailed { path: PathBuf, reason: String },
+    OutputFailed { reason: String },
+    /// Interpreter failed while running the compiled template.
+    ExecutionFailed { template: String, reason: String },
+    BlockNotFound { name: String, template: String },
+    MacroNotFound { name: String, template: String },
+    /// Template extends itself through its parents.
+    ExtendsCycle { template: String },
+    FunctionNotFound { name: String, template: String },
+    UnsupportedCharset { charset: String },
+    UnknownEscapeStrategy { strategy: String, template: String },
+    ExtensionDependencyMissing { extension: String, dependency: String },
+    ExtensionDependencyCycle { extension: String },
+    /// Lexer delimiter is empty.
+    EmptyDelimiter { name: String },
+    /// Tag start delimiter or whitespace modifier is the same as, or a prefix of, another one.
+    DelimiterConflict { name: String, value: String, other: String, other_value: String },
 }
 
 impl EngineError {
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            EngineError::TemplateNotFound { .. }
+            | EngineError::TemplateOutsideOfRoot { .. }
+            | EngineError::BlockNotFound { .. }
+            | EngineError::MacroNotFound { .. } => ErrorKind::NotFound,
+            EngineError::FunctionNotFound { .. } => ErrorKind::Syntax,
+            EngineError::ExtendsCycle { .. } => ErrorKind::Runtime,
+            EngineError::ExecutionFailed { .. } => ErrorKind::Runtime,
+            EngineError::CacheWriteFailed { .. }
+            | EngineError::OutputFailed { .. } => ErrorKind::Io,
+            EngineError::UnsupportedCharset { .. }
+            | EngineError::UnknownEscapeStrategy { .. }
+            | EngineError::ExtensionDependencyMissing { .. }
+            | EngineError::ExtensionDependencyCycle { .. }
+            | EngineError::EmptyDelimiter { .. }
+            | EngineError::DelimiterConflict { .. } => ErrorKind::Config,
+        }
+    }
+
     pub fn caused_by<I: Into<Error>>(self, cause: I) -> Caused<EngineError> {
         Caused::new(self, Some(cause.into()))
     }
@@ -23,11 +62,59 @@ impl fmt::Display for EngineError {
                     try!(write!(f, "Template \"{}\" was not found, looked in ", name));
                     write!(f, "{:?}", search_paths)
                 }
-            }
+            },
+            EngineError::TemplateOutsideOfRoot { ref name, ref from } => {
+                match *from {
+                    Some(ref from) => write!(f, "Template \"{}\" referenced from \"{}\" points outside of the template root", name, from),
+                    None => write!(f, "Template \"{}\" points outside of the template root", name),
+                }
+            },
+            EngineError::CacheWriteFailed { ref path, ref reason } => {
+                write!(f, "Failed to write compiled template to \"{}\": {}", path.display(), reason)
+            },
+            EngineError::OutputFailed { ref reason } => {
+                write!(f, "Failed to write template output: {}", reason)
+            },
+            EngineError::ExecutionFailed { ref template, ref reason } => {
+                write!(f, "Failed to execute template \"{}\": {}", template, reason)
+            },
+            EngineError::BlockNotFound { ref name, ref template } => {
+                write!(f, "Block \"{}\" is not defined in template \"{}\"", name, template)
+            },
+            EngineError::MacroNotFound { ref name, ref template } => {
+                write!(f, "Macro \"{}\" is not defined in template \"{}\"", name, template)
+            },
+            EngineError::ExtendsCycle { ref template } => {
+                write!(f, "Template \"{}\" extends itself", template)
+            },
+            EngineError::FunctionNotFound { ref name, ref template } => {
+                write!(f, "Unknown function \"{}\" called in template \"{}\"", name, template)
+            },
+            EngineError::UnsupportedCharset { ref charset } => {
+                write!(f, "Output charset \"{}\" is not supported", charset)
+            },
+            EngineError::UnknownEscapeStrategy { ref strategy, ref template } => {
+                write!(f, "Unknown escaping strategy \"{}\" for template \"{}\"", strategy, template)
+            },
+            EngineError::ExtensionDependencyMissing { ref extension, ref dependency } => {
+                write!(f, "Extension \"{}\" depends on extension \"{}\" that is not registered", extension, dependency)
+            },
+            EngineError::ExtensionDependencyCycle { ref extension } => {
+                write!(f, "Extension \"{}\" depends on itself through its dependencies", extension)
+            },
+            EngineError::EmptyDelimiter { ref name } => {
+                write!(f, "Delimiter \"{}\" is empty", name)
+            },
+            EngineError::DelimiterConflict { ref name, ref value, ref other, ref other_value } => {
+                let relation = if value == other_value { "the same as" } else if other_value.starts_with(&value[..]) { "a prefix of" } else { "prefixed by" };
+                write!(f, "Delimiter \"{}\" ({:?}) is {} delimiter \"{}\" ({:?})", name, value, relation, other, other_value)
+            },
         }
     }
 }
 
+impl StdError for EngineError {}
+
 impl From<EngineError> for Error {
     fn from(inner: EngineError) -> Error {
         Error::Engine(Caused::new(inner, None))
@@ -1,13 +1,17 @@
 mod template;
 mod runtime;
 mod engine;
+mod diagnostic;
 
 use std::fmt;
 use std::result;
+use std::error::Error as StdError;
+use tokens::Span;
 
 pub use self::template::{ TemplateError, Received };
-pub use self::runtime::{ RuntimeError, TracedRuntimeError, CastTarget, CastError };
+pub use self::runtime::{ RuntimeError, TracedRuntimeError, TraceEntry, CastTarget, CastError };
 pub use self::engine::{ EngineError };
+pub use self::diagnostic::{ Diagnostic, Style, suggest };
 
 #[derive(Clone, Debug)]
 pub enum Error {
@@ -19,7 +23,26 @@ pub enum Error {
     Runtime(TracedRuntimeError),
 }
 
-pub trait ExtensionError: fmt::Display {
+/// Kind of error, for telling errors apart without matching every variant.
+///
+/// Kinds are stable, new error variants get one of the existing kinds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Template or its block does not exist.
+    NotFound,
+    /// Template source is not valid, or calls unknown function.
+    Syntax,
+    /// Template failed while rendering.
+    Runtime,
+    /// Value could not be converted to the type required while rendering.
+    Cast,
+    /// Compiled template or output could not be written.
+    Io,
+    /// Engine or its extensions are configured incorrectly.
+    Config,
+}
+
+pub trait ExtensionError: StdError {
     fn boxed_clone(&self) -> Box<ExtensionError>;
 }
 
@@ -29,9 +52,13 @@ impl Clone for Box<ExtensionError> {
     }
 }
 
-impl fmt::Debug for Box<ExtensionError> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        (&**self).fmt(f)
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            Error::Template(ref e) => e.err.kind(),
+            Error::Engine(ref e) => e.err.kind(),
+            Error::Runtime(ref e) => e.message.kind(),
+        }
     }
 }
 
@@ -45,6 +72,18 @@ impl fmt::Display for Error {
     }
 }
 
+/// Error is displayed as the wrapped error, so the source is the source of
+/// the wrapped error.
+impl StdError for Error {
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        match *self {
+            Error::Template(ref e) => e.source(),
+            Error::Engine(ref e) => e.source(),
+            Error::Runtime(ref e) => e.source(),
+        }
+    }
+}
+
 /// Adds optional cause to error.
 #[derive(Clone, Debug)]
 pub struct Caused<E: fmt::Display> {
@@ -72,6 +111,15 @@ impl<E: fmt::Display> fmt::Display for Caused<E> {
     }
 }
 
+impl<E: StdError> StdError for Caused<E> {
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        match *self.cause {
+            Some(ref cause) => Some(cause),
+            None => None,
+        }
+    }
+}
+
 /// Pins any error type to source file location.
 #[derive(Copy, Clone, Debug)]
 pub struct At<E: fmt::Display> {
@@ -119,15 +167,32 @@ impl<E: fmt::Display> fmt::Display for At<E> {
     }
 }
 
+/// Error is displayed together with the location, so the source is the
+/// source of the located error.
+impl<E: StdError> StdError for At<E> {
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        self.err.source()
+    }
+}
+
 /// Location record in source file.
 #[derive(Debug, Copy, Clone)]
 pub struct Location {
     pub line: usize,
+    /// Column, starting from 1, if known.
+    pub column: Option<usize>,
+    /// Bytes of source, if known.
+    pub span: Option<Span>,
 }
 
 impl Location {
     pub fn new(line: usize) -> Location {
-        Location { line: line }
+        Location { line: line, column: None, span: None }
+    }
+
+    /// Location of exact source bytes.
+    pub fn spanning(line: usize, column: usize, span: Span) -> Location {
+        Location { line: line, column: Some(column), span: Some(span) }
     }
 }
 
@@ -1,11 +1,19 @@
 use std::fmt;
-use error::{ Location, Error };
+use std::error::Error as StdError;
+use error::{ Location, Error, ErrorKind };
 
 /// Stack trace record.
+///
+/// Stack trace starts at the outermost template, and each include or block
+/// reference is followed by the entries of the template or block it renders.
 #[derive(Clone, Debug)]
 pub enum TraceEntry {
     /// Trace source file change that caused the error.
     SourceFile { target: String },
+    /// Trace block of the last known source that caused the error.
+    Block { name: String },
+    /// Trace macro of the last known source that caused the error.
+    Macro { name: String },
     /// Trace operator call that caused the error.
     Operator { target: String, extension: String },
     /// Trace position in last known source that cause the error.
@@ -77,9 +85,30 @@ pub enum RuntimeError {
     ObjectMethodArgumentMismatch { name: String, defined: u16, given: u16 },
     /// Value casting error.
     ImpossibleCast { target: CastTarget, reason: CastError },
+    /// Template read a variable that is not defined, in strict variables mode.
+    ///
+    /// Contains names of variables that are available in the context.
+    UndefinedVariable { name: String, template: String, line: usize, available: Vec<String> },
+    /// Template read an attribute that does not exist, in strict variables mode.
+    ///
+    /// Contains keys that are available in the accessed value.
+    UndefinedAttribute { name: String, template: String, line: usize, available: Vec<String> },
+    /// Division or modulo with zero divisor.
+    DivisionByZero,
+    /// Regular expression pattern is not valid.
+    InvalidPattern { pattern: String, reason: String },
+    /// Escape filter was called with a strategy that does not exist.
+    UnknownEscapeStrategy(String),
 }
 
 impl RuntimeError {
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            RuntimeError::ImpossibleCast { .. } => ErrorKind::Cast,
+            _ => ErrorKind::Runtime,
+        }
+    }
+
     pub fn at(self, stack_trace: Vec<TraceEntry>) -> TracedRuntimeError {
         TracedRuntimeError {
             message: self,
@@ -115,11 +144,40 @@ impl fmt::Display for RuntimeError {
                     CastTarget::Int => "an integer",
                     CastTarget::Number => "a number",
                 })
-            }
+            },
+            RuntimeError::UndefinedVariable { ref name, ref template, line, ref available } => {
+                try!(write!(f, "Variable {:?} does not exist in {:?} at line {}", name, template, line));
+                write_available_keys(f, available)
+            },
+            RuntimeError::UndefinedAttribute { ref name, ref template, line, ref available } => {
+                try!(write!(f, "Attribute {:?} does not exist in {:?} at line {}", name, template, line));
+                write_available_keys(f, available)
+            },
+            RuntimeError::DivisionByZero => {
+                write!(f, "Division by zero")
+            },
+            RuntimeError::InvalidPattern { ref pattern, ref reason } => {
+                write!(f, "Invalid regular expression {:?}: {}", pattern, reason)
+            },
+            RuntimeError::UnknownEscapeStrategy(ref strategy) => {
+                write!(f, "Unknown escape strategy {:?}", strategy)
+            },
         }
     }
 }
 
+impl StdError for CastError {}
+
+impl StdError for RuntimeError {}
+
+fn write_available_keys(f: &mut fmt::Formatter, available: &[String]) -> fmt::Result {
+    if available.is_empty() {
+        return Ok(());
+    }
+    let quoted: Vec<_> = available.iter().map(|k| format!("{:?}", k)).collect();
+    write!(f, ", available keys: {}", quoted.join(", "))
+}
+
 /// Runtime error with stack trace.
 #[derive(Clone, Debug)]
 pub struct TracedRuntimeError {
@@ -134,14 +192,107 @@ impl TracedRuntimeError {
             stack_trace: Vec::new(),
         }
     }
+
+    /// Adds entries of the outer template, block or include to the start of
+    /// the stack trace.
+    pub fn within(mut self, mut entries: Vec<TraceEntry>) -> TracedRuntimeError {
+        entries.extend(self.stack_trace);
+        self.stack_trace = entries;
+        self
+    }
+
+    /// Groups stack trace into frames, the innermost frame first.
+    ///
+    /// Each template, block and operator call is a frame, located at the last
+    /// position traced in it.
+    fn frames(&self) -> Vec<Frame> {
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut template = None;
+        for entry in &self.stack_trace {
+            match *entry {
+                TraceEntry::SourceFile { ref target } => {
+                    template = Some(&target[..]);
+                    frames.push(Frame { template: template, call: None, loc: None });
+                },
+                TraceEntry::Block { ref name } => {
+                    frames.push(Frame { template: template, call: Some(format!("block {:?}", name)), loc: None });
+                },
+                TraceEntry::Macro { ref name } => {
+                    frames.push(Frame { template: template, call: Some(format!("macro {:?}", name)), loc: None });
+                },
+                TraceEntry::Operator { ref target, ref extension } => {
+                    frames.push(Frame { template: template, call: Some(format!("{:?} of {:?} extension", target, extension)), loc: None });
+                },
+                TraceEntry::Position { from } => match frames.last_mut() {
+                    Some(frame) => frame.loc = Some(from),
+                    None => frames.push(Frame { template: None, call: None, loc: Some(from) }),
+                },
+            }
+        }
+        frames.reverse();
+        frames
+    }
 }
 
+/// Stack trace frame, as displayed.
+struct Frame<'a> {
+    template: Option<&'a str>,
+    call: Option<String>,
+    loc: Option<Location>,
+}
+
+impl<'a> fmt::Display for Frame<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.template, self.loc) {
+            (Some(template), Some(loc)) => {
+                try!(write!(f, "{}:{}", template, loc.line));
+                if let Some(column) = loc.column {
+                    try!(write!(f, ":{}", column));
+                }
+            },
+            (Some(template), None) => try!(write!(f, "{}", template)),
+            (None, Some(loc)) => {
+                try!(write!(f, "line {}", loc.line));
+                if let Some(column) = loc.column {
+                    try!(write!(f, ", column {}", column));
+                }
+            },
+            (None, None) => try!(write!(f, "<unknown>")),
+        }
+        match self.call {
+            Some(ref call) => write!(f, " in {}", call),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Displays the message followed by the stack trace, the innermost frame
+/// first:
+///
+/// ```text
+/// Division by zero
+/// stack trace:
+///    0: part.twig:2:4 in block "content"
+///    1: part.twig:1:1
+///    2: page.twig:3:1
+/// ```
 impl fmt::Display for TracedRuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self.message)
+        try!(write!(f, "{}", self.message));
+        let frames = self.frames();
+        if frames.is_empty() {
+            return Ok(());
+        }
+        try!(write!(f, "\nstack trace:"));
+        for (i, frame) in frames.iter().enumerate() {
+            try!(write!(f, "\n{:>4}: {}", i, frame));
+        }
+        Ok(())
     }
 }
 
+impl StdError for TracedRuntimeError {}
+
 impl From<TracedRuntimeError> for Error {
     fn from(inner: TracedRuntimeError) -> Error {
         Error::Runtime(inner)
@@ -1,6 +1,7 @@
 use std::fmt;
-use tokens::TokenValue;
-use error::{ Error, ExtensionError, At, Location };
+use std::error::Error as StdError;
+use tokens::{ TokenValue, TokenRef };
+use error::{ Error, ErrorKind, ExtensionError, At, Location };
 
 #[derive(Clone, Debug)]
 pub enum Received {
@@ -36,13 +37,30 @@ pub enum TemplateError {
     DefaultValueForArgumentMustBeConstant,
     ParameterNameMustBeAString { given: String },
     TemplateNotFound(String),
+    UnknownFilter(String),
+    UnknownTest(String),
+    UnknownTag(String),
+    /// Node of extension tag has no source representation.
+    CanNotFormatExtensionNode,
     CustomError(Box<ExtensionError>),
 }
 
 impl TemplateError {
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            TemplateError::TemplateNotFound(_) => ErrorKind::NotFound,
+            _ => ErrorKind::Syntax,
+        }
+    }
+
     pub fn at(self, line: usize) -> At<TemplateError> {
         At::new(self, Location::new(line))
     }
+
+    /// Pins the error to the exact token position.
+    pub fn at_token(self, token: &TokenRef) -> At<TemplateError> {
+        At::new(self, token.location())
+    }
 }
 
 impl fmt::Display for TemplateError {
@@ -105,11 +123,24 @@ impl fmt::Display for TemplateError {
             TemplateError::DefaultValueForArgumentMustBeConstant => write!(f, "A default value for an argument must be a constant (a boolean, a string, a number, or an array)."),
             TemplateError::ParameterNameMustBeAString { ref given } => write!(f, "A parameter name must be a string, \"{}\" given", given),
             TemplateError::TemplateNotFound(ref name) => write!(f, "Template \"{}\" was not found", name),
+            TemplateError::UnknownFilter(ref name) => write!(f, "Unknown \"{}\" filter", name),
+            TemplateError::UnknownTest(ref name) => write!(f, "Unknown \"{}\" test", name),
+            TemplateError::UnknownTag(ref name) => write!(f, "Unknown \"{}\" tag", name),
+            TemplateError::CanNotFormatExtensionNode => write!(f, "Node of extension tag can not be formatted"),
             TemplateError::CustomError(ref e) => write!(f, "{}", e),
         }
     }
 }
 
+impl StdError for TemplateError {
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        match *self {
+            TemplateError::CustomError(ref e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
 impl From<At<TemplateError>> for Error {
     fn from(inner: At<TemplateError>) -> Error {
         Error::Template(inner)
@@ -1,18 +1,28 @@
 use std::convert::From;
 use std::fmt;
+use std::error::Error as StdError;
 use error::{ ExtensionError, Location, At, TemplateError };
+use tokens::TokenRef;
 
 #[derive(Debug, Clone)]
 pub enum CoreTemplateError {
     OnlyVariablesCanBeAssignedTo,
     ExpectedEndmacroName { expected: String, given: String },
+    ExpectedEndblockName { expected: String, given: String },
+    BlockAlreadyDefined { name: String, line: usize },
     CanNotAssignTo(String),
+    MultipleExtends,
 }
 
 impl CoreTemplateError {
     pub fn at(self, line: usize) -> At<CoreTemplateError> {
         At::new(self, Location::new(line))
     }
+
+    /// Pins the error to the exact token position.
+    pub fn at_token(self, token: &TokenRef) -> At<CoreTemplateError> {
+        At::new(self, token.location())
+    }
 }
 
 impl ExtensionError for CoreTemplateError {
@@ -26,14 +36,18 @@ impl fmt::Display for CoreTemplateError {
         match *self {
             CoreTemplateError::OnlyVariablesCanBeAssignedTo => write!(f, "Only variables can be assigned to"),
             CoreTemplateError::ExpectedEndmacroName { ref expected, ref given } => write!(f, "Expected endmacro for macro \"{}\" (but \"{}\" given)", expected, given),
+            CoreTemplateError::ExpectedEndblockName { ref expected, ref given } => write!(f, "Expected endblock for block \"{}\" (but \"{}\" given)", expected, given),
+            CoreTemplateError::BlockAlreadyDefined { ref name, line } => write!(f, "The block \"{}\" has already been defined line {}", name, line),
             CoreTemplateError::CanNotAssignTo(ref v) => write!(f, "You cannot assign a value to \"{}\"", v),
+            CoreTemplateError::MultipleExtends => write!(f, "Multiple extends tags are forbidden"),
         }
     }
 }
 
+impl StdError for CoreTemplateError {}
+
 impl From<At<CoreTemplateError>> for At<TemplateError> {
-    fn from(At { loc: Location { line }, err }: At<CoreTemplateError>) -> At<TemplateError> {
-        TemplateError::CustomError(Box::new(err))
-            .at(line)
+    fn from(At { loc, err }: At<CoreTemplateError>) -> At<TemplateError> {
+        At::new(TemplateError::CustomError(Box::new(err)), loc)
     }
 }
@@ -0,0 +1,41 @@
+use value::Value;
+use error::{ RuntimeResult, RuntimeError };
+use engine;
+use engine::escape::Strategy;
+use super::tests::is_empty;
+
+/// Returns the default value if the filtered value is empty.
+///
+/// Undefined variables are compiled to null for this filter, so they get
+/// the default value even in strict variables mode.
+pub fn default(args: &[Value]) -> RuntimeResult<Value> {
+    match args {
+        [ref value] if is_empty(value) => Ok(Value::Str("".into())),
+        [ref value, ref default] if is_empty(value) => Ok(default.clone()),
+        [ref value] | [ref value, _] => Ok(value.clone()),
+        _ => Err(RuntimeError::InvalidArgumentCount { defined: 2, given: args.len() }),
+    }
+}
+
+/// Marks the value as safe, so it is not autoescaped when printed.
+pub fn raw(args: &[Value]) -> RuntimeResult<Value> {
+    match args {
+        [ref value] => Ok(value.clone()),
+        _ => Err(RuntimeError::InvalidArgumentCount { defined: 1, given: args.len() }),
+    }
+}
+
+/// Escapes the value with the given strategy, "html" by default.
+///
+/// The result is not autoescaped again when printed.
+pub fn escape(args: &[Value]) -> RuntimeResult<Value> {
+    let (value, strategy) = match args {
+        [ref value] => (value, "html".to_string()),
+        [ref value, ref strategy] => (value, strategy.to_string()),
+        _ => return Err(RuntimeError::InvalidArgumentCount { defined: 2, given: args.len() }),
+    };
+    match Strategy::from_name(&strategy) {
+        Some(s) => Ok(Value::Str(engine::escape::escape(&value.to_string(), s))),
+        None => Err(RuntimeError::UnknownEscapeStrategy(strategy)),
+    }
+}
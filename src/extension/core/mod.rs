@@ -1,54 +1,61 @@
-use environment::Environment;
 use extension::Extension;
 use operator::Operator;
 use nodes::TokenParser;
-use function::{ Function, Arg };
-use little::Instruction;
+use function::{ Function, Filter, Test, Arg };
 use instructions::CompiledExpression;
 
 pub mod token_parsers;
+pub mod operators;
 pub mod error;
+pub mod filters;
+pub mod tests;
 
 pub struct CoreExtension;
 
 impl Extension for CoreExtension {
-    fn apply(env: &mut Environment) {
-        env.push_operators(vec![
-            Operator::new_unary("not", 50, |_| unimplemented!()),
-            Operator::new_unary("-", 500, |_| unimplemented!()),
-            Operator::new_unary("+", 500, |_| unimplemented!()),
+    fn name(&self) -> &'static str {
+        "core"
+    }
+
+    fn operators(&self) -> Vec<Operator> {
+        vec![
+            Operator::new_unary("not", 50, operators::not),
+            Operator::new_unary("-", 500, operators::neg),
+            Operator::new_unary("+", 500, operators::pos),
 
-            Operator::new_binary_left("or"         , 10, |_, _| unimplemented!()),
-            Operator::new_binary_left("and"        , 15, |_, _| unimplemented!()),
-            Operator::new_binary_left("b-or"       , 16, |_, _| unimplemented!()),
-            Operator::new_binary_left("b-xor"      , 17, |_, _| unimplemented!()),
-            Operator::new_binary_left("b-and"      , 18, |_, _| unimplemented!()),
-            Operator::new_binary_left("=="         , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("!="         , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("<"          , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left(">"          , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left(">="         , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("<="         , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("not in"     , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("in"         , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("matches"    , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("starts with", 20, |_, _| unimplemented!()),
-            Operator::new_binary_left("ends with"  , 20, |_, _| unimplemented!()),
-            Operator::new_binary_left(".."         , 25, |_, _| unimplemented!()),
-            Operator::new_binary_left("+"          , 30, |_, _| unimplemented!()),
-            Operator::new_binary_left("-"          , 30, |_, _| unimplemented!()),
-            Operator::new_binary_left("~"          , 40, |_, _| unimplemented!()),
-            Operator::new_binary_left("*"          , 60, |_, _| unimplemented!()),
-            Operator::new_binary_left("/"          , 60, |_, _| unimplemented!()),
-            Operator::new_binary_left("//"         , 60, |_, _| unimplemented!()),
-            Operator::new_binary_left("%"          , 60, |_, _| unimplemented!()),
+            Operator::new_binary_left("or"         , 10, operators::or),
+            Operator::new_binary_left("and"        , 15, operators::and),
+            Operator::new_binary_left("b-or"       , 16, operators::bitwise_or),
+            Operator::new_binary_left("b-xor"      , 17, operators::bitwise_xor),
+            Operator::new_binary_left("b-and"      , 18, operators::bitwise_and),
+            Operator::new_binary_left("=="         , 20, operators::equal),
+            Operator::new_binary_left("!="         , 20, operators::not_equal),
+            Operator::new_binary_left("<"          , 20, operators::less),
+            Operator::new_binary_left(">"          , 20, operators::greater),
+            Operator::new_binary_left(">="         , 20, operators::greater_or_equal),
+            Operator::new_binary_left("<="         , 20, operators::less_or_equal),
+            Operator::new_binary_left("not in"     , 20, operators::not_in),
+            Operator::new_binary_left("in"         , 20, operators::in_),
+            Operator::new_binary_left("matches"    , 20, operators::matches),
+            Operator::new_binary_left("starts with", 20, operators::starts_with),
+            Operator::new_binary_left("ends with"  , 20, operators::ends_with),
+            Operator::new_binary_left(".."         , 25, operators::range),
+            Operator::new_binary_left("+"          , 30, operators::add),
+            Operator::new_binary_left("-"          , 30, operators::sub),
+            Operator::new_binary_left("~"          , 40, operators::concat),
+            Operator::new_binary_left("*"          , 60, operators::mul),
+            Operator::new_binary_left("/"          , 60, operators::div),
+            Operator::new_binary_left("//"         , 60, operators::floor_div),
+            Operator::new_binary_left("%"          , 60, operators::modulo),
             Operator::new_binary_left("is"         , 100, |_, _| unimplemented!()),
             Operator::new_binary_left("is not"     , 100, |_, _| unimplemented!()),
 
-            Operator::new_binary_right("**"         , 200, |_, _| unimplemented!()),
-        ]);
+            Operator::new_binary_right("**"         , 200, operators::pow),
+        ]
+    }
 
-        env.push_token_parsers(vec![
+    fn token_parsers(&self) -> Vec<TokenParser> {
+        vec![
             TokenParser::new(token_parsers::For::new()),
             TokenParser::new(token_parsers::If::new()),
             TokenParser::new(token_parsers::Extends::new()),
@@ -64,14 +71,37 @@ impl Extension for CoreExtension {
             TokenParser::new(token_parsers::Flush::new()),
             TokenParser::new(token_parsers::Do::new()),
             TokenParser::new(token_parsers::Embed::new()),
-        ]);
+        ]
+    }
 
-        env.push_functions(vec![
+    fn functions(&self) -> Vec<Function> {
+        vec![
             Function::new_static("include", vec![Arg::Anon], |staging| {
                 //staging.instr(Instruction::Push(staging.use_str_const("include")));
                 //staging.instr(Instruction::Push(staging.use_str_const("include")));
                 Ok(CompiledExpression::empty("include"))
             })
-        ]);
+        ]
+    }
+
+    fn filters(&self) -> Vec<Filter> {
+        vec![
+            Filter::new_dynamic("default", filters::default),
+            Filter::new_dynamic("raw", filters::raw),
+            Filter::new_dynamic("escape", filters::escape),
+            Filter::new_dynamic("e", filters::escape),
+        ]
+    }
+
+    fn tests(&self) -> Vec<Test> {
+        vec![
+            Test::new_dynamic("defined", tests::defined),
+            Test::new_dynamic("null", tests::null),
+            Test::new_dynamic("none", tests::null),
+            Test::new_dynamic("empty", tests::empty),
+            Test::new_dynamic("even", tests::even),
+            Test::new_dynamic("odd", tests::odd),
+            Test::new_dynamic("iterable", tests::iterable),
+        ]
     }
 }
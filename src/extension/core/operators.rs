@@ -0,0 +1,399 @@
+/*!
+Runtime implementations of core operators.
+
+Values are compared and converted following PHP rules, the same way as the
+reference Twig implementation does.
+*/
+
+use std::cmp::Ordering;
+use regex::Regex;
+use value::Value;
+use value::ops::{ parse_as_numeric, float_to_int, ParseAsNumericResult };
+use error::{ RuntimeResult, RuntimeError };
+
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn float(&self) -> f64 {
+        match *self {
+            Number::Int(v) => v as f64,
+            Number::Float(v) => v,
+        }
+    }
+
+    fn int(&self) -> RuntimeResult<i64> {
+        match *self {
+            Number::Int(v) => Ok(v),
+            Number::Float(v) => float_to_int(v),
+        }
+    }
+}
+
+fn number(value: &Value) -> RuntimeResult<Number> {
+    Ok(match *value {
+        Value::Null => Number::Int(0),
+        Value::Bool(v) => Number::Int(v as i64),
+        Value::Int(v) => Number::Int(v),
+        Value::Float(v) => Number::Float(v),
+        Value::Str(ref v) => match try!(parse_as_numeric(v)) {
+            ParseAsNumericResult::Int(v) => Number::Int(v),
+            ParseAsNumericResult::Float(v) => Number::Float(v),
+        },
+        ref other => Number::Int(try!(other.clone().int())),
+    })
+}
+
+/// Returns number if the value is numeric without any conversion loss.
+fn numeric(value: &Value) -> Option<Number> {
+    match *value {
+        Value::Int(v) => Some(Number::Int(v)),
+        Value::Float(v) => Some(Number::Float(v)),
+        Value::Str(ref v) => match parse_as_numeric(v) {
+            Ok(ParseAsNumericResult::Int(v)) => Some(Number::Int(v)),
+            Ok(ParseAsNumericResult::Float(v)) => Some(Number::Float(v)),
+            Err(_) => None,
+        },
+        _ => None,
+    }
+}
+
+fn compare_numbers(left: Number, right: Number) -> Option<Ordering> {
+    match (left, right) {
+        (Number::Int(a), Number::Int(b)) => a.partial_cmp(&b),
+        (a, b) => a.float().partial_cmp(&b.float()),
+    }
+}
+
+/// Compares values loosely, like PHP comparison operators.
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (&Value::Str(ref a), &Value::Str(ref b)) => match (numeric(left), numeric(right)) {
+            (Some(a), Some(b)) => compare_numbers(a, b),
+            _ => a.partial_cmp(b),
+        },
+        (&Value::Null, &Value::Str(ref v)) => "".partial_cmp(&v[..]),
+        (&Value::Str(ref v), &Value::Null) => v[..].partial_cmp(""),
+        (&Value::Bool(_), _) | (_, &Value::Bool(_)) | (&Value::Null, _) | (_, &Value::Null) => {
+            left.is_truthy().partial_cmp(&right.is_truthy())
+        },
+        (&Value::Hash(ref a), &Value::Hash(ref b)) => if a == b {
+            Some(Ordering::Equal)
+        } else {
+            None
+        },
+        (&Value::Int(_), _) | (&Value::Float(_), _) | (_, &Value::Int(_)) | (_, &Value::Float(_)) => {
+            match (numeric(left), numeric(right)) {
+                (Some(a), Some(b)) => compare_numbers(a, b),
+                _ => left.to_string().partial_cmp(&right.to_string()),
+            }
+        },
+        _ => left.partial_cmp(right),
+    }
+}
+
+fn loose_eq(left: &Value, right: &Value) -> bool {
+    compare(left, right) == Some(Ordering::Equal)
+}
+
+fn contains(haystack: &Value, needle: &Value) -> bool {
+    match *haystack {
+        Value::Array(ref items) => items.iter().any(|item| loose_eq(needle, item)),
+        Value::Hash(ref items) => items.values().any(|item| loose_eq(needle, item)),
+        Value::Str(ref haystack) => match *needle {
+            Value::Str(_) | Value::Int(_) | Value::Float(_) => haystack.contains(&needle.to_string()[..]),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn arithmetic<I, F>(left: &Value, right: &Value, int_op: I, float_op: F) -> RuntimeResult<Value>
+    where I: Fn(i64, i64) -> Option<i64>, F: Fn(f64, f64) -> f64
+{
+    Ok(match (try!(number(left)), try!(number(right))) {
+        (Number::Int(a), Number::Int(b)) => match int_op(a, b) {
+            Some(v) => Value::Int(v),
+            // Integer overflow continues in floats.
+            None => Value::Float(float_op(a as f64, b as f64)),
+        },
+        (a, b) => Value::Float(float_op(a.float(), b.float())),
+    })
+}
+
+fn bitwise<F>(left: &Value, right: &Value, op: F) -> RuntimeResult<Value>
+    where F: Fn(i64, i64) -> i64
+{
+    Ok(Value::Int(op(try!(try!(number(left)).int()), try!(try!(number(right)).int()))))
+}
+
+pub fn not(value: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(!value.is_truthy()))
+}
+
+pub fn neg(value: &Value) -> RuntimeResult<Value> {
+    Ok(match try!(number(value)) {
+        Number::Int(v) => match v.checked_neg() {
+            Some(v) => Value::Int(v),
+            None => Value::Float(-(v as f64)),
+        },
+        Number::Float(v) => Value::Float(-v),
+    })
+}
+
+pub fn pos(value: &Value) -> RuntimeResult<Value> {
+    Ok(match try!(number(value)) {
+        Number::Int(v) => Value::Int(v),
+        Number::Float(v) => Value::Float(v),
+    })
+}
+
+pub fn or(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(left.is_truthy() || right.is_truthy()))
+}
+
+pub fn and(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(left.is_truthy() && right.is_truthy()))
+}
+
+pub fn bitwise_or(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    bitwise(left, right, |a, b| a | b)
+}
+
+pub fn bitwise_xor(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    bitwise(left, right, |a, b| a ^ b)
+}
+
+pub fn bitwise_and(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    bitwise(left, right, |a, b| a & b)
+}
+
+pub fn equal(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(loose_eq(left, right)))
+}
+
+pub fn not_equal(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(!loose_eq(left, right)))
+}
+
+pub fn less(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(compare(left, right) == Some(Ordering::Less)))
+}
+
+pub fn greater(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(compare(left, right) == Some(Ordering::Greater)))
+}
+
+pub fn greater_or_equal(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(match compare(left, right) {
+        Some(Ordering::Greater) | Some(Ordering::Equal) => true,
+        _ => false,
+    }))
+}
+
+pub fn less_or_equal(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(match compare(left, right) {
+        Some(Ordering::Less) | Some(Ordering::Equal) => true,
+        _ => false,
+    }))
+}
+
+pub fn not_in(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(!contains(right, left)))
+}
+
+pub fn in_(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(contains(right, left)))
+}
+
+/// Matches string against PHP-style pattern, i.e. `/^a/i`.
+pub fn matches(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    let pattern = right.to_string();
+    let invalid = |reason: &str| RuntimeError::InvalidPattern {
+        pattern: pattern.clone(),
+        reason: reason.into(),
+    };
+
+    let delimiter = match pattern.chars().next() {
+        Some(c) if !c.is_alphanumeric() && !c.is_whitespace() && c != '\\' => c,
+        _ => return Err(invalid("delimiter must not be alphanumeric or backslash")),
+    };
+    let end = match pattern.rfind(delimiter) {
+        Some(end) if end > 0 => end,
+        _ => return Err(invalid("no ending delimiter")),
+    };
+
+    let mut flags = String::new();
+    for flag in pattern[end + delimiter.len_utf8()..].chars() {
+        match flag {
+            'i' | 'm' | 's' | 'x' => flags.push(flag),
+            _ => return Err(invalid(&format!("unknown modifier {:?}", flag))),
+        }
+    }
+
+    let expression = if flags.is_empty() {
+        pattern[delimiter.len_utf8()..end].to_string()
+    } else {
+        format!("(?{}){}", flags, &pattern[delimiter.len_utf8()..end])
+    };
+
+    match Regex::new(&expression) {
+        Ok(regex) => Ok(Value::Bool(regex.is_match(&left.to_string()))),
+        Err(e) => Err(invalid(&e.to_string())),
+    }
+}
+
+pub fn starts_with(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(left.to_string().starts_with(&right.to_string()[..])))
+}
+
+pub fn ends_with(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Bool(left.to_string().ends_with(&right.to_string()[..])))
+}
+
+/// Returns array with all values from left to right, inclusive.
+///
+/// Works with integers and single characters.
+pub fn range(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    if let (&Value::Str(ref a), &Value::Str(ref b)) = (left, right) {
+        if let (None, None) = (numeric(left), numeric(right)) {
+            let (a, b) = (a.chars().next().unwrap_or('\0') as u32, b.chars().next().unwrap_or('\0') as u32);
+            let chars: Vec<u32> = if a <= b { (a..b + 1).collect() } else { (b..a + 1).rev().collect() };
+            return Ok(Value::Array(
+                chars.into_iter()
+                    .filter_map(::std::char::from_u32)
+                    .map(|c| Value::Str(c.to_string()))
+                    .collect()
+            ));
+        }
+    }
+
+    let (a, b) = (try!(try!(number(left)).int()), try!(try!(number(right)).int()));
+    Ok(Value::Array(if a <= b {
+        (a..b + 1).map(Value::Int).collect()
+    } else {
+        (b..a + 1).rev().map(Value::Int).collect()
+    }))
+}
+
+pub fn add(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    arithmetic(left, right, |a, b| a.checked_add(b), |a, b| a + b)
+}
+
+pub fn sub(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    arithmetic(left, right, |a, b| a.checked_sub(b), |a, b| a - b)
+}
+
+pub fn concat(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    Ok(Value::Str(format!("{}{}", left, right)))
+}
+
+pub fn mul(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    arithmetic(left, right, |a, b| a.checked_mul(b), |a, b| a * b)
+}
+
+/// Divides values, the result is an integer only if both values are
+/// integers and the division has no remainder.
+pub fn div(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    if try!(number(right)).float() == 0.0 {
+        return Err(RuntimeError::DivisionByZero);
+    }
+    arithmetic(left, right, |a, b| match a.checked_rem(b) {
+        Some(0) => a.checked_div(b),
+        _ => None,
+    }, |a, b| a / b)
+}
+
+pub fn floor_div(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    match try!(div(left, right)) {
+        Value::Float(v) => Ok(Value::Int(try!(float_to_int(v.floor())))),
+        other => Ok(other),
+    }
+}
+
+pub fn modulo(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    let (a, b) = (try!(try!(number(left)).int()), try!(try!(number(right)).int()));
+    match b {
+        0 => Err(RuntimeError::DivisionByZero),
+        -1 => Ok(Value::Int(0)),
+        b => Ok(Value::Int(a % b)),
+    }
+}
+
+pub fn pow(left: &Value, right: &Value) -> RuntimeResult<Value> {
+    arithmetic(left, right, |a, b| {
+        if b < 0 || b > u32::max_value() as i64 {
+            return None;
+        }
+        a.checked_pow(b as u32)
+    }, |a, b| a.powf(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::i64;
+    use value::Value;
+    use super::*;
+
+    fn s(v: &str) -> Value {
+        Value::Str(v.into())
+    }
+
+    #[test]
+    fn compares_numeric_strings_as_numbers() {
+        assert_eq!(equal(&s("10"), &s("10.0")).unwrap(), Value::Bool(true));
+        assert_eq!(equal(&Value::Int(18), &s("18")).unwrap(), Value::Bool(true));
+        assert_eq!(less(&s("9"), &s("10")).unwrap(), Value::Bool(true));
+        assert_eq!(less(&s("b"), &s("a")).unwrap(), Value::Bool(false));
+        assert_eq!(greater_or_equal(&Value::Float(18.5), &Value::Int(18)).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn compares_null_and_bool_by_truthiness() {
+        assert_eq!(equal(&Value::Null, &Value::Bool(false)).unwrap(), Value::Bool(true));
+        assert_eq!(equal(&Value::Int(0), &Value::Null).unwrap(), Value::Bool(true));
+        assert_eq!(equal(&s("a"), &Value::Bool(true)).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn finds_values_in_collections_and_strings() {
+        let roles = Value::Array(vec![s("user"), s("admin")]);
+        assert_eq!(in_(&s("admin"), &roles).unwrap(), Value::Bool(true));
+        assert_eq!(not_in(&s("root"), &roles).unwrap(), Value::Bool(true));
+        assert_eq!(in_(&s("ell"), &s("hello")).unwrap(), Value::Bool(true));
+        assert_eq!(in_(&Value::Int(1), &Value::Null).unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn keeps_integer_arithmetic_in_integers() {
+        assert_eq!(add(&Value::Int(2), &s("3")).unwrap(), Value::Int(5));
+        assert_eq!(div(&Value::Int(6), &Value::Int(3)).unwrap(), Value::Int(2));
+        assert_eq!(div(&Value::Int(7), &Value::Int(2)).unwrap(), Value::Float(3.5));
+        assert_eq!(floor_div(&Value::Int(7), &Value::Int(2)).unwrap(), Value::Int(3));
+        assert_eq!(modulo(&Value::Int(7), &Value::Int(4)).unwrap(), Value::Int(3));
+        assert_eq!(pow(&Value::Int(2), &Value::Int(10)).unwrap(), Value::Int(1024));
+        assert_eq!(add(&Value::Int(i64::MAX), &Value::Int(1)).unwrap(), Value::Float(i64::MAX as f64 + 1.0));
+    }
+
+    #[test]
+    fn fails_division_by_zero() {
+        assert_eq!(div(&Value::Int(1), &Value::Int(0)).unwrap_err().to_string(), "Division by zero");
+        assert_eq!(modulo(&Value::Int(1), &s("0")).unwrap_err().to_string(), "Division by zero");
+    }
+
+    #[test]
+    fn builds_ranges() {
+        assert_eq!(range(&Value::Int(1), &Value::Int(3)).unwrap(), Value::Array(vec![Value::Int(1), Value::Int(2), Value::Int(3)]));
+        assert_eq!(range(&Value::Int(2), &Value::Int(1)).unwrap(), Value::Array(vec![Value::Int(2), Value::Int(1)]));
+        assert_eq!(range(&s("a"), &s("c")).unwrap(), Value::Array(vec![s("a"), s("b"), s("c")]));
+    }
+
+    #[test]
+    fn matches_php_style_patterns() {
+        assert_eq!(matches(&s("Hello"), &s("/^hel/i")).unwrap(), Value::Bool(true));
+        assert_eq!(matches(&s("Hello"), &s("/^hel/")).unwrap(), Value::Bool(false));
+        assert!(matches(&s("Hello"), &s("^hel")).is_err());
+    }
+}
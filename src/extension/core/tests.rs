@@ -0,0 +1,48 @@
+use value::Value;
+use error::{ RuntimeResult, RuntimeError };
+
+/// Returns true if the value is null, false, empty string or empty collection.
+pub fn is_empty(value: &Value) -> bool {
+    match *value {
+        Value::Null | Value::Bool(false) => true,
+        Value::Str(ref v) => v.is_empty(),
+        Value::Array(ref items) => items.is_empty(),
+        Value::Hash(ref hash) => hash.is_empty(),
+        _ => false,
+    }
+}
+
+fn value(args: &[Value]) -> RuntimeResult<&Value> {
+    match args {
+        [ref value] => Ok(value),
+        _ => Err(RuntimeError::InvalidArgumentCount { defined: 1, given: args.len() }),
+    }
+}
+
+/// The "defined" test is compiled as a lookup, so the value always exists here.
+pub fn defined(_: &[Value]) -> RuntimeResult<bool> {
+    Ok(true)
+}
+
+pub fn null(args: &[Value]) -> RuntimeResult<bool> {
+    Ok(*try!(value(args)) == Value::Null)
+}
+
+pub fn empty(args: &[Value]) -> RuntimeResult<bool> {
+    Ok(is_empty(try!(value(args))))
+}
+
+pub fn even(args: &[Value]) -> RuntimeResult<bool> {
+    Ok(try!(value(args)).to_string().parse::<i64>().map(|v| v % 2 == 0).unwrap_or(false))
+}
+
+pub fn odd(args: &[Value]) -> RuntimeResult<bool> {
+    Ok(try!(value(args)).to_string().parse::<i64>().map(|v| v % 2 != 0).unwrap_or(false))
+}
+
+pub fn iterable(args: &[Value]) -> RuntimeResult<bool> {
+    Ok(match *try!(value(args)) {
+        Value::Array(_) | Value::Hash(_) => true,
+        _ => false,
+    })
+}
@@ -53,7 +53,7 @@ pub fn parse_assignment_expression<'p, 'c>(parser: &mut Parser<'p, 'c>)
             },
             _ => return Err(
                 CoreTemplateError::OnlyVariablesCanBeAssignedTo
-                    .at(token.line)
+                    .at_token(&token)
                     .into()
             ),
         };
@@ -61,12 +61,12 @@ pub fn parse_assignment_expression<'p, 'c>(parser: &mut Parser<'p, 'c>)
         if INVALID_LVALUES.contains(&name) {
             return Err(
                 CoreTemplateError::CanNotAssignTo(name.into())
-                    .at(token.line)
+                    .at_token(&token)
                     .into()
             )
         }
 
-        targets.push(Expr::new_at(ExprValue::AssignName(name), token.line));
+        targets.push(Expr::new_at(ExprValue::AssignName(name.into()), token.line).with_span(token.column, token.span));
 
         if !try!(parser.skip_to_next_if(TokenValueRef::Punctuation(','))) {
             break;
@@ -1,7 +1,10 @@
-use tokens::TokenRef;
-use nodes::{ Parser, TokenParserExtension };
+use tokens::{ TokenRef, TokenValueRef };
+use nodes::{ Parser, TokenParserExtension, Block as BlockNode };
 use nodes::body::Body;
+use nodes::expr_parser::parse_expression;
+use nodes::body_parser::{ subparse, BlockEnd };
 use error::TemplateResult;
+use extension::core::error::*;
 
 pub struct Block;
 
@@ -15,7 +18,52 @@ impl TokenParserExtension for Block {
     fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
         -> TemplateResult<Option<Body<'c>>>
     {
-        unreachable!("not implemented Block::parse")
+        trace!("Block::parse, {:?}", token);
+
+        let line = token.line;
+        let name = try!(parser.expect_name());
+
+        if let Some(existing) = parser.blocks.iter().find(|b| b.name == name) {
+            return Err(
+                CoreTemplateError::BlockAlreadyDefined { name: name.into(), line: existing.line }
+                    .at_token(&token)
+                    .into()
+            );
+        }
+
+        let body = if try!(parser.skip_to_next_if(TokenValueRef::BlockEnd)) {
+            let body = try!(subparse(parser, |token| match token.value {
+                TokenValueRef::Name("endblock") => Some(BlockEnd { drop_needle: true }),
+                _ => None,
+            }));
+            let token = try!(parser.current());
+            if let TokenValueRef::Name(value) = token.value {
+                try!(parser.next());
+
+                if value != name {
+                    return Err(
+                        CoreTemplateError::ExpectedEndblockName { given: value.into(), expected: name.into() }
+                            .at_token(&token)
+                            .into()
+                    )
+                }
+            }
+            body
+        } else {
+            let expr = try!(parse_expression(parser, 0));
+            let (column, span) = (expr.column, expr.span);
+            Body::Print { expr: Box::new(expr), line: line, column: column, span: span }
+        };
+
+        try!(parser.expect(TokenValueRef::BlockEnd));
+
+        parser.set_block(BlockNode {
+            name: name.into(),
+            body: body,
+            line: line,
+        });
+
+        Ok(Some(Body::BlockReference { name: name.into(), line: line, column: token.column, span: token.span }))
     }
 
     fn get_tag(&self) -> &'static str {
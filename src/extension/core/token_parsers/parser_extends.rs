@@ -1,7 +1,9 @@
-use tokens::TokenRef;
+use tokens::{ TokenRef, TokenValueRef };
 use nodes::{ Parser, TokenParserExtension };
 use nodes::body::Body;
+use nodes::expr_parser::parse_expression;
 use error::TemplateResult;
+use extension::core::error::*;
 
 pub struct Extends;
 
@@ -15,7 +17,19 @@ impl TokenParserExtension for Extends {
     fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
         -> TemplateResult<Option<Body<'c>>>
     {
-        unreachable!("not implemented Extends::parse")
+        trace!("Extends::parse {:?}", token);
+
+        if parser.parent.is_some() {
+            return Err(CoreTemplateError::MultipleExtends.at_token(&token).into());
+        }
+
+        let parent = try!(parse_expression(parser, 0));
+
+        try!(parser.expect(TokenValueRef::BlockEnd));
+
+        parser.parent = Some(parent);
+
+        Ok(None)
     }
 
     fn get_tag(&self) -> &'static str {
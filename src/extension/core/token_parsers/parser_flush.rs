@@ -1,4 +1,4 @@
-use tokens::TokenRef;
+use tokens::{ TokenRef, TokenValueRef };
 use nodes::{ Parser, TokenParserExtension };
 use nodes::body::Body;
 use error::TemplateResult;
@@ -15,7 +15,11 @@ impl TokenParserExtension for Flush {
     fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
         -> TemplateResult<Option<Body<'c>>>
     {
-        unreachable!("not implemented Flush::parse")
+        trace!("Flush::parse {:?}", token);
+
+        try!(parser.expect(TokenValueRef::BlockEnd));
+
+        Ok(Some(Body::Flush { line: token.line, column: token.column, span: token.span }))
     }
 
     fn get_tag(&self) -> &'static str {
@@ -42,8 +42,8 @@ impl TokenParserExtension for From {
             target_slots.push(
                 (
                     parser.add_imported_function(alias, name),
-                    alias,
-                    ImportTarget::Function { symbol: name }
+                    alias.into(),
+                    ImportTarget::Function { symbol: name.into() }
                 )
             );
         }
@@ -52,6 +52,8 @@ impl TokenParserExtension for From {
             source: Box::new(macro_expr),
             targets: target_slots,
             line: token.line,
+            column: token.column,
+            span: token.span,
         }))
     }
 
@@ -1,8 +1,10 @@
-use tokens::TokenRef;
+use tokens::{ TokenRef, TokenValueRef };
 use nodes::{ Parser, TokenParserExtension };
-use nodes::body::Body;
+use nodes::body::{ Body, ImportTarget };
 use error::TemplateResult;
 
+use nodes::expr_parser::parse_expression;
+
 pub struct Import;
 
 impl Import {
@@ -15,7 +17,22 @@ impl TokenParserExtension for Import {
     fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
         -> TemplateResult<Option<Body<'c>>>
     {
-        unreachable!("not implemented Import::parse")
+        trace!("Import::parse {:?}", token);
+
+        let macro_expr = try!(parse_expression(parser, 0));
+
+        try!(parser.expect(TokenValueRef::Name("as")));
+        let alias = try!(parser.expect_name());
+
+        try!(parser.expect(TokenValueRef::BlockEnd));
+
+        Ok(Some(Body::Import {
+            source: Box::new(macro_expr),
+            targets: vec![(parser.add_imported_template(alias), alias.into(), ImportTarget::Template)],
+            line: token.line,
+            column: token.column,
+            span: token.span,
+        }))
     }
 
     fn get_tag(&self) -> &'static str {
@@ -1,8 +1,10 @@
-use tokens::TokenRef;
+use tokens::{ TokenRef, TokenValueRef };
 use nodes::{ Parser, TokenParserExtension };
 use nodes::body::Body;
 use error::TemplateResult;
 
+use nodes::expr_parser::parse_expression;
+
 pub struct Include;
 
 impl Include {
@@ -15,7 +17,34 @@ impl TokenParserExtension for Include {
     fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
         -> TemplateResult<Option<Body<'c>>>
     {
-        unreachable!("not implemented Include::parse")
+        trace!("Include::parse {:?}", token);
+
+        let template = try!(parse_expression(parser, 0));
+
+        let mut ignore_missing = false;
+        if try!(parser.skip_to_next_if(TokenValueRef::Name("ignore"))) {
+            try!(parser.expect(TokenValueRef::Name("missing")));
+            ignore_missing = true;
+        }
+
+        let mut variables = None;
+        if try!(parser.skip_to_next_if(TokenValueRef::Name("with"))) {
+            variables = Some(Box::new(try!(parse_expression(parser, 0))));
+        }
+
+        let only = try!(parser.skip_to_next_if(TokenValueRef::Name("only")));
+
+        try!(parser.expect(TokenValueRef::BlockEnd));
+
+        Ok(Some(Body::Include {
+            template: Box::new(template),
+            variables: variables,
+            only: only,
+            ignore_missing: ignore_missing,
+            line: token.line,
+            column: token.column,
+            span: token.span,
+        }))
     }
 
     fn get_tag(&self) -> &'static str {
@@ -22,7 +22,7 @@ impl TokenParserExtension for Macro {
 
         let name = try!(parser.expect_name());
         let arguments = try!(parse_named_arguments(parser, true));
-        let line = token.line;
+        let (line, column, span) = (token.line, token.column, token.span);
 
         try!(parser.expect(TokenValueRef::BlockEnd));
         parser.push_local_scope();
@@ -48,10 +48,12 @@ impl TokenParserExtension for Macro {
         try!(parser.expect(TokenValueRef::BlockEnd));
 
         Ok(Some(Body::Macro {
-            name: name,
+            name: name.into(),
             body: Box::new(body),
             arguments: arguments,
             line: line,
+            column: column,
+            span: span,
         }))
     }
 
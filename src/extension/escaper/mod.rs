@@ -1,10 +1,9 @@
-use environment::Environment;
 use extension::Extension;
 
 pub struct EscaperExtension;
 
 impl Extension for EscaperExtension {
-    fn apply(env: &mut Environment) {
-
+    fn name(&self) -> &'static str {
+        "escaper"
     }
 }
@@ -1,12 +1,60 @@
 mod core;
 mod escaper;
 
-use environment::Environment;
+use std::fmt;
+use operator::Operator;
+use function::{ Function, Filter, Test };
+use global::Global;
+use nodes::{ TokenParser, NodeVisitor };
 
 pub use self::core::CoreExtension;
 pub use self::escaper::EscaperExtension;
 
 /// Implement this trait to create a new Twig extension.
+///
+/// Extension is registered with `Environment::add_extension`, and can keep
+/// its own configuration, i.e. a translator or a router. Items returned by
+/// the extension are added to the environment when it is initialized.
 pub trait Extension {
-    fn apply(env: &mut Environment);
+    /// Unique extension name.
+    fn name(&self) -> &'static str;
+
+    /// Names of extensions that have to be initialized before this one.
+    fn dependencies(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn operators(&self) -> Vec<Operator> {
+        Vec::new()
+    }
+
+    fn token_parsers(&self) -> Vec<TokenParser> {
+        Vec::new()
+    }
+
+    fn functions(&self) -> Vec<Function> {
+        Vec::new()
+    }
+
+    fn filters(&self) -> Vec<Filter> {
+        Vec::new()
+    }
+
+    fn tests(&self) -> Vec<Test> {
+        Vec::new()
+    }
+
+    fn globals(&self) -> Vec<(String, Global)> {
+        Vec::new()
+    }
+
+    fn node_visitors(&self) -> Vec<Box<NodeVisitor>> {
+        Vec::new()
+    }
+}
+
+impl fmt::Debug for Box<Extension> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Extension({:?})", self.name())
+    }
 }
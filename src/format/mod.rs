@@ -0,0 +1,806 @@
+/*!
+
+Formats parsed templates back to normalized Twig source.
+
+# Summary
+
+The template is parsed with comments kept, and printed back from the node
+tree. Expressions are printed with single spaces around operators and after
+commas, strings are quoted with single quotes unless they contain one, and
+parentheses are kept only where they are needed. Tags that start a line
+inside of a block or macro are indented one level deeper than the opening
+tag. Text is kept as it is, text of `verbatim` and `raw` tags that contains
+delimiters is wrapped in the `verbatim` tag. Whitespace modifiers of tags are
+kept, together with the whitespace they trim.
+
+Formatting the output again gives the same output.
+
+```rust
+use twig::environment::Environment;
+use twig::format::format;
+
+let env = Environment::default().init_all();
+
+let formatted = format(&env, "{%block title%}\n{{user.name|default(\"guest\")~\"!\"}}{%endblock%}").unwrap();
+
+assert_eq!(formatted, "{% block title %}\n    {{ user.name|default('guest') ~ '!' }}{% endblock %}\n");
+```
+
+*/
+
+use std::cmp;
+use environment::{ CompiledEnvironment, ParsingEnvironment };
+use tokens::{ Lexer, LexerOptions, Delimiters };
+use nodes::{ Parser, Parse, Module, Block };
+use nodes::body::{ Body, ImportTarget };
+use nodes::expr::{ Expr, ExprValue, ExprConstant, ExprCallType };
+use operator::{ OperatorOptions, OperatorKind, Associativity };
+use error::{ TemplateResult, TemplateError };
+
+/// Indentation added for every nested level.
+const INDENT: &'static str = "    ";
+
+/// Precedence of expressions that never need parentheses.
+const PRIMARY: u16 = ::std::u16::MAX;
+
+/// Parses template source and prints it back in normalized form.
+///
+/// Node visitors of the environment are not run, so the output matches the
+/// template as it was written.
+///
+/// Output uses the delimiters configured in the environment.
+pub fn format(env: &CompiledEnvironment, template: &str) -> TemplateResult<String> {
+    let lexer = Lexer::new(
+        LexerOptions { comments: true, .. env.lexing.options.clone() },
+        &env.lexing.operators
+    );
+    let mut tokens = lexer.tokens(template);
+    let module = try!(Module::parse(&mut Parser::new(&env.parsing, &mut tokens)));
+
+    print_module(&env.parsing, &env.lexing.options, Some(template), &module)
+}
+
+/// Prints parsed module as normalized template source, with default delimiters.
+///
+/// Fails if the module contains nodes of extension tags.
+pub fn format_module<'c>(env: &ParsingEnvironment, module: &Module<'c>) -> TemplateResult<String> {
+    print_module(env, &LexerOptions::default(), None, module)
+}
+
+/// Prints the module, whitespace modifiers are read from the source if it is
+/// known.
+fn print_module<'c>(env: &ParsingEnvironment, options: &LexerOptions, source: Option<&str>, module: &Module<'c>)
+    -> TemplateResult<String>
+{
+    let mut formatter = Formatter {
+        env: env,
+        options: options,
+        source: source,
+        blocks: &module.blocks,
+        out: String::new(),
+        indents: Vec::new(),
+        tag_end: None,
+        restored: 0,
+    };
+    if let Some(ref parent) = module.parent {
+        let tag = format!("extends {}", formatter.expr(parent, 0));
+        let written = formatter.extends_source(parent);
+        formatter.tag(&tag, parent.line, written);
+    }
+    try!(formatter.body(&module.body));
+    if formatter.tag_end.is_some() {
+        formatter.out.push('\n');
+    }
+
+    Ok(formatter.out)
+}
+
+struct Formatter<'a, 'c: 'a> {
+    env: &'a ParsingEnvironment,
+    /// Delimiters of printed tags.
+    options: &'a LexerOptions,
+    /// Formatted template source.
+    source: Option<&'a str>,
+    blocks: &'a [Block<'c>],
+    out: String,
+    indents: Vec<Indent>,
+    /// Line of the end of the last written tag, until the new line after
+    /// it is written.
+    tag_end: Option<usize>,
+    /// Source offset up to which the whitespace trimmed by modifiers is
+    /// written back.
+    restored: usize,
+}
+
+/// Tag as it was written in the source, with its whitespace modifiers.
+#[derive(Clone, Copy)]
+struct Written<'a> {
+    start: usize,
+    end: usize,
+    left: &'a str,
+    right: &'a str,
+}
+
+/// Indentation of the tag that has a body.
+struct Indent {
+    /// Indentation of the opening tag line.
+    outer: String,
+    /// Indentation of tags nested in the body.
+    nested: String,
+    /// The body starts on the line after the opening tag.
+    multiline: bool,
+}
+
+impl<'a, 'c> Formatter<'a, 'c> {
+    fn body(&mut self, body: &Body<'c>) -> TemplateResult<()> {
+        if let Some(line) = self.first_line(body) {
+            self.line_break(line);
+        }
+
+        match *body {
+            Body::List { ref items } => {
+                for item in items {
+                    try!(self.body(item));
+                }
+            },
+            Body::Text { ref value, .. } => self.text(value),
+            Body::Comment { ref value, line, span, .. } => {
+                let comment = &self.options.tag_comment;
+                let written = self.written(span.start, span.end, comment);
+                self.restore_before(written);
+                self.align_nested();
+                let (left, right) = modifiers(written);
+                let trimmed = value.trim();
+                if trimmed.len() == 0 {
+                    self.out.push_str(&format!("{}{} {}{}", comment.start, left, right, comment.end));
+                } else {
+                    self.out.push_str(&format!("{}{} {} {}{}", comment.start, left, trimmed, right, comment.end));
+                }
+                self.tag_end = Some(line + new_lines(value));
+                self.restore_after(written);
+            },
+            Body::Print { ref expr, span, .. } => {
+                let variable = &self.options.tag_variable;
+                let written = self.written(span.start, span.end, variable);
+                self.restore_before(written);
+                self.align_nested();
+                let (left, right) = modifiers(written);
+                let expr = self.expr(expr, 0);
+                self.out.push_str(&format!("{}{} {} {}{}", variable.start, left, expr, right, variable.end));
+                self.restore_after(written);
+            },
+            Body::Flush { line, span, .. } => {
+                let written = self.written(span.start, span.end, &self.options.tag_block);
+                self.tag("flush", line, written);
+            },
+            Body::BlockReference { ref name, line, span, .. } => {
+                let body = self.block_body(name);
+                let (opening, closing) = self.written_pair(span.start, span.end);
+
+                self.open(&format!("block {}", name), line, body, opening);
+                try!(self.body(body));
+                self.close("endblock", line, body, closing);
+            },
+            Body::Include { ref template, ref variables, only, ignore_missing, line, span, .. } => {
+                let mut tag = format!("include {}", self.expr(template, 0));
+                if ignore_missing {
+                    tag.push_str(" ignore missing");
+                }
+                if let Some(ref variables) = *variables {
+                    tag.push_str(&format!(" with {}", self.expr(variables, 0)));
+                }
+                if only {
+                    tag.push_str(" only");
+                }
+                let written = self.written(span.start, span.end, &self.options.tag_block);
+                self.tag(&tag, line, written);
+            },
+            Body::Import { ref source, ref targets, line, span, .. } => {
+                let tag = match targets.first() {
+                    Some(&(_, ref alias, ImportTarget::Template)) => {
+                        format!("import {} as {}", self.expr(source, 0), alias)
+                    },
+                    _ => {
+                        let targets: Vec<_> = targets.iter()
+                            .map(|&(_, ref alias, ref target)| match *target {
+                                ImportTarget::Function { ref symbol } if symbol == alias => symbol.to_string(),
+                                ImportTarget::Function { ref symbol } => format!("{} as {}", symbol, alias),
+                                ImportTarget::Template => alias.to_string(),
+                            })
+                            .collect();
+                        format!("from {} import {}", self.expr(source, 0), targets.join(", "))
+                    },
+                };
+                let written = self.written(span.start, span.end, &self.options.tag_block);
+                self.tag(&tag, line, written);
+            },
+            Body::Macro { ref name, ref body, ref arguments, line, span, .. } => {
+                let arguments: Vec<_> = arguments.iter()
+                    .map(|&(ref name, ref default)| {
+                        let name = name.as_ref().map(|n| &n[..]).unwrap_or("");
+                        match default.value {
+                            ExprValue::Constant(ExprConstant::Null) => name.to_string(),
+                            _ => format!("{}={}", name, self.expr(default, 0)),
+                        }
+                    })
+                    .collect();
+
+                let (opening, closing) = self.written_pair(span.start, span.end);
+                self.open(&format!("macro {}({})", name, arguments.join(", ")), line, body, opening);
+                try!(self.body(body));
+                self.close("endmacro", line, body, closing);
+            },
+            Body::Extension { line, .. } => return Err(
+                TemplateError::CanNotFormatExtensionNode.at(line)
+            ),
+        };
+
+        Ok(())
+    }
+
+    /// Writes text, text from `verbatim` or `raw` tags that contains
+    /// delimiters is wrapped in the `verbatim` tag again.
+    fn text(&mut self, value: &str) {
+        let options = self.options;
+        let literal = [&options.tag_variable, &options.tag_block, &options.tag_comment].iter()
+            .any(|delimiters| value.contains(&delimiters.start[..]));
+        if !literal {
+            self.out.push_str(value);
+            return;
+        }
+
+        // The lexer keeps new lines after these tags.
+        let block = &options.tag_block;
+        self.out.push_str(&format!("{} verbatim {}{}{} endverbatim {}",
+            block.start, block.end, value, block.start, block.end
+        ));
+    }
+
+    /// Writes tag that has no body.
+    fn tag(&mut self, contents: &str, line: usize, written: Option<Written<'a>>) {
+        self.restore_before(written);
+        self.align_nested();
+        self.write_tag(contents, line, written);
+    }
+
+    /// Writes opening tag and indents tags in the body one level deeper.
+    fn open(&mut self, contents: &str, line: usize, body: &Body<'c>, written: Option<Written<'a>>) {
+        self.restore_before(written);
+        self.align_nested();
+        let indent = self.line_indent();
+        self.indents.push(Indent {
+            outer: indent.clone(),
+            nested: indent + INDENT,
+            multiline: self.first_line(body).map_or(false, |first| first > line),
+        });
+        self.write_tag(contents, line, written);
+    }
+
+    /// Writes closing tag aligned with the opening tag.
+    ///
+    /// Closing tag is placed on a new line after a tag at the end of the
+    /// body, if the body starts on a new line too.
+    fn close(&mut self, contents: &str, line: usize, body: &Body<'c>, written: Option<Written<'a>>) {
+        let indent = self.indents.pop()
+            .expect("twig bug: expected closing tag to have an opening tag");
+        if self.tag_end.take().is_some() && indent.multiline {
+            self.new_line();
+        }
+        self.restore_before(written);
+        self.align(&indent.outer);
+        let end = self.close_line(line, body);
+        self.write_tag(contents, end, written);
+    }
+
+    fn write_tag(&mut self, contents: &str, end: usize, written: Option<Written<'a>>) {
+        let block = &self.options.tag_block;
+        let (left, right) = modifiers(written);
+        self.out.push_str(&format!("{}{} {} {}{}", block.start, left, contents, right, block.end));
+        self.tag_end = Some(end);
+        self.restore_after(written);
+    }
+
+    /// The lexer drops a single new line after the tag, so it is written
+    /// back if the next node was on the next line.
+    fn line_break(&mut self, line: usize) {
+        if let Some(end) = self.tag_end.take() {
+            if line > end {
+                self.new_line();
+            }
+        }
+    }
+
+    /// Writes back the new line dropped after the tag.
+    fn new_line(&mut self) {
+        self.out.push('\n');
+        if let Some(source) = self.source {
+            let rest = &source[self.restored..];
+            if rest.starts_with("\r\n") {
+                self.restored += 2;
+            } else if rest.starts_with('\n') {
+                self.restored += 1;
+            }
+        }
+    }
+
+    /// Returns the tag at `start..end` of the source, if the source is known.
+    fn written(&self, start: usize, end: usize, delimiters: &Delimiters) -> Option<Written<'a>> {
+        let source = match self.source {
+            Some(source) => source,
+            None => return None,
+        };
+        let inner = match source.get(start + delimiters.start.len() .. end.saturating_sub(delimiters.end.len())) {
+            Some(inner) if end >= start + delimiters.start.len() + delimiters.end.len() => inner,
+            _ => return None,
+        };
+        let options = self.options;
+        let modifier = |found: &Fn(&str) -> bool| {
+            [&options.whitespace_trim, &options.whitespace_line_trim].iter()
+                .find(|modifier| modifier.len() > 0 && found(modifier))
+                .map_or("", |modifier| &modifier[..])
+        };
+
+        Some(Written {
+            start: start,
+            end: end,
+            left: modifier(&|m| inner.starts_with(m)),
+            right: modifier(&|m| inner.ends_with(m)),
+        })
+    }
+
+    /// Returns opening and closing tags of the block or macro that spans
+    /// `start..end` of the source.
+    ///
+    /// The short block has no closing tag in the source, so the right
+    /// modifier of its tag is given to the closing tag.
+    fn written_pair(&self, start: usize, end: usize) -> (Option<Written<'a>>, Option<Written<'a>>) {
+        let (source, block) = match self.source {
+            Some(source) => (source, &self.options.tag_block),
+            None => return (None, None),
+        };
+        let opening_end = match tag_end(source, start + block.start.len(), &block.end) {
+            Some(opening_end) => opening_end,
+            None => return (None, None),
+        };
+        let opening = self.written(start, opening_end, block);
+        if opening_end >= end {
+            let right = opening.map_or("", |o| o.right);
+            return (
+                opening.map(|o| Written { right: "", .. o }),
+                Some(Written { start: end, end: end, left: "", right: right }),
+            );
+        }
+        let closing = source[..end].rfind(&block.start[..])
+            .and_then(|closing_start| self.written(closing_start, end, block));
+
+        (opening, closing)
+    }
+
+    /// Returns the `extends` tag of the parent template expression.
+    fn extends_source(&self, parent: &Expr<'c>) -> Option<Written<'a>> {
+        let (source, block) = match self.source {
+            Some(source) => (source, &self.options.tag_block),
+            None => return None,
+        };
+        let start = match source[..parent.span.start].rfind(&block.start[..]) {
+            Some(start) => start,
+            None => return None,
+        };
+        tag_end(source, parent.span.end, &block.end)
+            .and_then(|end| self.written(start, end, block))
+    }
+
+    /// Writes back whitespace before the tag trimmed by its left modifier.
+    fn restore_before(&mut self, written: Option<Written<'a>>) {
+        let (source, written) = match (self.source, written) {
+            (Some(source), Some(written)) if written.left.len() > 0 => (source, written),
+            _ => return,
+        };
+        let trimmed = self.trimmed(written.left);
+        let from = source[self.restored..written.start]
+            .trim_right_matches(|c| trimmed(c))
+            .len() + self.restored;
+        self.out.push_str(&source[from..written.start]);
+        self.restored = written.start;
+    }
+
+    /// Writes back whitespace after the tag trimmed by its right modifier.
+    fn restore_after(&mut self, written: Option<Written<'a>>) {
+        let (source, written) = match (self.source, written) {
+            (Some(source), Some(written)) => (source, written),
+            _ => return,
+        };
+        self.restored = cmp::max(self.restored, written.end);
+        if written.right.len() == 0 {
+            return;
+        }
+        let trimmed = self.trimmed(written.right);
+        let rest = &source[self.restored..];
+        let to = rest.len() - rest.trim_left_matches(|c| trimmed(c)).len() + self.restored;
+        self.out.push_str(&source[self.restored..to]);
+        self.restored = to;
+        // The new line after the tag is trimmed too, so it is written back.
+        self.tag_end = None;
+    }
+
+    /// Returns characters trimmed by the whitespace modifier.
+    fn trimmed(&self, modifier: &str) -> fn(char) -> bool {
+        if modifier == self.options.whitespace_trim {
+            char::is_whitespace
+        } else {
+            is_indent
+        }
+    }
+
+    fn block_body(&self, name: &str) -> &'a Body<'c> {
+        let blocks = self.blocks;
+        &blocks.iter()
+            .find(|b| b.name == name)
+            .expect("twig bug: expected block reference to point to module block")
+            .body
+    }
+
+    fn first_line(&self, body: &Body<'c>) -> Option<usize> {
+        match *body {
+            Body::List { ref items } => items.iter().filter_map(|i| self.first_line(i)).next(),
+            Body::Text { line, .. }
+            | Body::Comment { line, .. }
+            | Body::Print { line, .. }
+            | Body::Flush { line, .. }
+            | Body::BlockReference { line, .. }
+            | Body::Include { line, .. }
+            | Body::Import { line, .. }
+            | Body::Macro { line, .. }
+            | Body::Extension { line, .. } => Some(line),
+        }
+    }
+
+    /// Returns line of the end of the node, as it is written by formatter.
+    fn last_line(&self, body: &Body<'c>) -> Option<usize> {
+        match *body {
+            Body::List { ref items } => items.iter().rev().filter_map(|i| self.last_line(i)).next(),
+            Body::Text { ref value, line, .. } | Body::Comment { ref value, line, .. } => Some(line + new_lines(value)),
+            Body::BlockReference { ref name, line, .. } => Some(self.close_line(line, self.block_body(name))),
+            Body::Macro { ref body, line, .. } => Some(self.close_line(line, body)),
+            Body::Print { line, .. }
+            | Body::Flush { line, .. }
+            | Body::Include { line, .. }
+            | Body::Import { line, .. }
+            | Body::Extension { line, .. } => Some(line),
+        }
+    }
+
+    fn close_line(&self, line: usize, body: &Body<'c>) -> usize {
+        let last = match self.last_line(body) {
+            Some(last) => last,
+            None => return line,
+        };
+        let ends_with_tag = match last_node(body) {
+            Some(&Body::Text { .. }) | Some(&Body::Print { .. }) | None => false,
+            _ => true,
+        };
+        let multiline = self.first_line(body).map_or(false, |first| first > line);
+
+        if ends_with_tag && multiline {
+            last + 1
+        } else {
+            last
+        }
+    }
+
+    fn align_nested(&mut self) {
+        let indent = match self.indents.last() {
+            Some(indent) => indent.nested.clone(),
+            None => return,
+        };
+        self.align(&indent);
+    }
+
+    /// Replaces whitespace before the tag that starts a line.
+    fn align(&mut self, indent: &str) {
+        let line_start = self.line_start();
+        if self.out[line_start..].chars().all(is_indent) {
+            self.out.truncate(line_start);
+            self.out.push_str(indent);
+        }
+    }
+
+    /// Returns leading whitespace of the last output line.
+    fn line_indent(&self) -> String {
+        self.out[self.line_start()..].chars()
+            .take_while(|&c| is_indent(c))
+            .collect()
+    }
+
+    fn line_start(&self) -> usize {
+        self.out.rfind('\n').map(|i| i + 1).unwrap_or(0)
+    }
+
+    /// Prints expression, wrapped in parentheses if it binds weaker than
+    /// the specified precedence.
+    fn expr(&self, expr: &Expr<'c>, min_precedence: u16) -> String {
+        let printed = self.expr_value(expr);
+        if self.precedence(expr) < min_precedence {
+            format!("({})", printed)
+        } else {
+            printed
+        }
+    }
+
+    fn expr_value(&self, expr: &Expr<'c>) -> String {
+        match expr.value {
+            ExprValue::Constant(ref value) => constant(value),
+            ExprValue::Name(ref name) => name.to_string(),
+            ExprValue::AssignName(ref name) => name.to_string(),
+            ExprValue::Array(ref items) => {
+                let items: Vec<_> = items.iter().map(|i| self.expr(i, 0)).collect();
+                format!("[{}]", items.join(", "))
+            },
+            ExprValue::Hash(ref items) => {
+                let items: Vec<_> = items.iter()
+                    .map(|&(ref key, ref value)| format!("{}: {}", self.hash_key(key), self.expr(value, 0)))
+                    .collect();
+                format!("{{{}}}", items.join(", "))
+            },
+            ExprValue::UnaryOperator { ref value, expr: ref operand } => {
+                let value = normalized(value);
+                if let ("not", &ExprValue::Test { .. }) = (&value[..], &operand.value) {
+                    return self.test(operand, true);
+                }
+                let precedence = self.precedence(expr);
+                if value.chars().all(char::is_alphabetic) {
+                    format!("{} {}", value, self.expr(operand, precedence))
+                } else if let ExprValue::UnaryOperator { .. } = operand.value {
+                    format!("{}({})", value, self.expr(operand, 0))
+                } else {
+                    format!("{}{}", value, self.expr(operand, precedence))
+                }
+            },
+            ExprValue::BinaryOperator { ref value, ref left, ref right } => {
+                let (precedence, associativity) = self.binary_operator(value);
+                let (left_min, right_min) = match associativity {
+                    Associativity::Left => (precedence, precedence + 1),
+                    Associativity::Right => (precedence + 1, precedence),
+                };
+                let value = normalized(value);
+                let separator = if value == ".." { "" } else { " " };
+                format!("{}{}{}{}{}",
+                    self.expr(left, left_min),
+                    separator, value, separator,
+                    self.expr(right, right_min)
+                )
+            },
+            ExprValue::Concat { .. } => self.interpolated_string(expr),
+            ExprValue::Conditional { expr: ref condition, ref yay, ref nay } => format!("{} ? {} : {}",
+                self.expr(condition, 1),
+                self.expr(yay, 1),
+                self.expr(nay, 0)
+            ),
+            ExprValue::GetAttr { ref node, ref arg, ref arguments, ref call_type } => {
+                let node = self.expr(node, PRIMARY);
+                match (call_type, &arg.value) {
+                    (&ExprCallType::Array, _) => format!("{}[{}]", node, self.expr(arg, 0)),
+                    (&ExprCallType::Method, &ExprValue::Constant(ExprConstant::Str(ref name))) =>
+                        format!("{}.{}({})", node, name, self.list(arguments)),
+                    (&ExprCallType::Method, _) =>
+                        format!("{}.{}({})", node, self.expr(arg, PRIMARY), self.list(arguments)),
+                    (&ExprCallType::Any, &ExprValue::Constant(ExprConstant::Str(ref name))) =>
+                        format!("{}.{}", node, name),
+                    (&ExprCallType::Any, _) =>
+                        format!("{}.{}", node, self.expr(arg, PRIMARY)),
+                }
+            },
+            ExprValue::ImportedFunctionCall { ref alias, ref arguments, .. } =>
+                format!("{}({})", alias, self.list(arguments)),
+            ExprValue::FunctionCall { ref name, ref arguments } => {
+                let arguments: Vec<_> = arguments.iter()
+                    .map(|&(ref name, ref value)| match *name {
+                        Some(ref name) => format!("{}={}", name, self.expr(value, 0)),
+                        None => self.expr(value, 0),
+                    })
+                    .collect();
+                format!("{}({})", name, arguments.join(", "))
+            },
+            ExprValue::Filter { ref name, expr: ref operand, ref arguments } => {
+                let operand = self.expr(operand, PRIMARY);
+                if arguments.len() == 0 {
+                    format!("{}|{}", operand, name)
+                } else {
+                    format!("{}|{}({})", operand, name, self.list(arguments))
+                }
+            },
+            ExprValue::Test { .. } => self.test(expr, false),
+        }
+    }
+
+    /// Prints test expression, i.e. `value is defined` or `value is not defined`.
+    fn test(&self, expr: &Expr<'c>, negated: bool) -> String {
+        match expr.value {
+            ExprValue::Test { ref name, expr: ref operand, ref arguments } => {
+                let (precedence, _) = self.binary_operator("is");
+                let operand = self.expr(operand, precedence);
+                let is = if negated { "is not" } else { "is" };
+                if arguments.len() == 0 {
+                    format!("{} {} {}", operand, is, name)
+                } else {
+                    format!("{} {} {}({})", operand, is, name, self.list(arguments))
+                }
+            },
+            _ => unreachable!("twig bug: expected test expression"),
+        }
+    }
+
+    /// Prints string with interpolated expressions.
+    ///
+    /// The parser does not produce two string parts in a row, so a string
+    /// after another string is printed as interpolated constant.
+    fn interpolated_string(&self, expr: &Expr<'c>) -> String {
+        let mut parts = Vec::new();
+        concat_parts(expr, &mut parts);
+
+        let mut out = String::from("\"");
+        let mut after_string = false;
+        for part in parts {
+            match part.value {
+                ExprValue::Constant(ExprConstant::Str(ref value)) if !after_string && fits_double_quotes(value) => {
+                    out.push_str(value);
+                    after_string = true;
+                },
+                _ => {
+                    out.push_str(&format!("#{{{}}}", self.expr(part, 0)));
+                    after_string = false;
+                },
+            }
+        }
+        out.push('"');
+
+        out
+    }
+
+    /// Prints hash key, names and strings are printed as quoted strings.
+    fn hash_key(&self, key: &Expr<'c>) -> String {
+        match key.value {
+            ExprValue::Constant(ExprConstant::Str(_))
+            | ExprValue::Constant(ExprConstant::Int(_))
+            | ExprValue::Constant(ExprConstant::Float(_))
+            | ExprValue::Constant(ExprConstant::Big(_)) => self.expr(key, 0),
+            _ => format!("({})", self.expr(key, 0)),
+        }
+    }
+
+    fn list(&self, items: &[Expr<'c>]) -> String {
+        let items: Vec<_> = items.iter().map(|i| self.expr(i, 0)).collect();
+        items.join(", ")
+    }
+
+    /// Returns precedence of expression for placing parentheses.
+    fn precedence(&self, expr: &Expr<'c>) -> u16 {
+        match expr.value {
+            ExprValue::UnaryOperator { ref value, expr: ref operand } => match (&normalized(value)[..], &operand.value) {
+                ("not", &ExprValue::Test { .. }) => self.binary_operator("is not").0,
+                (value, _) => self.unary_operator(value),
+            },
+            ExprValue::BinaryOperator { ref value, .. } => self.binary_operator(value).0,
+            ExprValue::Test { .. } => self.binary_operator("is").0,
+            ExprValue::Conditional { .. } => 0,
+            _ => PRIMARY,
+        }
+    }
+
+    /// Unknown unary operators are assumed to bind tighter than any binary.
+    fn unary_operator(&self, value: &str) -> u16 {
+        match self.env.operators.get(&normalized(value)[..]) {
+            Some(&OperatorOptions { kind: OperatorKind::Unary { .. }, precedence: Some(precedence) }) => precedence,
+            _ => PRIMARY - 1,
+        }
+    }
+
+    fn binary_operator(&self, value: &str) -> (u16, Associativity) {
+        match self.env.operators.get(&normalized(value)[..]) {
+            Some(&OperatorOptions { kind: OperatorKind::Binary { associativity, .. }, precedence: Some(precedence) }) =>
+                (precedence, associativity),
+            _ => (1, Associativity::Left),
+        }
+    }
+}
+
+fn constant(value: &ExprConstant) -> String {
+    match *value {
+        ExprConstant::Str(ref value) => quote(value),
+        ExprConstant::Bool(value) => value.to_string(),
+        ExprConstant::Int(value) => value.to_string(),
+        ExprConstant::Float(value) => format!("{:?}", value),
+        ExprConstant::Big(ref value) => value.to_string(),
+        ExprConstant::Null => "null".into(),
+    }
+}
+
+/// Quotes string as it was written in the template, without changing its
+/// escape sequences.
+///
+/// Single quotes are used unless the string contains not escaped single quote.
+fn quote(value: &str) -> String {
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\'' => return format!("\"{}\"", value),
+            _ => (),
+        }
+    }
+    format!("'{}'", value)
+}
+
+/// Returns true if string can be placed in double quotes without changing it.
+fn fits_double_quotes(value: &str) -> bool {
+    let mut escaped = false;
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return false,
+            '#' if chars.peek() == Some(&'{') => return false,
+            _ => (),
+        }
+    }
+    true
+}
+
+/// Collects parts of concatenated string in order.
+fn concat_parts<'r, 'c>(expr: &'r Expr<'c>, parts: &mut Vec<&'r Expr<'c>>) {
+    match expr.value {
+        ExprValue::Concat { ref left, ref right } => {
+            concat_parts(left, parts);
+            parts.push(right);
+        },
+        _ => parts.push(expr),
+    }
+}
+
+/// Operators like "not in" can be written with any whitespace inside.
+fn normalized(value: &str) -> String {
+    let words: Vec<_> = value.split_whitespace().collect();
+    words.join(" ")
+}
+
+/// Returns the last node that is not a list.
+fn last_node<'r, 'c>(body: &'r Body<'c>) -> Option<&'r Body<'c>> {
+    match *body {
+        Body::List { ref items } => items.iter().rev().filter_map(last_node).next(),
+        ref other => Some(other),
+    }
+}
+
+/// Returns modifiers of the written tag, none if the tag is not known.
+fn modifiers<'a>(written: Option<Written<'a>>) -> (&'a str, &'a str) {
+    written.map_or(("", ""), |written| (written.left, written.right))
+}
+
+/// Returns the end of the tag, after the first end delimiter outside of
+/// strings, starting at `from`.
+fn tag_end(source: &str, from: usize, delimiter: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in source[from..].char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if source[from + i..].starts_with(delimiter) => return Some(from + i + delimiter.len()),
+            None => (),
+        }
+    }
+    None
+}
+
+fn new_lines(value: &str) -> usize {
+    value.chars().filter(|&c| c == '\n').count()
+}
+
+fn is_indent(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
@@ -13,13 +13,13 @@ pub enum Arg {
 pub enum Callable {
     /// Executable at runtime.
     Dynamic(Box<
-        for<'e> Fn(&'e [Value]) -> RuntimeResult<Value>
+        for<'e> Fn(&'e [Value]) -> RuntimeResult<Value> + Send + Sync
     >),
     /// Inlined into instructions at compile time.
     Static {
         arguments: Vec<Arg>,
         compile: Box<
-            for<'c> Fn(&mut Staging<'c, Value>) -> TemplateResult<CompiledExpression>
+            for<'c> Fn(&mut Staging<'c, Value>) -> TemplateResult<CompiledExpression> + Send + Sync
         >
     }
 }
@@ -37,7 +37,7 @@ impl Function {
     )
         -> Function
     where
-        F: for<'e> Fn(&'e [Value]) -> RuntimeResult<Value>
+        F: for<'e> Fn(&'e [Value]) -> RuntimeResult<Value> + Send + Sync
     {
         Function {
             name: name,
@@ -52,7 +52,7 @@ impl Function {
     )
         -> Function
     where
-        F: for<'c> Fn(&mut Staging<'c, Value>) -> TemplateResult<CompiledExpression>
+        F: for<'c> Fn(&mut Staging<'c, Value>) -> TemplateResult<CompiledExpression> + Send + Sync
     {
         Function {
             name: name,
@@ -69,3 +69,63 @@ impl fmt::Debug for Function {
         write!(f, "{}()", self.name)
     }
 }
+
+/// Represents environment filter, i.e. `value|upper`.
+///
+/// The filtered value is passed as the first argument.
+pub struct Filter {
+    pub name: &'static str,
+    pub callable: Callable,
+}
+
+impl Filter {
+    pub fn new_dynamic<F: 'static>(
+        name: &'static str,
+        callable: F
+    )
+        -> Filter
+    where
+        F: for<'e> Fn(&'e [Value]) -> RuntimeResult<Value> + Send + Sync
+    {
+        Filter {
+            name: name,
+            callable: Callable::Dynamic(Box::new(callable)),
+        }
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "|{}", self.name)
+    }
+}
+
+/// Represents environment test, i.e. `value is even`.
+///
+/// The tested value is passed as the first argument.
+pub struct Test {
+    pub name: &'static str,
+    pub callable: Callable,
+}
+
+impl Test {
+    pub fn new_dynamic<F: 'static>(
+        name: &'static str,
+        callable: F
+    )
+        -> Test
+    where
+        F: for<'e> Fn(&'e [Value]) -> RuntimeResult<bool> + Send + Sync
+    {
+        Test {
+            name: name,
+            callable: Callable::Dynamic(Box::new(move |args| callable(args).map(Value::Bool))),
+        }
+    }
+}
+
+impl fmt::Debug for Test {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "is {}", self.name)
+    }
+}
@@ -0,0 +1,19 @@
+use std::fmt;
+use value::Value;
+
+/// Value of environment global, visible in all templates.
+pub enum Global {
+    /// Value shared by all renders.
+    Value(Value),
+    /// Value computed on first use, at most once per render.
+    Lazy(Box<Fn() -> Value + Send + Sync>),
+}
+
+impl fmt::Debug for Global {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Global::Value(ref value) => write!(f, "Value({:?})", value),
+            Global::Lazy(_) => write!(f, "Lazy"),
+        }
+    }
+}
@@ -0,0 +1,468 @@
+/*!
+Binary representation of compiled `Program`.
+
+The format starts with `little` bytecode header, followed by format version,
+constants, runtime function names, instructions, block programs, macros and
+the parent template name. All numbers are little endian.
+*/
+
+use std::io::{ self, Read };
+use std::collections::HashMap;
+use byteorder::{ LittleEndian, ReadBytesExt, WriteBytesExt };
+use little::bytecode::{ Serializer, Header, Error };
+use little::{ Instruction, Mem, Cond, Constant, Binding, Call };
+use value::{ Value, HashKey };
+use super::{ Program, Macro };
+
+/// Binary format version, increment it when the format changes.
+pub const FORMAT_VERSION: u32 = 11;
+
+impl Serializer for Program {
+    fn serialize<O: io::Write>(&self, output: &mut O) -> Result<u64, Error> {
+        let mut len = try!(Header::new().serialize(output));
+        len += try!(write_u32(output, FORMAT_VERSION));
+        len += try!(write_program(output, self));
+
+        Ok(len)
+    }
+
+    fn deserialize<I: io::Read>(input: &mut I) -> Result<(u64, Program), Error> {
+        let (mut len, header) = try!(Header::deserialize(input));
+        if !header.is_magical() {
+            return Err(Error::InvalidBinaryFormat);
+        }
+        if try!(input.read_u32::<LittleEndian>()) != FORMAT_VERSION {
+            return Err(Error::InvalidBinaryFormat);
+        }
+        len += 4;
+
+        let program = try!(read_program(input, &mut len));
+
+        Ok((len, program))
+    }
+}
+
+fn write_program<O: io::Write>(output: &mut O, program: &Program) -> Result<u64, Error> {
+    let mut len = try!(write_u32(output, program.constants.len() as u32));
+    for value in &program.constants {
+        len += try!(write_value(output, value));
+    }
+
+    len += try!(write_u32(output, program.calls.len() as u32));
+    for name in &program.calls {
+        len += try!(write_str(output, name));
+    }
+
+    len += try!(write_u32(output, program.instructions.len() as u32));
+    for instruction in &program.instructions {
+        len += try!(write_instruction(output, instruction));
+    }
+
+    len += try!(write_u32(output, program.blocks.len() as u32));
+    for &(ref name, ref block) in &program.blocks {
+        len += try!(write_str(output, name));
+        len += try!(write_program(output, block));
+    }
+
+    len += try!(write_u32(output, program.macros.len() as u32));
+    for &(ref name, ref found) in &program.macros {
+        len += try!(write_str(output, name));
+        len += try!(write_u32(output, found.arguments.len() as u32));
+        for &(ref argument, ref default) in &found.arguments {
+            len += try!(write_str(output, argument));
+            len += try!(write_value(output, default));
+        }
+        len += try!(write_program(output, &found.program));
+    }
+
+    len += match program.parent {
+        Some(ref parent) => try!(write_u8(output, 1)) + try!(write_str(output, parent)),
+        None => try!(write_u8(output, 0)),
+    };
+
+    Ok(len)
+}
+
+fn read_program<I: io::Read>(input: &mut I, len: &mut u64) -> Result<Program, Error> {
+    let mut program = Program::new();
+
+    for _ in 0..try!(read_len(input, len)) {
+        program.constants.push(try!(read_value(input, len)));
+    }
+    for _ in 0..try!(read_len(input, len)) {
+        program.calls.push(try!(read_str(input, len)));
+    }
+    for _ in 0..try!(read_len(input, len)) {
+        program.instructions.push(try!(read_instruction(input, len)));
+    }
+    for _ in 0..try!(read_len(input, len)) {
+        let name = try!(read_str(input, len));
+        program.blocks.push((name, try!(read_program(input, len))));
+    }
+    for _ in 0..try!(read_len(input, len)) {
+        let name = try!(read_str(input, len));
+        let mut arguments = Vec::new();
+        for _ in 0..try!(read_len(input, len)) {
+            let argument = try!(read_str(input, len));
+            arguments.push((argument, try!(read_value(input, len))));
+        }
+        let code = try!(read_program(input, len));
+        program.macros.push((name, Macro { arguments: arguments, program: code }));
+    }
+    program.parent = match try!(read_u8(input, len)) {
+        0 => None,
+        1 => Some(try!(read_str(input, len))),
+        _ => return Err(Error::InvalidBinaryFormat),
+    };
+
+    Ok(program)
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidInput, format!("{} can not be serialized", what)))
+}
+
+fn write_u8<O: io::Write>(output: &mut O, value: u8) -> Result<u64, Error> {
+    try!(output.write_u8(value));
+    Ok(1)
+}
+
+fn write_u32<O: io::Write>(output: &mut O, value: u32) -> Result<u64, Error> {
+    try!(output.write_u32::<LittleEndian>(value));
+    Ok(4)
+}
+
+fn write_str<O: io::Write>(output: &mut O, value: &str) -> Result<u64, Error> {
+    let len = try!(write_u32(output, value.len() as u32));
+    try!(output.write_all(value.as_bytes()));
+    Ok(len + value.len() as u64)
+}
+
+fn write_value<O: io::Write>(output: &mut O, value: &Value) -> Result<u64, Error> {
+    Ok(match *value {
+        Value::Null => try!(write_u8(output, 0)),
+        Value::Int(v) => {
+            try!(output.write_u8(1));
+            try!(output.write_i64::<LittleEndian>(v));
+            9
+        },
+        Value::Float(v) => {
+            try!(output.write_u8(2));
+            try!(output.write_f64::<LittleEndian>(v));
+            9
+        },
+        Value::Str(ref v) => try!(write_u8(output, 3)) + try!(write_str(output, v)),
+        Value::Array(ref items) => {
+            let mut len = try!(write_u8(output, 4)) + try!(write_u32(output, items.len() as u32));
+            for item in items {
+                len += try!(write_value(output, item));
+            }
+            len
+        },
+        Value::Hash(ref items) => {
+            let mut len = try!(write_u8(output, 5)) + try!(write_u32(output, items.len() as u32));
+            for (key, item) in items {
+                len += match *key {
+                    HashKey::Int(v) => {
+                        try!(output.write_u8(0));
+                        try!(output.write_i64::<LittleEndian>(v));
+                        9
+                    },
+                    HashKey::Str(ref v) => try!(write_u8(output, 1)) + try!(write_str(output, v)),
+                };
+                len += try!(write_value(output, item));
+            }
+            len
+        },
+        Value::Bool(v) => try!(write_u8(output, 6)) + try!(write_u8(output, v as u8)),
+        Value::Obj(_) => return Err(unsupported("Object")),
+        Value::Func(_) => return Err(unsupported("Function")),
+    })
+}
+
+fn write_mem<O: io::Write>(output: &mut O, mem: &Mem) -> Result<u64, Error> {
+    Ok(match *mem {
+        Mem::Const(Constant(i)) => try!(write_u8(output, 0)) + try!(write_u32(output, i)),
+        Mem::Binding(Binding(i)) => try!(write_u8(output, 1)) + try!(write_u32(output, i)),
+        Mem::Parameter { name: Constant(i) } => try!(write_u8(output, 2)) + try!(write_u32(output, i)),
+        Mem::Parameters => try!(write_u8(output, 3)),
+        Mem::StackTop1 => try!(write_u8(output, 4)),
+        Mem::StackTop2 => try!(write_u8(output, 5)),
+    })
+}
+
+fn write_cond<O: io::Write>(output: &mut O, cond: Cond) -> Result<u64, Error> {
+    write_u8(output, match cond {
+        Cond::Eq => 0,
+        Cond::Ne => 1,
+        Cond::Gt => 2,
+        Cond::Lt => 3,
+        Cond::Gte => 4,
+        Cond::Lte => 5,
+    })
+}
+
+fn write_instruction<O: io::Write>(output: &mut O, instruction: &Instruction) -> Result<u64, Error> {
+    Ok(match *instruction {
+        Instruction::Output { ref location } => try!(write_u8(output, 0)) + try!(write_mem(output, location)),
+        Instruction::Property { ref name } => try!(write_u8(output, 1)) + try!(write_mem(output, name)),
+        Instruction::Push { ref location } => try!(write_u8(output, 2)) + try!(write_mem(output, location)),
+        Instruction::Pop { times } => {
+            try!(output.write_u8(3));
+            try!(output.write_u16::<LittleEndian>(times));
+            3
+        },
+        Instruction::Jump { pc } => {
+            try!(output.write_u8(4));
+            try!(output.write_u16::<LittleEndian>(pc));
+            3
+        },
+        Instruction::CondJump { pc, ref location, test } => {
+            try!(output.write_u8(5));
+            try!(output.write_u16::<LittleEndian>(pc));
+            3 + try!(write_mem(output, location)) + try!(write_cond(output, test))
+        },
+        Instruction::Call { call: Call(i), argc, push_result_to_stack } => {
+            try!(output.write_u8(6));
+            try!(output.write_u32::<LittleEndian>(i));
+            try!(output.write_u8(argc));
+            try!(output.write_u8(push_result_to_stack as u8));
+            7
+        },
+        Instruction::Load { binding: Binding(i), ref location } => {
+            try!(output.write_u8(7));
+            try!(output.write_u32::<LittleEndian>(i));
+            5 + try!(write_mem(output, location))
+        },
+        Instruction::Interupt => try!(write_u8(output, 8)),
+    })
+}
+
+fn read_u8<I: io::Read>(input: &mut I, len: &mut u64) -> Result<u8, Error> {
+    *len += 1;
+    Ok(try!(input.read_u8()))
+}
+
+fn read_u16<I: io::Read>(input: &mut I, len: &mut u64) -> Result<u16, Error> {
+    *len += 2;
+    Ok(try!(input.read_u16::<LittleEndian>()))
+}
+
+fn read_u32<I: io::Read>(input: &mut I, len: &mut u64) -> Result<u32, Error> {
+    *len += 4;
+    Ok(try!(input.read_u32::<LittleEndian>()))
+}
+
+fn read_i64<I: io::Read>(input: &mut I, len: &mut u64) -> Result<i64, Error> {
+    *len += 8;
+    Ok(try!(input.read_i64::<LittleEndian>()))
+}
+
+fn read_len<I: io::Read>(input: &mut I, len: &mut u64) -> Result<u32, Error> {
+    read_u32(input, len)
+}
+
+fn read_str<I: io::Read>(input: &mut I, len: &mut u64) -> Result<String, Error> {
+    let str_len = try!(read_u32(input, len)) as usize;
+    // length comes from the file, so the buffer grows only with bytes actually read
+    let mut bytes = Vec::new();
+    let read = try!(input.by_ref().take(str_len as u64).read_to_end(&mut bytes));
+    if read != str_len {
+        return Err(Error::UnexpectedEOF);
+    }
+    *len += str_len as u64;
+    String::from_utf8(bytes).map_err(|_| Error::InvalidBinaryFormat)
+}
+
+fn read_value<I: io::Read>(input: &mut I, len: &mut u64) -> Result<Value, Error> {
+    Ok(match try!(read_u8(input, len)) {
+        0 => Value::Null,
+        1 => Value::Int(try!(read_i64(input, len))),
+        2 => {
+            *len += 8;
+            Value::Float(try!(input.read_f64::<LittleEndian>()))
+        },
+        3 => Value::Str(try!(read_str(input, len))),
+        4 => {
+            let mut items = Vec::new();
+            for _ in 0..try!(read_len(input, len)) {
+                items.push(try!(read_value(input, len)));
+            }
+            Value::Array(items)
+        },
+        5 => {
+            let mut items = HashMap::new();
+            for _ in 0..try!(read_len(input, len)) {
+                let key = match try!(read_u8(input, len)) {
+                    0 => HashKey::Int(try!(read_i64(input, len))),
+                    1 => HashKey::Str(try!(read_str(input, len))),
+                    _ => return Err(Error::InvalidBinaryFormat),
+                };
+                items.insert(key, try!(read_value(input, len)));
+            }
+            Value::Hash(items)
+        },
+        6 => Value::Bool(try!(read_u8(input, len)) != 0),
+        _ => return Err(Error::InvalidBinaryFormat),
+    })
+}
+
+fn read_mem<I: io::Read>(input: &mut I, len: &mut u64) -> Result<Mem, Error> {
+    Ok(match try!(read_u8(input, len)) {
+        0 => Mem::Const(Constant(try!(read_u32(input, len)))),
+        1 => Mem::Binding(Binding(try!(read_u32(input, len)))),
+        2 => Mem::Parameter { name: Constant(try!(read_u32(input, len))) },
+        3 => Mem::Parameters,
+        4 => Mem::StackTop1,
+        5 => Mem::StackTop2,
+        _ => return Err(Error::InvalidBinaryFormat),
+    })
+}
+
+fn read_cond<I: io::Read>(input: &mut I, len: &mut u64) -> Result<Cond, Error> {
+    Ok(match try!(read_u8(input, len)) {
+        0 => Cond::Eq,
+        1 => Cond::Ne,
+        2 => Cond::Gt,
+        3 => Cond::Lt,
+        4 => Cond::Gte,
+        5 => Cond::Lte,
+        _ => return Err(Error::InvalidBinaryFormat),
+    })
+}
+
+fn read_instruction<I: io::Read>(input: &mut I, len: &mut u64) -> Result<Instruction, Error> {
+    Ok(match try!(read_u8(input, len)) {
+        0 => Instruction::Output { location: try!(read_mem(input, len)) },
+        1 => Instruction::Property { name: try!(read_mem(input, len)) },
+        2 => Instruction::Push { location: try!(read_mem(input, len)) },
+        3 => Instruction::Pop { times: try!(read_u16(input, len)) },
+        4 => Instruction::Jump { pc: try!(read_u16(input, len)) },
+        5 => Instruction::CondJump {
+            pc: try!(read_u16(input, len)),
+            location: try!(read_mem(input, len)),
+            test: try!(read_cond(input, len)),
+        },
+        6 => Instruction::Call {
+            call: Call(try!(read_u32(input, len))),
+            argc: try!(read_u8(input, len)),
+            push_result_to_stack: try!(read_u8(input, len)) != 0,
+        },
+        7 => Instruction::Load {
+            binding: Binding(try!(read_u32(input, len))),
+            location: try!(read_mem(input, len)),
+        },
+        8 => Instruction::Interupt,
+        _ => return Err(Error::InvalidBinaryFormat),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::collections::HashMap;
+    use little::bytecode::{ Serializer, Error };
+    use little::{ Instruction, Mem, Cond, Constant, Binding, Call };
+    use value::{ Value, HashKey };
+    use instructions::{ Program, Macro };
+
+    fn program() -> Program {
+        let mut hash = HashMap::new();
+        hash.insert(HashKey::Str("a".into()), Value::Int(1));
+        hash.insert(HashKey::Int(2), Value::Array(vec![Value::Null, Value::Float(1.5)]));
+
+        Program {
+            constants: vec![Value::Str("hello".into()), Value::Int(-42), Value::Bool(true), Value::Hash(hash)],
+            calls: vec!["include".into()],
+            instructions: vec![
+                Instruction::Output { location: Mem::Const(Constant(0)) },
+                Instruction::Push { location: Mem::Parameters },
+                Instruction::Load { binding: Binding(3), location: Mem::StackTop1 },
+                Instruction::CondJump { pc: 7, location: Mem::Binding(Binding(3)), test: Cond::Gte },
+                Instruction::Call { call: Call(0), argc: 1, push_result_to_stack: true },
+                Instruction::Pop { times: 2 },
+                Instruction::Interupt,
+            ],
+            blocks: vec![
+                ("title".into(), Program {
+                    constants: vec![Value::Str("title".into())],
+                    calls: vec![],
+                    instructions: vec![Instruction::Output { location: Mem::Const(Constant(0)) }],
+                    blocks: vec![],
+                    macros: vec![],
+                    parent: None,
+                }),
+            ],
+            macros: vec![
+                ("input".into(), Macro {
+                    arguments: vec![("name".into(), Value::Null), ("type".into(), Value::Str("text".into()))],
+                    program: Program::new(),
+                }),
+            ],
+            parent: Some("base.twig".into()),
+        }
+    }
+
+    #[test]
+    fn program_survives_round_trip() {
+        let original = program();
+        let mut bytes = Vec::new();
+        let written = original.serialize(&mut bytes).unwrap();
+        assert_eq!(written, bytes.len() as u64);
+
+        let (read, restored) = Program::deserialize(&mut Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(read, written);
+        assert_eq!(restored.constants[..3], original.constants[..3]);
+        match (&restored.constants[3], &original.constants[3]) {
+            (&Value::Hash(ref restored), &Value::Hash(ref original)) => assert_eq!(restored, original),
+            other => panic!("expected hashes, got {:?}", other),
+        }
+        assert_eq!(restored.calls, original.calls);
+        assert_eq!(format!("{:?}", restored.instructions), format!("{:?}", original.instructions));
+        assert_eq!(restored.blocks.len(), 1);
+        assert_eq!(restored.blocks[0].0, "title");
+        assert_eq!(restored.blocks[0].1.constants, original.blocks[0].1.constants);
+        assert_eq!(restored.macros.len(), 1);
+        assert_eq!(restored.macros[0].0, "input");
+        assert_eq!(restored.macros[0].1.arguments, original.macros[0].1.arguments);
+        assert_eq!(restored.parent, original.parent);
+        assert_eq!(restored.blocks[0].1.parent, None);
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let mut bytes = Vec::new();
+        program().serialize(&mut bytes).unwrap();
+        bytes[0] = !bytes[0];
+
+        match Program::deserialize(&mut Cursor::new(&bytes[..])) {
+            Err(Error::InvalidBinaryFormat) => (),
+            other => panic!("expected invalid format error, got {:?}", other.map(|(l, _)| l)),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let mut bytes = Vec::new();
+        program().serialize(&mut bytes).unwrap();
+        let len = bytes.len();
+        bytes.truncate(len - 3);
+
+        assert!(Program::deserialize(&mut Cursor::new(&bytes[..])).is_err());
+    }
+
+    #[test]
+    fn rejects_string_longer_than_data() {
+        let mut bytes = Vec::new();
+        program().serialize(&mut bytes).unwrap();
+        let at = bytes.windows(5).position(|w| w == b"hello").unwrap();
+        for b in &mut bytes[at - 4 .. at] {
+            *b = 0xFF;
+        }
+
+        match Program::deserialize(&mut Cursor::new(&bytes[..])) {
+            Err(Error::UnexpectedEOF) => (),
+            other => panic!("expected unexpected end of file, got {:?}", other.map(|(l, _)| l)),
+        }
+    }
+}
@@ -1,9 +1,12 @@
-use little::{ Instruction };
+use little::{ Instruction, Mem };
 use instructions::{ Compile, CompileExpression };
-use nodes::body::Body;
+use nodes::body::{ Body, ImportTarget };
+use nodes::expr::{ Expr, ExprValue };
 use value::Value;
 use error::{ TemplateResult };
 use mold::Staging;
+use super::{ call, compile_operand, position_args };
+use super::expr::compile_printed;
 
 impl<'c> Compile<'c> for Body<'c> {
     fn compile<'r>(&'r self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<()> {
@@ -16,20 +19,153 @@ impl<'c> Compile<'c> for Body<'c> {
                 }
                 Ok(())
             },
-            Body::Text { .. } => unreachable!("Body::Text::compile"),
+            Body::Text { ref value, .. } => {
+                trace!("Body::Text::compile");
+
+                let text = stage.include_const(Value::Str(value.to_string()));
+                stage.instr(Instruction::Output { location: text });
+
+                Ok(())
+            },
             Body::Print { ref expr, .. } => {
                 trace!("Body::Print::compile");
 
-                let ce = try!(expr.compile(stage));
-                if let Some(result) = ce.result() {
-                    stage.instr(Instruction::Output { location: result });
+                if let Some(printed) = try!(compile_printed(expr, stage)) {
+                    return printed.finalize(stage);
+                }
+
+                if is_safe(expr) {
+                    let ce = try!(expr.compile(stage));
+                    if let Some(result) = ce.result() {
+                        stage.instr(Instruction::Output { location: result });
+                    };
+                    return ce.finalize(stage);
+                }
+
+                // Escaping strategy is picked for the template at runtime.
+                let value = try!(compile_operand(expr, stage));
+                let escaped = call(stage, "Body::Print", "__escape", vec![value]);
+                stage.instr(Instruction::Output { location: escaped.result().expect("call has result") });
+                try!(escaped.finalize(stage));
+
+                Ok(())
+            },
+            Body::Comment { .. } => Ok(()),
+            Body::Flush { .. } => {
+                trace!("Body::Flush::compile");
+
+                // The engine flushes the writer when interrupted.
+                stage.instr(Instruction::Interupt);
+
+                Ok(())
+            },
+            Body::BlockReference { ref name, line, column, span } => {
+                trace!("Body::BlockReference::compile");
+
+                let name = stage.include_const(Value::Str(name.to_string()));
+                stage.instr(Instruction::Push { location: name });
+                stage.instr(Instruction::Push { location: Mem::Parameters });
+                for position in position_args(line, column, span, stage) {
+                    stage.instr(Instruction::Push { location: position });
+                }
+
+                // The block is written to the template output.
+                let call = stage.use_call("__block");
+                stage.instr(Instruction::Call { call: call, argc: 6, push_result_to_stack: false });
+                stage.instr(Instruction::Pop { times: 6 });
+
+                Ok(())
+            },
+            Body::Include { ref template, ref variables, only, ignore_missing, line, column, span } => {
+                trace!("Body::Include::compile");
+
+                let name = try!(template.compile(stage));
+                let vars = match *variables {
+                    Some(ref variables) => Some(try!(variables.compile(stage))),
+                    None => None,
+                };
+
+                let name_mem = name.result().unwrap_or_else(|| stage.include_const(Value::Null));
+                stage.instr(Instruction::Push { location: name_mem });
+
+                let context_mem = if only {
+                    stage.include_const(Value::Null)
+                } else {
+                    Mem::Parameters
+                };
+                stage.instr(Instruction::Push { location: context_mem });
+
+                let vars_mem = vars.as_ref()
+                    .and_then(|v| v.result())
+                    .unwrap_or_else(|| stage.include_const(Value::Null));
+                stage.instr(Instruction::Push { location: vars_mem });
+
+                let ignore_missing_mem = stage.include_const(Value::Int(ignore_missing as i64));
+                stage.instr(Instruction::Push { location: ignore_missing_mem });
+                for position in position_args(line, column, span, stage) {
+                    stage.instr(Instruction::Push { location: position });
+                }
+
+                // The included template is written to the template output.
+                let call = stage.use_call("__include");
+                stage.instr(Instruction::Call { call: call, argc: 8, push_result_to_stack: false });
+                stage.instr(Instruction::Pop { times: 8 });
+
+                if let Some(vars) = vars {
+                    try!(vars.finalize(stage));
+                }
+                try!(name.finalize(stage));
+
+                Ok(())
+            },
+            Body::Import { ref source, ref targets, line, column, span } => {
+                trace!("Body::Import::compile");
+
+                // `_self` imports macros of the current template
+                let source = match source.value {
+                    ExprValue::Name(ref name) if name == "_self" => stage.include_const(Value::Null),
+                    _ => try!(compile_operand(source, stage)),
                 };
-                try!(ce.finalize(stage));
+                for &(ref uuid, _, ref target) in targets {
+                    let symbol = match *target {
+                        ImportTarget::Function { ref symbol } => Value::Str(symbol.to_string()),
+                        ImportTarget::Template => Value::Null,
+                    };
+                    let mut args = vec![
+                        stage.include_const(Value::Str(uuid.to_hyphenated_string())),
+                        source.clone(),
+                        stage.include_const(symbol),
+                    ];
+                    args.extend(position_args(line, column, span, stage));
+                    try!(call(stage, "Body::Import", "__import", args).finalize(stage));
+                }
 
                 Ok(())
             },
-            Body::Import { .. } => unreachable!("Body::Import::compile"),
-            Body::Macro { .. } => unreachable!("Body::Macro::compile"),
+            // Macros are compiled together with the template, see `compile`.
+            Body::Macro { .. } => Ok(()),
+            Body::Extension { ref node, .. } => {
+                trace!("Body::Extension::compile");
+                node.compile(stage)
+            },
         }
     }
 }
+
+/// Returns true if printed expression does not need escaping.
+///
+/// Constants are written by template author, included templates and macros
+/// are escaped when they are rendered, and "raw" or "escape" filter output is
+/// trusted as is. Concatenation is safe when both sides are.
+fn is_safe(expr: &Expr) -> bool {
+    match expr.value {
+        ExprValue::Constant(_) => true,
+        ExprValue::FunctionCall { ref name, .. } => name == "include",
+        ExprValue::ImportedFunctionCall { .. } => true,
+        ExprValue::Filter { ref name, .. } => name == "raw" || name == "escape" || name == "e",
+        ExprValue::BinaryOperator { ref value, ref left, ref right } => {
+            value == "~" && is_safe(left) && is_safe(right)
+        },
+        _ => false,
+    }
+}
@@ -1,5 +1,7 @@
+use little::{ Instruction, Mem, Cond };
 use instructions::{ CompileExpression, CompiledExpression };
-use nodes::expr::{ Expr, ExprValue };
+use super::{ call, compile_operand, position_args };
+use nodes::expr::{ Expr, ExprValue, ExprConstant, ExprCallType };
 use value::Value;
 use error::TemplateResult;
 use mold::Staging;
@@ -8,34 +10,323 @@ impl<'c> CompileExpression<'c> for Expr<'c> {
     fn compile<'r>(&'r self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<CompiledExpression> {
         trace!("Expr::compile");
         Ok(match self.value {
-            ExprValue::Constant(_) => unreachable!("ExprValue::Constant::compile"),
-            ExprValue::Name(name) => {
-                let maybe_mem = stage.use_name(name);
-
-                let name_mem = match maybe_mem {
-                    Some(mem) => {
-                        trace!("use mem {:?} for name {:?}", mem, name);
-                        mem
-                    },
-                    None => {
-                        stage.include_const(Value::Str(name.into()))
-                    }
-                };
-
-                CompiledExpression::with_result("ExprValue::Name", name_mem)
+            ExprValue::Constant(ref constant) => {
+                let value = constant_value(constant);
+
+                CompiledExpression::with_result("ExprValue::Constant", stage.include_const(value))
             },
+            ExprValue::Name(ref name) => compile_name(name, self, false, stage),
             ExprValue::AssignName(_) => unreachable!("ExprValue::AssignName::compile"),
-            ExprValue::Array(_) => unreachable!("ExprValue::Array::compile"),
-            ExprValue::Hash(_) => unreachable!("ExprValue::Hash::compile"),
-            ExprValue::UnaryOperator { .. } => unreachable!("ExprValue::UnaryOperator::compile"),
-            ExprValue::BinaryOperator { .. } => unreachable!("ExprValue::UnaryOperator::compile"),
-            ExprValue::Concat { .. } => unreachable!("ExprValue::Concat::compile"),
-            ExprValue::Conditional { .. } => unreachable!("ExprValue::Conditional::compile"),
-            ExprValue::GetAttr { .. } => unreachable!("ExprValue::GetAttr::compile"),
-            ExprValue::ImportedFunctionCall { .. } => unreachable!("ExprValue::ImportedFunctionCall::compile"),
-            ExprValue::FunctionCall { name, ref arguments } => {
-                CompiledExpression::empty("function call")
+            ExprValue::Array(ref items) => {
+                let mut args = Vec::new();
+                for item in items {
+                    args.push(try!(compile_operand(item, stage)));
+                }
+
+                call(stage, "ExprValue::Array", "__array", args)
+            },
+            ExprValue::Hash(ref items) => {
+                let mut args = Vec::new();
+                for &(ref key, ref value) in items {
+                    args.push(try!(compile_operand(key, stage)));
+                    args.push(try!(compile_operand(value, stage)));
+                }
+
+                call(stage, "ExprValue::Hash", "__hash", args)
+            },
+            ExprValue::UnaryOperator { ref value, ref expr } => {
+                let mut args = operator_args(value, self, stage);
+                args.push(try!(compile_operand(expr, stage)));
+
+                call(stage, "ExprValue::UnaryOperator", "__unary", args)
+            },
+            ExprValue::BinaryOperator { ref value, ref left, ref right } => match value.as_ref() {
+                "and" => try!(compile_logical(left, right, false, stage)),
+                "or" => try!(compile_logical(left, right, true, stage)),
+                operator => try!(compile_binary(operator, self, left, right, stage)),
+            },
+            ExprValue::Concat { ref left, ref right } => {
+                try!(compile_binary("~", self, left, right, stage))
+            },
+            ExprValue::Conditional { ref expr, ref yay, ref nay } => {
+                let result = stage.use_temp();
+
+                let condition = try!(compile_operand(expr, stage));
+                let nay_jump = compile_test(condition, false, stage);
+
+                let yay = try!(compile_operand(yay, stage));
+                stage.instr(Instruction::Load { binding: result, location: yay });
+                let end_jump = stage.next_pc();
+                stage.instr(Instruction::Jump { pc: 0 });
+
+                stage.jump_here(nay_jump);
+                stage.instr(Instruction::Pop { times: 2 });
+                let nay = try!(compile_operand(nay, stage));
+                stage.instr(Instruction::Load { binding: result, location: nay });
+
+                stage.jump_here(end_jump);
+
+                CompiledExpression::with_result("ExprValue::Conditional", Mem::Binding(result))
+            },
+            ExprValue::GetAttr { .. } => try!(compile_get_attr(self, false, stage)),
+            ExprValue::ImportedFunctionCall { .. } => try!(compile_macro_call(self, false, stage)),
+            ExprValue::FunctionCall { .. } => try!(compile_function_call(self, false, stage)),
+            ExprValue::Filter { ref name, ref expr, ref arguments } => {
+                let mut args = operator_args(name, self, stage);
+                // Undefined value gets the default instead of failing.
+                args.push(if name == "default" {
+                    try!(compile_lenient(expr, stage))
+                } else {
+                    try!(compile_operand(expr, stage))
+                });
+                for argument in arguments {
+                    args.push(try!(compile_operand(argument, stage)));
+                }
+
+                call(stage, "ExprValue::Filter", "__filter", args)
+            },
+            ExprValue::Test { ref name, ref expr, .. } if name == "defined" => {
+                try!(compile_defined(expr, stage))
+            },
+            ExprValue::Test { ref name, ref expr, ref arguments } => {
+                let mut args = operator_args(name, self, stage);
+                args.push(try!(compile_operand(expr, stage)));
+                for argument in arguments {
+                    args.push(try!(compile_operand(argument, stage)));
+                }
+
+                call(stage, "ExprValue::Test", "__test", args)
             },
         })
     }
 }
+
+/// Compiles `include` function and macro calls that are printed as they are.
+///
+/// They write their output to the template output, instead of returning
+/// it, so the `flush` tag inside them reaches the writer.
+pub fn compile_printed<'c>(expr: &Expr<'c>, stage: &mut Staging<'c, Value>)
+    -> TemplateResult<Option<CompiledExpression>>
+{
+    Ok(match expr.value {
+        ExprValue::ImportedFunctionCall { .. } => Some(try!(compile_macro_call(expr, true, stage))),
+        ExprValue::FunctionCall { ref name, .. } if name == "include" => {
+            Some(try!(compile_function_call(expr, true, stage)))
+        },
+        _ => None,
+    })
+}
+
+/// Compiles macro call, the macro output is written to the template output
+/// when `printed` is set.
+fn compile_macro_call<'c>(expr: &Expr<'c>, printed: bool, stage: &mut Staging<'c, Value>)
+    -> TemplateResult<CompiledExpression>
+{
+    let (uuid, alias, arguments) = match expr.value {
+        ExprValue::ImportedFunctionCall { ref uuid, ref alias, ref arguments } => (uuid, alias, arguments),
+        _ => unreachable!("twig bug: expected ImportedFunctionCall expression"),
+    };
+
+    let mut args = vec![
+        stage.include_const(Value::Str(uuid.to_hyphenated_string())),
+        stage.include_const(Value::Str(alias.to_string())),
+    ];
+    args.extend(position_args(expr.line, expr.column, expr.span, stage));
+    args.push(stage.include_const(Value::Bool(printed)));
+    for argument in arguments {
+        args.push(try!(compile_operand(argument, stage)));
+    }
+
+    Ok(call(stage, "ExprValue::ImportedFunctionCall", "__macro", args))
+}
+
+/// Compiles function call. The `include` function gets the current context
+/// and the `printed` flag before its own arguments.
+fn compile_function_call<'c>(expr: &Expr<'c>, printed: bool, stage: &mut Staging<'c, Value>)
+    -> TemplateResult<CompiledExpression>
+{
+    let (name, arguments) = match expr.value {
+        ExprValue::FunctionCall { ref name, ref arguments } => (name, arguments),
+        _ => unreachable!("twig bug: expected FunctionCall expression"),
+    };
+
+    let mut args = Vec::new();
+    if name == "include" {
+        args.push(Mem::Parameters);
+        args.push(stage.include_const(Value::Bool(printed)));
+    }
+    for &(_, ref argument) in arguments {
+        args.push(try!(compile_operand(argument, stage)));
+    }
+
+    Ok(call(stage, "ExprValue::FunctionCall", name, args))
+}
+
+/// Compiles variable lookup in the template context.
+///
+/// The lookup fails for undefined variable in strict variables mode, unless
+/// `ignore_strict_check` is set.
+fn compile_name<'c>(name: &str, expr: &Expr<'c>, ignore_strict_check: bool, stage: &mut Staging<'c, Value>)
+    -> CompiledExpression
+{
+    match stage.use_name(name) {
+        Some(mem) => {
+            trace!("use mem {:?} for name {:?}", mem, name);
+            CompiledExpression::with_result("ExprValue::Name", mem)
+        },
+        None => {
+            let mut args = vec![Mem::Parameters, stage.include_const(Value::Str(name.into()))];
+            args.extend(position_args(expr.line, expr.column, expr.span, stage));
+            args.push(stage.include_const(Value::Bool(ignore_strict_check)));
+            call(stage, "ExprValue::Name", "__name", args)
+        },
+    }
+}
+
+/// Compiles attribute lookup, the same way as variable lookup.
+///
+/// With `ignore_strict_check`, the node is compiled leniently too.
+fn compile_get_attr<'c>(expr: &Expr<'c>, ignore_strict_check: bool, stage: &mut Staging<'c, Value>)
+    -> TemplateResult<CompiledExpression>
+{
+    let (node, arg, arguments, call_type) = match expr.value {
+        ExprValue::GetAttr { ref node, ref arg, ref arguments, ref call_type } => (node, arg, arguments, call_type),
+        _ => unreachable!("twig bug: expected GetAttr expression"),
+    };
+
+    let node = if ignore_strict_check {
+        try!(compile_lenient(node, stage))
+    } else {
+        try!(compile_operand(node, stage))
+    };
+    let mut args = vec![node, try!(compile_operand(arg, stage))];
+    args.extend(position_args(expr.line, expr.column, expr.span, stage));
+    args.push(stage.include_const(Value::Bool(ignore_strict_check)));
+
+    if let ExprCallType::Method = *call_type {
+        let mut method_args = Vec::new();
+        for argument in arguments {
+            method_args.push(try!(compile_operand(argument, stage)));
+        }
+        let method_args = call(stage, "ExprValue::GetAttr", "__array", method_args);
+        args.push(method_args.result().expect("call has result"));
+        try!(method_args.finalize(stage));
+    }
+
+    Ok(call(stage, "ExprValue::GetAttr", "__attr", args))
+}
+
+/// Compiles expression that evaluates to null instead of failing on
+/// undefined variables and attributes.
+fn compile_lenient<'c>(expr: &Expr<'c>, stage: &mut Staging<'c, Value>) -> TemplateResult<Mem> {
+    let ce = match expr.value {
+        ExprValue::Name(ref name) => compile_name(name, expr, true, stage),
+        ExprValue::GetAttr { .. } => try!(compile_get_attr(expr, true, stage)),
+        _ => return compile_operand(expr, stage),
+    };
+    let mem = ce.result().expect("lookup has result");
+    try!(ce.finalize(stage));
+    Ok(mem)
+}
+
+/// Compiles the "defined" test, that never fails on undefined values.
+fn compile_defined<'c>(expr: &Expr<'c>, stage: &mut Staging<'c, Value>)
+    -> TemplateResult<CompiledExpression>
+{
+    Ok(match expr.value {
+        ExprValue::Name(ref name) if stage.use_name(name).is_none() => {
+            let name = stage.include_const(Value::Str(name.to_string()));
+            call(stage, "ExprValue::Test", "__defined_name", vec![Mem::Parameters, name])
+        },
+        ExprValue::GetAttr { ref node, ref arg, .. } => {
+            let node = try!(compile_lenient(node, stage));
+            let arg = try!(compile_operand(arg, stage));
+            call(stage, "ExprValue::Test", "__defined", vec![node, arg])
+        },
+        _ => CompiledExpression::with_result("ExprValue::Test", stage.include_const(Value::Bool(true))),
+    })
+}
+
+fn compile_binary<'c>(operator: &str, expr: &Expr<'c>, left: &Expr<'c>, right: &Expr<'c>, stage: &mut Staging<'c, Value>)
+    -> TemplateResult<CompiledExpression>
+{
+    let mut args = operator_args(operator, expr, stage);
+    args.push(try!(compile_operand(left, stage)));
+    args.push(try!(compile_operand(right, stage)));
+
+    Ok(call(stage, "ExprValue::BinaryOperator", "__binary", args))
+}
+
+/// Returns the first arguments of operator, filter and test calls, the
+/// callable name and the expression position.
+fn operator_args<'c>(name: &str, expr: &Expr<'c>, stage: &mut Staging<'c, Value>) -> Vec<Mem> {
+    let mut args = vec![stage.include_const(Value::Str(name.into()))];
+    args.extend(position_args(expr.line, expr.column, expr.span, stage));
+    args
+}
+
+/// Pushes truthiness of the value to the stack and jumps if it equals `when`.
+///
+/// Returns position of the jump instruction. The jump target has to pop
+/// two values from the stack.
+fn compile_test<'c>(value: Mem, when: bool, stage: &mut Staging<'c, Value>) -> u16 {
+    stage.instr(Instruction::Push { location: value });
+    let call = stage.use_call("__bool");
+    stage.instr(Instruction::Call { call: call, argc: 1, push_result_to_stack: true });
+
+    let expected = stage.include_const(Value::Bool(when));
+    let jump = stage.next_pc();
+    stage.instr(Instruction::CondJump { pc: 0, location: expected, test: Cond::Eq });
+    stage.instr(Instruction::Pop { times: 2 });
+
+    jump
+}
+
+/// Compiles "and" or "or" operator, the right side is evaluated only if
+/// the left side does not decide the result.
+fn compile_logical<'c>(left: &Expr<'c>, right: &Expr<'c>, is_or: bool, stage: &mut Staging<'c, Value>)
+    -> TemplateResult<CompiledExpression>
+{
+    let result = stage.use_temp();
+    let decided = stage.include_const(Value::Bool(is_or));
+    let undecided = stage.include_const(Value::Bool(!is_or));
+
+    let left = try!(compile_operand(left, stage));
+    stage.instr(Instruction::Load { binding: result, location: decided });
+    let decided_jump = compile_test(left, is_or, stage);
+
+    let right = try!(compile_operand(right, stage));
+    let right_jump = compile_test(right, is_or, stage);
+    stage.instr(Instruction::Load { binding: result, location: undecided });
+    let end_jump = stage.next_pc();
+    stage.instr(Instruction::Jump { pc: 0 });
+
+    stage.jump_here(decided_jump);
+    stage.jump_here(right_jump);
+    stage.instr(Instruction::Pop { times: 2 });
+
+    stage.jump_here(end_jump);
+
+    Ok(CompiledExpression::with_result("ExprValue::BinaryOperator", Mem::Binding(result)))
+}
+
+fn constant_value(constant: &ExprConstant) -> Value {
+    match *constant {
+        ExprConstant::Str(ref v) => Value::Str(v.to_string()),
+        ExprConstant::Int(v) => Value::Int(v),
+        ExprConstant::Float(v) => Value::Float(v),
+        ExprConstant::Bool(v) => Value::Bool(v),
+        ExprConstant::Big(_) => unreachable!("ExprConstant::Big::compile"),
+        ExprConstant::Null => Value::Null,
+    }
+}
+
+/// Returns value of the constant expression, like the default value of a
+/// macro argument, or null if the expression is not constant.
+pub fn default_value(expr: &Expr) -> Value {
+    match expr.value {
+        ExprValue::Constant(ref constant) => constant_value(constant),
+        ExprValue::Array(ref items) => Value::Array(items.iter().map(default_value).collect()),
+        _ => Value::Null,
+    }
+}
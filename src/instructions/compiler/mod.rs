@@ -1,12 +1,16 @@
 use little::{ Mem, Instruction };
+use nodes::expr::Expr;
 use value::Value;
 use error::TemplateResult;
+use tokens::Span;
 use mold::Staging;
 
 mod body;
 mod expr;
 mod module;
 
+pub use self::expr::default_value;
+
 pub trait Compile<'c> {
     fn compile<'r>(&'r self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<()>;
 }
@@ -53,6 +57,11 @@ impl CompiledExpression {
         self.result.clone()
     }
 
+    /// Returns number of stack items left by this expression.
+    pub fn stack_length(&self) -> u16 {
+        self.stack_length
+    }
+
     pub fn finalize<'c, 'r>(mut self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<()> {
         if self.stack_length > 0 {
             trace!("finalize {}", self.origin);
@@ -75,3 +84,48 @@ pub trait CompileExpression<'c> {
     /// Compiles ast subnodes that return result in
     fn compile<'r>(&'r self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<CompiledExpression>;
 }
+
+/// Compiles expression used as an argument of another expression.
+///
+/// Argument values are pushed together, so results that live on the
+/// stack are moved out of the way.
+pub fn compile_operand<'c>(expr: &Expr<'c>, stage: &mut Staging<'c, Value>) -> TemplateResult<Mem> {
+    let ce = try!(expr.compile(stage));
+    let mut mem = ce.result().unwrap_or_else(|| stage.include_const(Value::Null));
+    if ce.stack_length() > 0 {
+        let temp = stage.use_temp();
+        stage.instr(Instruction::Load { binding: temp, location: mem });
+        mem = Mem::Binding(temp);
+    }
+    try!(ce.finalize(stage));
+    Ok(mem)
+}
+
+/// Compiles runtime call with specified arguments.
+///
+/// The call result is kept in a temporary binding, so the stack is left clean.
+pub fn call<'c>(stage: &mut Staging<'c, Value>, origin: &'static str, name: &str, args: Vec<Mem>) -> CompiledExpression {
+    let argc = args.len();
+    for mem in args {
+        stage.instr(Instruction::Push { location: mem });
+    }
+
+    let call = stage.use_call(name);
+    stage.instr(Instruction::Call { call: call, argc: argc as u8, push_result_to_stack: true });
+
+    let result = stage.use_temp();
+    stage.instr(Instruction::Load { binding: result, location: Mem::StackTop1 });
+    stage.instr(Instruction::Pop { times: argc as u16 + 1 });
+
+    CompiledExpression::with_result(origin, Mem::Binding(result))
+}
+
+/// Includes source position as constant runtime call arguments: line,
+/// column, span start and span end.
+///
+/// Lookups, includes and block references get it for error reporting.
+fn position_args<'c>(line: usize, column: usize, span: Span, stage: &mut Staging<'c, Value>) -> Vec<Mem> {
+    vec![line, column, span.start, span.end].into_iter()
+        .map(|n| stage.include_const(Value::Int(n as i64)))
+        .collect()
+}
@@ -1,12 +1,34 @@
+use little::{ Instruction, Mem };
 use instructions::Compile;
 use nodes::Module;
 use value::Value;
 use error::TemplateResult;
 use mold::Staging;
+use super::{ compile_operand, position_args };
 
 impl<'c> Compile<'c> for Module<'c> {
     fn compile<'r>(&'r self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<()> {
         trace!("Module::compile");
-        self.body.compile(stage)
+
+        let parent = match self.parent {
+            Some(ref parent) => parent,
+            None => return self.body.compile(stage),
+        };
+
+        // Contents outside of blocks are not rendered, the parent template
+        // is rendered instead, and it uses blocks of this template.
+        let name = try!(compile_operand(parent, stage));
+        stage.instr(Instruction::Push { location: name });
+        stage.instr(Instruction::Push { location: Mem::Parameters });
+        for position in position_args(parent.line, parent.column, parent.span, stage) {
+            stage.instr(Instruction::Push { location: position });
+        }
+
+        // The parent template is written to the template output.
+        let call = stage.use_call("__extends");
+        stage.instr(Instruction::Call { call: call, argc: 6, push_result_to_stack: false });
+        stage.instr(Instruction::Pop { times: 6 });
+
+        Ok(())
     }
 }
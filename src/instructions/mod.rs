@@ -1,16 +1,108 @@
-pub use self::compiler::{ Compile, CompileExpression, CompiledExpression };
+pub use self::compiler::{ Compile, CompileExpression, CompiledExpression, compile_operand, call };
+use self::compiler::default_value;
+pub use self::program::{ Program, Macro };
+pub use self::bytecode::FORMAT_VERSION;
 
+use little::Instruction;
+use environment::{ CompiledEnvironment, OPTIMIZE_OUTPUT };
 use nodes::Module;
-use error::Result;
+use nodes::body::Body;
+use nodes::expr::Expr;
 use value::Value;
-use little::{ Template };
+use error::Result;
 use mold::Staging;
 
 mod compiler;
+mod program;
+mod bytecode;
+mod optimizer;
 
-pub fn compile(env: (), nodes: &Module) -> Result<Template<Value>> {
+pub fn compile(env: &CompiledEnvironment, nodes: &Module) -> Result<Program> {
     trace!("compile");
     let mut stage = Staging::new();
     try!(nodes.compile(&mut stage));
+    let mut program: Program = stage.into();
+    program.parent = match nodes.parent.as_ref().map(default_value) {
+        Some(Value::Str(parent)) => Some(parent),
+        _ => None,
+    };
+
+    // Blocks and macros are executed on their own, so they import macros
+    // imported by the template again.
+    let (imports, macros) = top_level_nodes(&nodes.body);
+
+    for block in &nodes.blocks {
+        let mut stage = Staging::new();
+        for import in &imports {
+            try!(import.compile(&mut stage));
+        }
+        try!(block.body.compile(&mut stage));
+        program.blocks.push((block.name.to_string(), stage.into()));
+    }
+
+    for found in macros {
+        if let Body::Macro { ref name, ref body, ref arguments, .. } = *found {
+            let mut stage = Staging::new();
+            for import in &imports {
+                try!(import.compile(&mut stage));
+            }
+            try!(body.compile(&mut stage));
+            program.macros.push((name.to_string(), Macro {
+                arguments: arguments.iter()
+                    .map(|&(ref name, ref default)| {
+                        (name.as_ref().map_or(String::new(), |n| n.to_string()), default_value(default))
+                    })
+                    .collect(),
+                program: stage.into(),
+            }));
+        }
+    }
+
+    if env.config.optimizations & OPTIMIZE_OUTPUT != 0 {
+        optimizer::merge_output(&mut program);
+        for &mut (_, ref mut block) in &mut program.blocks {
+            optimizer::merge_output(block);
+        }
+        for &mut (_, ref mut found) in &mut program.macros {
+            optimizer::merge_output(&mut found.program);
+        }
+    }
+
+    Ok(program)
+}
+
+/// Returns imports and macros of the template body, not nested in other tags.
+fn top_level_nodes<'r, 'c>(body: &'r Body<'c>) -> (Vec<&'r Body<'c>>, Vec<&'r Body<'c>>) {
+    let mut imports = Vec::new();
+    let mut macros = Vec::new();
+    let items = match *body {
+        Body::List { ref items } => items.iter().collect(),
+        ref other => vec![other],
+    };
+    for item in items {
+        match *item {
+            Body::Import { .. } => imports.push(item),
+            Body::Macro { .. } => macros.push(item),
+            _ => (),
+        }
+    }
+    (imports, macros)
+}
+
+/// Compiles standalone expression.
+///
+/// The program passes the expression value to the "__return" runtime call.
+pub fn compile_expression(env: &CompiledEnvironment, expr: &Expr) -> Result<Program> {
+    trace!("compile_expression");
+    let mut stage = Staging::new();
+    let ce = try!(expr.compile(&mut stage));
+    let result = ce.result().unwrap_or_else(|| stage.include_const(Value::Null));
+
+    stage.instr(Instruction::Push { location: result });
+    let call = stage.use_call("__return");
+    stage.instr(Instruction::Call { call: call, argc: 1, push_result_to_stack: false });
+    stage.instr(Instruction::Pop { times: 1 });
+    try!(ce.finalize(&mut stage));
+
     Ok(stage.into())
 }
@@ -0,0 +1,94 @@
+use std::collections::{ HashMap, HashSet };
+use little::{ Instruction, Mem, Constant };
+use value::Value;
+use instructions::Program;
+
+/// Merges adjacent outputs of string constants into a single output.
+///
+/// Outputs that are jump targets are kept separate, and jumps are pointed
+/// to the new instruction positions.
+pub fn merge_output(program: &mut Program) {
+    let targets: HashSet<u16> = program.instructions.iter()
+        .filter_map(|i| match *i {
+            Instruction::Jump { pc } => Some(pc),
+            Instruction::CondJump { pc, .. } => Some(pc),
+            _ => None,
+        })
+        .collect();
+
+    let mut merged: Vec<Instruction> = Vec::with_capacity(program.instructions.len());
+    let mut positions = HashMap::new();
+    let mut previous_text: Option<String> = None;
+
+    for (pc, instruction) in program.instructions.iter().enumerate() {
+        let text = match *instruction {
+            Instruction::Output { location: Mem::Const(Constant(c)) } => match program.constants.get(c as usize) {
+                Some(&Value::Str(ref text)) => Some(text.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match (previous_text.take(), text) {
+            (Some(previous), Some(text)) if !targets.contains(&(pc as u16)) => {
+                let joined = previous + &text;
+                program.constants.push(Value::Str(joined.clone()));
+                let constant = Constant(program.constants.len() as u32 - 1);
+                *merged.last_mut().expect("previous output") = Instruction::Output { location: Mem::Const(constant) };
+                previous_text = Some(joined);
+            },
+            (_, text) => {
+                merged.push(instruction.clone());
+                previous_text = text;
+            },
+        }
+        positions.insert(pc as u16, merged.len() as u16 - 1);
+    }
+    positions.insert(program.instructions.len() as u16, merged.len() as u16);
+
+    for instruction in &mut merged {
+        match *instruction {
+            Instruction::Jump { ref mut pc } | Instruction::CondJump { ref mut pc, .. } => {
+                *pc = positions[&*pc];
+            },
+            _ => (),
+        }
+    }
+
+    program.instructions = merged;
+}
+
+#[cfg(test)]
+mod tests {
+    use little::{ Instruction, Mem, Constant, Cond };
+    use value::Value;
+    use instructions::Program;
+    use super::merge_output;
+
+    fn output(c: u32) -> Instruction {
+        Instruction::Output { location: Mem::Const(Constant(c)) }
+    }
+
+    #[test]
+    fn merges_adjacent_text_and_moves_jumps() {
+        let mut program = Program::new();
+        program.constants = vec![Value::Str("a".into()), Value::Str("b".into()), Value::Bool(false)];
+        program.instructions = vec![
+            output(0),
+            output(1),
+            Instruction::CondJump { pc: 5, location: Mem::Const(Constant(2)), test: Cond::Eq },
+            output(0),
+            output(1),
+            output(0),
+        ];
+
+        merge_output(&mut program);
+
+        assert_eq!(program.instructions.len(), 4);
+        assert_eq!(program.constants[3], Value::Str("ab".into()));
+        match program.instructions[1] {
+            Instruction::CondJump { pc, .. } => assert_eq!(pc, 3),
+            ref other => panic!("expected jump, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use little::{ Template, Instruction, Constant, Call };
+use value::Value;
+
+/// Compiled template.
+///
+/// Unlike `little::Template`, the program is not consumed when it is built
+/// for execution, therefore it can be kept around and executed many times.
+#[derive(Clone, Debug)]
+pub struct Program {
+    /// Constant values, indexed by `Constant`.
+    pub constants: Vec<Value>,
+    /// Names of runtime functions, indexed by `Call`.
+    pub calls: Vec<String>,
+    /// Executable instructions.
+    pub instructions: Vec<Instruction>,
+    /// Programs of named blocks, in definition order.
+    pub blocks: Vec<(String, Program)>,
+    /// Macros of the template, in definition order.
+    pub macros: Vec<(String, Macro)>,
+    /// Name of the extended template, if it is a constant string.
+    pub parent: Option<String>,
+}
+
+/// Compiled macro.
+#[derive(Clone, Debug)]
+pub struct Macro {
+    /// Argument names with default values.
+    pub arguments: Vec<(String, Value)>,
+    pub program: Program,
+}
+
+impl Program {
+    pub fn new() -> Program {
+        Program {
+            constants: Vec::new(),
+            calls: Vec::new(),
+            instructions: Vec::new(),
+            blocks: Vec::new(),
+            macros: Vec::new(),
+            parent: None,
+        }
+    }
+
+    /// Returns program of the block with specified name.
+    pub fn block(&self, name: &str) -> Option<&Program> {
+        self.blocks.iter()
+            .find(|&&(ref n, _)| n == name)
+            .map(|&(_, ref program)| program)
+    }
+
+    /// Returns the macro with specified name.
+    pub fn find_macro(&self, name: &str) -> Option<&Macro> {
+        self.macros.iter()
+            .find(|&&(ref n, _)| n == name)
+            .map(|&(_, ref found)| found)
+    }
+
+    /// Creates `little` template that can be built by the interpreter.
+    pub fn template(&self) -> Template<Value> {
+        let mut template = Template::empty();
+        for (i, value) in self.constants.iter().enumerate() {
+            template.push_constant(Constant(i as u32), value.clone());
+        }
+        for (i, name) in self.calls.iter().enumerate() {
+            template.calls_template.push(&name[..], Call(i as u32));
+        }
+        template.with_instructions(self.instructions.iter().cloned())
+    }
+}
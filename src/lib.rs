@@ -20,6 +20,7 @@ extern crate regex;
 extern crate uuid;
 extern crate little;
 #[macro_use] extern crate log;
+#[cfg(feature = "serde")] #[macro_use] extern crate serde;
 
 pub mod tokens;
 pub mod nodes;
@@ -30,12 +31,14 @@ pub mod environment;
 pub mod extension;
 pub mod operator;
 pub mod function;
+pub mod global;
 pub mod value;
 pub mod mold;
+pub mod format;
 
 mod engine;
 
-pub use engine::Engine;
+pub use engine::{ Engine, TemplateHandle, Expression };
 
 /// Returns different output based on expected value.
 pub trait Expect<V> {
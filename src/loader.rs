@@ -1,19 +1,46 @@
 use std::collections::HashMap;
+use std::time::SystemTime;
 use error::{ Result, EngineError };
 
 pub trait Loader {
     fn get_source(&self, name: &str) -> Result<String>;
+
+    /// Returns the key that identifies compiled template in cache.
+    fn get_cache_key(&self, name: &str) -> Result<String> {
+        Ok(name.into())
+    }
+
+    /// Returns true if the template was not modified since specified time.
+    fn is_fresh(&self, _name: &str, _time: SystemTime) -> Result<bool> {
+        Ok(true)
+    }
 }
 
 #[derive(Debug)]
 pub struct ArrayLoader {
     files: HashMap<String, String>,
+    modified: HashMap<String, SystemTime>,
 }
 
 impl ArrayLoader {
     pub fn new(sources: HashMap<String, String>) -> ArrayLoader {
         ArrayLoader {
             files: sources,
+            modified: HashMap::new(),
+        }
+    }
+
+    /// Adds or replaces template source.
+    pub fn set_template<N: Into<String>, S: Into<String>>(&mut self, name: N, source: S) {
+        let name = name.into();
+        self.modified.insert(name.clone(), SystemTime::now());
+        self.files.insert(name, source.into());
+    }
+
+    fn not_found(name: &str) -> EngineError {
+        EngineError::TemplateNotFound {
+            name: name.into(),
+            search_paths: Vec::new()
         }
     }
 }
@@ -22,10 +49,99 @@ impl Loader for ArrayLoader {
     fn get_source(&self, name: &str) -> Result<String> {
         match self.files.get(name) {
             Some(contents) => Ok(contents.clone()),
-            None => Err(EngineError::TemplateNotFound {
-                name: name.into(),
-                search_paths: Vec::new()
-            }.into()),
+            None => Err(ArrayLoader::not_found(name).into()),
+        }
+    }
+
+    fn is_fresh(&self, name: &str, time: SystemTime) -> Result<bool> {
+        if !self.files.contains_key(name) {
+            return Err(ArrayLoader::not_found(name).into());
+        }
+        Ok(match self.modified.get(name) {
+            Some(modified) => *modified < time,
+            None => true,
+        })
+    }
+}
+
+/// Resolves template name relative to the name of the current template.
+///
+/// Names starting with `./` or `../` are resolved against the directory of
+/// the `current` template (or the loader root, if there is no current template).
+/// Other names are only normalised: backslashes become slashes, and empty or
+/// `.` segments are dropped.
+///
+/// Returns an error if the resolved name would point above the loader root.
+pub fn resolve_template_name(current: Option<&str>, name: &str) -> Result<String> {
+    let name = name.replace("\\", "/");
+
+    let mut segments: Vec<&str> = Vec::new();
+
+    if name.starts_with("./") || name.starts_with("../") {
+        if let Some(current) = current {
+            segments.extend(current.split('/').filter(|s| !s.is_empty()));
+            // Drop the file name of current template, keep the directory.
+            segments.pop();
+        }
+    }
+
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => if let None = segments.pop() {
+                return Err(EngineError::TemplateOutsideOfRoot {
+                    name: name.clone(),
+                    from: current.map(|c| c.into()),
+                }.into());
+            },
+            other => segments.push(other),
         }
     }
+
+    Ok(segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_template_name;
+
+    #[test]
+    fn keeps_absolute_names() {
+        assert_eq!(resolve_template_name(Some("pages/index.twig"), "base.twig").unwrap(), "base.twig");
+        assert_eq!(resolve_template_name(None, "layout/base.twig").unwrap(), "layout/base.twig");
+    }
+
+    #[test]
+    fn normalises_names() {
+        assert_eq!(resolve_template_name(None, "layout//./base.twig").unwrap(), "layout/base.twig");
+        assert_eq!(resolve_template_name(None, "layout\\base.twig").unwrap(), "layout/base.twig");
+        assert_eq!(resolve_template_name(None, "layout/../base.twig").unwrap(), "base.twig");
+    }
+
+    #[test]
+    fn resolves_relative_to_current_directory() {
+        assert_eq!(
+            resolve_template_name(Some("pages/index.twig"), "./partials/row.twig").unwrap(),
+            "pages/partials/row.twig"
+        );
+        assert_eq!(
+            resolve_template_name(Some("pages/users/list.twig"), "../../base.twig").unwrap(),
+            "base.twig"
+        );
+        assert_eq!(
+            resolve_template_name(Some("index.twig"), "./row.twig").unwrap(),
+            "row.twig"
+        );
+        assert_eq!(
+            resolve_template_name(None, "./row.twig").unwrap(),
+            "row.twig"
+        );
+    }
+
+    #[test]
+    fn fails_to_resolve_above_root() {
+        assert!(resolve_template_name(Some("index.twig"), "../base.twig").is_err());
+        assert!(resolve_template_name(None, "../base.twig").is_err());
+        assert!(resolve_template_name(Some("pages/index.twig"), "../../base.twig").is_err());
+    }
 }
@@ -1,12 +1,16 @@
 use std::collections::{ HashMap, VecDeque };
 use little::*;
 use value::Value;
+use instructions::Program;
 
 pub struct Staging<'c, V: LittleValue> {
     next_constant: Constant,
     unique_constants: HashMap<Fingerprint, Constant>,
+    calls: HashMap<String, Call>,
     pub locals: VecDeque<Basket<'c, Binding>>,
-    template: Template<V>,
+    constants: Vec<V>,
+    call_names: Vec<String>,
+    instructions: Vec<Instruction>,
 }
 
 impl<'c, V: LittleValue> Staging<'c, V> {
@@ -14,8 +18,11 @@ impl<'c, V: LittleValue> Staging<'c, V> {
         let mut st = Staging {
             next_constant: Constant(0),
             unique_constants: HashMap::new(),
+            calls: HashMap::new(),
             locals: VecDeque::new(),
-            template: Template::empty(),
+            constants: Vec::new(),
+            call_names: Vec::new(),
+            instructions: Vec::new(),
         };
 
         st.locals.push_front(Basket::new(Binding(0), |Binding(p)| Binding(p + 1)));
@@ -42,7 +49,7 @@ impl<'c, V: LittleValue> Staging<'c, V> {
                 });
                 // and add it to constant list only once.
                 if added {
-                    self.template.push_constant(identifier, const_value);
+                    self.constants.push(const_value);
                 }
                 identifier
             },
@@ -52,7 +59,7 @@ impl<'c, V: LittleValue> Staging<'c, V> {
                 next = match next {
                     Constant(v) => Constant(v + 1),
                 };
-                self.template.push_constant(identifier, const_value);
+                self.constants.push(const_value);
                 identifier
             },
         };
@@ -63,7 +70,22 @@ impl<'c, V: LittleValue> Staging<'c, V> {
         Mem::Const(constant)
     }
 
-    pub fn use_name(&mut self, name: &'c str) -> Option<Mem> {
+    /// Returns call slot for runtime function with specified name.
+    ///
+    /// The function itself is provided by the engine when the template is built.
+    pub fn use_call(&mut self, name: &str) -> Call {
+        if let Some(call) = self.calls.get(name) {
+            return *call;
+        }
+
+        let call = Call(self.call_names.len() as u32);
+        self.calls.insert(name.into(), call);
+        self.call_names.push(name.into());
+
+        call
+    }
+
+    pub fn use_name(&mut self, name: &str) -> Option<Mem> {
         for basket in &self.locals {
             if let Some(ref binding) = basket.get(name) {
                 return Some(Mem::Binding(binding.clone()));
@@ -72,15 +94,44 @@ impl<'c, V: LittleValue> Staging<'c, V> {
         None
     }
 
+    /// Returns a new binding for intermediate value.
+    pub fn use_temp(&mut self) -> Binding {
+        self.locals.front_mut()
+            .expect("staging always has local scope")
+            .assign_unnamed()
+    }
+
     pub fn instr(&mut self, instruction: Instruction) {
         trace!("instr {:?}", &instruction);
-        self.template.push_instruction(instruction);
+        self.instructions.push(instruction);
+    }
+
+    /// Returns position of the next instruction.
+    pub fn next_pc(&self) -> u16 {
+        self.instructions.len() as u16
+    }
+
+    /// Points jump instruction at position `at` to the next instruction.
+    pub fn jump_here(&mut self, at: u16) {
+        let next = self.next_pc();
+        match self.instructions[at as usize] {
+            Instruction::Jump { ref mut pc } => *pc = next,
+            Instruction::CondJump { ref mut pc, .. } => *pc = next,
+            ref other => unreachable!("twig bug: expected jump instruction, found {:?}", other),
+        }
     }
 }
 
-impl<'a> Into<Template<Value>> for Staging<'a, Value> {
-    fn into(self) -> Template<Value> {
-        self.template
+impl<'a> Into<Program> for Staging<'a, Value> {
+    fn into(self) -> Program {
+        Program {
+            constants: self.constants,
+            calls: self.call_names,
+            instructions: self.instructions,
+            blocks: Vec::new(),
+            macros: Vec::new(),
+            parent: None,
+        }
     }
 }
 
@@ -110,6 +161,13 @@ impl<'c, T> Basket<'c, T> where T: Eq + Clone {
         result
     }
 
+    /// Assigns space that is not referenced by name.
+    pub fn assign_unnamed(&mut self) -> T {
+        let current = self.current.clone();
+        self.current = (self.next)(current.clone());
+        current
+    }
+
     pub fn get(&self, name: &str) -> Option<T> {
         self.map.get(name).cloned()
     }
@@ -7,22 +7,44 @@ Produces an abstract syntax tree from a stream of tokens.
 mod parser;
 mod node;
 mod token_parser;
+mod visitor;
 
 pub use self::node::body;
 pub use self::node::expr;
 pub use self::node::module::Module;
 pub use self::token_parser::{ TokenParser };
+pub use self::visitor::{ NodeVisitor, traverse_module, traverse_body, traverse_expr };
 pub use self::parser::{ Parser, Parse, ImportedFunction };
 pub use self::parser::body as body_parser;
 pub use self::parser::expr as expr_parser;
 pub use self::parser::module as module_parser;
 
+use std::borrow::Cow;
 use environment::ParsingEnvironment;
-use tokens::{ TokenRef, TokenIter };
-use error::TemplateResult;
+use tokens::{ TokenRef, TokenIter, TokenValueRef };
+use error::{ TemplateResult, TemplateError, At };
 
+/// Named template block.
+///
+/// Block body is rendered at the place of `Body::BlockReference`, and can be
+/// rendered separately from the rest of the template.
 #[derive(Debug)]
-pub struct Block;
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Block<'c> {
+    pub name: Cow<'c, str>,
+    pub body: body::Body<'c>,
+    pub line: usize,
+}
+
+impl<'c> Block<'c> {
+    pub fn into_owned(self) -> Block<'static> {
+        Block {
+            name: node::owned(self.name),
+            body: self.body.into_owned(),
+            line: self.line,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Macro;
@@ -33,7 +55,7 @@ pub struct Trait;
 #[derive(Debug)]
 pub struct EmbededTemplate;
 
-pub trait TokenParserExtension
+pub trait TokenParserExtension: Send + Sync
 {
     fn get_tag(&self) -> &'static str;
     fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
@@ -41,9 +63,78 @@ pub trait TokenParserExtension
 }
 
 /// Parse given token stream into a node tree.
+///
+/// Node visitors of the environment are run over the parsed tree.
 pub fn parse<'r, 'c>(env: &'r ParsingEnvironment, tokens: &'r mut TokenIter<'r, 'c>) -> TemplateResult<Module<'c>> {
+    let mut module = {
+        let mut parser = Parser::new(
+            env, tokens
+        );
+        try!(Module::parse(&mut parser))
+    };
+    for visitor in &env.node_visitors {
+        try!(traverse_module(visitor.as_ref(), &mut module));
+    }
+    Ok(module)
+}
+
+/// Parse given token stream into a node tree, collecting all errors instead of
+/// stopping at the first one.
+///
+/// After an error the parser skips to the next `BlockEnd` or `VarEnd` token
+/// and continues, so the returned module contains every node that could be
+/// parsed. Token stream should be `recovering`, otherwise the lexer ends it at
+/// the first lexer error.
+///
+/// ```
+/// use twig::environment::Environment;
+/// use twig::tokens::Lexer;
+/// use twig::nodes::parse_recovering;
+///
+/// let env = Environment::default().init_all();
+/// let lexer = Lexer::default(&env.lexing);
+/// let mut tokens = lexer.tokens("{{ a + }} {% nope %} {{ b }}").recovering();
+/// let (_module, errors) = parse_recovering(&env.parsing, &mut tokens);
+///
+/// assert_eq!(errors.len(), 2);
+/// ```
+pub fn parse_recovering<'r, 'c>(env: &'r ParsingEnvironment, tokens: &'r mut TokenIter<'r, 'c>)
+    -> (Module<'c>, Vec<At<TemplateError>>)
+{
+    let (mut module, mut errors) = {
+        let mut parser = Parser::new(
+            env, tokens
+        ).recovering();
+        // recovering parser collects errors instead of returning them
+        let module = Module::parse(&mut parser)
+            .expect("twig bug: recovering parser returned an error");
+        (module, parser.errors)
+    };
+    for visitor in &env.node_visitors {
+        if let Err(e) = traverse_module(visitor.as_ref(), &mut module) {
+            errors.push(e);
+        }
+    }
+    (module, errors)
+}
+
+/// Parse token stream of a single expression enclosed in variable delimiters.
+///
+/// Anything else than the expression is an error.
+pub fn parse_standalone_expression<'r, 'c>(env: &'r ParsingEnvironment, tokens: &'r mut TokenIter<'r, 'c>) -> TemplateResult<expr::Expr<'c>> {
     let mut parser = Parser::new(
         env, tokens
     );
-    Module::parse(&mut parser)
+    try!(parser.expect(TokenValueRef::VarStart));
+    let expr = try!(expr_parser::parse_expression(&mut parser, 0));
+    try!(parser.expect(TokenValueRef::VarEnd));
+
+    let mut expr = match try!(parser.maybe_current()) {
+        Some(token) => return Err(TemplateError::UnexpectedTokenValue(token.value.into()).at_token(&token)),
+        None => expr,
+    };
+    for visitor in &env.node_visitors {
+        try!(traverse_expr(visitor.as_ref(), &mut expr));
+    }
+    Ok(expr)
 }
@@ -1,29 +1,101 @@
+use std::fmt;
+use std::borrow::Cow;
 use nodes::expr::Expr;
+use nodes::node::owned;
+use instructions::Compile;
 use uuid::Uuid;
+use tokens::Span;
+use error::Location;
+
+/// Node produced by a tag parser defined outside of this crate.
+///
+/// It is compiled by itself, so the extension is in full control of the
+/// instructions emitted for the tag.
+pub trait ExtensionNode<'c>: Compile<'c> + fmt::Debug + Send + Sync {
+    /// Converts the node to one that does not borrow template source.
+    fn into_owned(self: Box<Self>) -> Box<ExtensionNode<'static>>;
+}
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ImportTarget<'c> {
-    Function { symbol: &'c str },
+    /// Macro imported with the `from` tag.
+    Function { symbol: Cow<'c, str> },
+    /// Template imported with the `import` tag, its macros are called as
+    /// methods of the alias.
+    Template,
+}
+
+impl<'c> ImportTarget<'c> {
+    pub fn into_owned(self) -> ImportTarget<'static> {
+        match self {
+            ImportTarget::Function { symbol } => ImportTarget::Function { symbol: owned(symbol) },
+            ImportTarget::Template => ImportTarget::Template,
+        }
+    }
 }
 
+/// Template body node.
+///
+/// Besides the line, nodes have the column, starting from 1, and the byte
+/// span in template source. Tag nodes span the whole tag, from the start of
+/// the opening tag to the end of the closing one, the span is set by the body
+/// parser after the tag parser returns the node.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Body<'c> {
     List { items: Vec<Body<'c>> },
-    Text { value: &'c str, line: usize },
-    Print { expr: Box<Expr<'c>>, line: usize },
+    Text { value: Cow<'c, str>, line: usize, column: usize, span: Span },
+    Print { expr: Box<Expr<'c>>, line: usize, column: usize, span: Span },
+    /// Comment contents, only produced when the lexer keeps comments.
+    Comment { value: Cow<'c, str>, line: usize, column: usize, span: Span },
+    /// Flushes the output produced so far.
+    Flush { line: usize, column: usize, span: Span },
+    /// Renders the block with specified name, defined in `Module::blocks`.
+    BlockReference { name: Cow<'c, str>, line: usize, column: usize, span: Span },
+    Include {
+        /// Name of the included template, resolved relative to the
+        /// including template.
+        template: Box<Expr<'c>>,
+        /// Variables passed to the included template.
+        variables: Option<Box<Expr<'c>>>,
+        /// Do not pass current context to the included template.
+        only: bool,
+        /// Output nothing if the included template does not exist.
+        ignore_missing: bool,
+        line: usize,
+        column: usize,
+        span: Span
+    },
     Import {
         /// Target template to import, which can be evaluated at runtime from
         /// provided expression.
         source: Box<Expr<'c>>,
         /// Target list alias => name.
-        targets: Vec<(Uuid, &'c str, ImportTarget<'c>)>,
-        line: usize
+        #[cfg_attr(feature = "serde", serde(with = "super::serde_uuid::targets"))]
+        targets: Vec<(Uuid, Cow<'c, str>, ImportTarget<'c>)>,
+        line: usize,
+        column: usize,
+        span: Span
     },
     Macro {
-        name: &'c str,
+        name: Cow<'c, str>,
         body: Box<Body<'c>>,
-        arguments: Vec<(Option<&'c str>, Expr<'c>)>,
-        line: usize
+        arguments: Vec<(Option<Cow<'c, str>>, Expr<'c>)>,
+        line: usize,
+        column: usize,
+        span: Span
+    },
+    /// Node returned by an extension token parser.
+    ///
+    /// Extension nodes are opaque, so with the `serde` feature writing a
+    /// module that contains one fails with an error.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Extension {
+        node: Box<ExtensionNode<'c> + 'c>,
+        line: usize,
+        column: usize,
+        span: Span
     }
 }
 
@@ -32,6 +104,87 @@ impl<'c> Body<'c> {
         Body::List { items: Vec::new() }
     }
 
+    /// Converts the node tree to one that does not borrow template source.
+    pub fn into_owned(self) -> Body<'static> {
+        match self {
+            Body::List { items } => Body::List {
+                items: items.into_iter().map(Body::into_owned).collect(),
+            },
+            Body::Text { value, line, column, span } => Body::Text { value: owned(value), line: line, column: column, span: span },
+            Body::Print { expr, line, column, span } => Body::Print { expr: Box::new(expr.into_owned()), line: line, column: column, span: span },
+            Body::Comment { value, line, column, span } => Body::Comment { value: owned(value), line: line, column: column, span: span },
+            Body::Flush { line, column, span } => Body::Flush { line: line, column: column, span: span },
+            Body::BlockReference { name, line, column, span } => Body::BlockReference { name: owned(name), line: line, column: column, span: span },
+            Body::Include { template, variables, only, ignore_missing, line, column, span } => Body::Include {
+                template: Box::new(template.into_owned()),
+                variables: variables.map(|v| Box::new(v.into_owned())),
+                only: only,
+                ignore_missing: ignore_missing,
+                line: line,
+                column: column,
+                span: span,
+            },
+            Body::Import { source, targets, line, column, span } => Body::Import {
+                source: Box::new(source.into_owned()),
+                targets: targets.into_iter()
+                    .map(|(uuid, alias, target)| (uuid, owned(alias), target.into_owned()))
+                    .collect(),
+                line: line,
+                column: column,
+                span: span,
+            },
+            Body::Macro { name, body, arguments, line, column, span } => Body::Macro {
+                name: owned(name),
+                body: Box::new(body.into_owned()),
+                arguments: arguments.into_iter()
+                    .map(|(name, default)| (name.map(owned), default.into_owned()))
+                    .collect(),
+                line: line,
+                column: column,
+                span: span,
+            },
+            Body::Extension { node, line, column, span } => Body::Extension { node: node.into_owned(), line: line, column: column, span: span },
+        }
+    }
+
+    /// Returns node position for error reporting, the list has no position.
+    pub fn location(&self) -> Option<Location> {
+        match *self {
+            Body::List { .. } => None,
+            Body::Text { line, column, span, .. }
+            | Body::Print { line, column, span, .. }
+            | Body::Comment { line, column, span, .. }
+            | Body::Flush { line, column, span }
+            | Body::BlockReference { line, column, span, .. }
+            | Body::Include { line, column, span, .. }
+            | Body::Import { line, column, span, .. }
+            | Body::Macro { line, column, span, .. }
+            | Body::Extension { line, column, span, .. } => Some(Location::spanning(line, column, span)),
+        }
+    }
+
+    /// Places the node at the line, column and span of template source.
+    ///
+    /// The list has no position and is left unchanged.
+    pub fn set_location(&mut self, new_line: usize, new_column: usize, new_span: Span) {
+        match *self {
+            Body::List { .. } => (),
+            Body::Text { ref mut line, ref mut column, ref mut span, .. }
+            | Body::Print { ref mut line, ref mut column, ref mut span, .. }
+            | Body::Comment { ref mut line, ref mut column, ref mut span, .. }
+            | Body::Flush { ref mut line, ref mut column, ref mut span }
+            | Body::BlockReference { ref mut line, ref mut column, ref mut span, .. }
+            | Body::Include { ref mut line, ref mut column, ref mut span, .. }
+            | Body::Import { ref mut line, ref mut column, ref mut span, .. }
+            | Body::Macro { ref mut line, ref mut column, ref mut span, .. }
+            | Body::Extension { ref mut line, ref mut column, ref mut span, .. } => {
+                *line = new_line;
+                *column = new_column;
+                *span = new_span;
+            },
+        }
+    }
+
     pub fn expect_print<'r>(&'r self) -> &'r Expr<'c> {
         match *self {
             Body::Print { expr: ref e, .. } => e,
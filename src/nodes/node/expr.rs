@@ -1,8 +1,21 @@
+use std::borrow::Cow;
+use nodes::node::owned;
 use uuid::Uuid;
+use tokens::Span;
+use error::Location;
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Expr<'c> {
     pub line: usize,
+    /// Column of the token the expression is reported at, starting from 1.
+    ///
+    /// Expressions created outside of the parser have zero column and empty span.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub column: usize,
+    /// Bytes of template source the whole expression was parsed from.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub span: Span,
     pub value: ExprValue<'c>,
 }
 
@@ -10,10 +23,26 @@ impl<'c> Expr<'c> {
     pub fn new_at<'r>(value: ExprValue<'r>, line: usize) -> Expr<'r> {
         Expr {
             line: line,
+            column: 0,
+            span: Span::default(),
             value: value
         }
     }
 
+    /// Places the expression at the column and span of template source.
+    pub fn with_span(self, column: usize, span: Span) -> Expr<'c> {
+        Expr {
+            column: column,
+            span: span,
+            .. self
+        }
+    }
+
+    /// Returns expression position for error reporting.
+    pub fn location(&self) -> Location {
+        Location::spanning(self.line, self.column, self.span)
+    }
+
     pub fn new_array<'r>(value: Vec<Expr<'r>>, line: usize) -> Expr<'r> {
         Expr::new_at(ExprValue::Array(value), line)
     }
@@ -23,7 +52,7 @@ impl<'c> Expr<'c> {
     }
 
     pub fn new_str_constant<'r>(value: &'r str, line: usize) -> Expr<'r> {
-        Expr::new_at(ExprValue::Constant(ExprConstant::Str(value)), line)
+        Expr::new_at(ExprValue::Constant(ExprConstant::Str(value.into())), line)
     }
 
     pub fn new_int_constant<'r>(value: i64, line: usize) -> Expr<'r> {
@@ -39,7 +68,17 @@ impl<'c> Expr<'c> {
     }
 
     pub fn new_name<'r>(name: &'r str, line: usize) -> Expr<'r> {
-        Expr::new_at(ExprValue::Name(name), line)
+        Expr::new_at(ExprValue::Name(name.into()), line)
+    }
+
+    /// Converts the expression to one that does not borrow template source.
+    pub fn into_owned(self) -> Expr<'static> {
+        Expr {
+            line: self.line,
+            column: self.column,
+            span: self.span,
+            value: self.value.into_owned(),
+        }
     }
 
     pub fn is_constant(&self) -> bool {
@@ -51,36 +90,41 @@ impl<'c> Expr<'c> {
             ExprValue::Conditional { .. } => false,
             ExprValue::Constant(_) => true,
             ExprValue::Name(_) => false,
-            ExprValue::UnaryOperator { value: "-", ref expr } => expr.is_constant(),
-            ExprValue::UnaryOperator { value: "+", ref expr } => expr.is_constant(),
-            ExprValue::UnaryOperator { .. } => false,
+            ExprValue::UnaryOperator { ref value, ref expr } => match value.as_ref() {
+                "-" | "+" => expr.is_constant(),
+                _ => false,
+            },
             ExprValue::Hash(ref items) => items.iter().all(|&(ref k, ref v)| k.is_constant() && v.is_constant()),
             ExprValue::GetAttr { .. } => false,
             ExprValue::ImportedFunctionCall { .. } => false,
             ExprValue::FunctionCall { .. } => false,
+            ExprValue::Filter { .. } => false,
+            ExprValue::Test { .. } => false,
         }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ExprConstant<'c> {
-    Str(&'c str),
+    Str(Cow<'c, str>),
     Bool(bool),
     Int(i64),
     Float(f64),
-    Big(&'c str),
+    Big(Cow<'c, str>),
     Null,
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ExprValue<'c> {
     Constant(ExprConstant<'c>),
-    Name(&'c str),
-    AssignName(&'c str),
+    Name(Cow<'c, str>),
+    AssignName(Cow<'c, str>),
     Array(Vec<Expr<'c>>),
     Hash(Vec<(Expr<'c>, Expr<'c>)>),
-    UnaryOperator { value: &'c str, expr: Box<Expr<'c>> },
-    BinaryOperator { value: &'c str, left: Box<Expr<'c>>, right: Box<Expr<'c>> },
+    UnaryOperator { value: Cow<'c, str>, expr: Box<Expr<'c>> },
+    BinaryOperator { value: Cow<'c, str>, left: Box<Expr<'c>>, right: Box<Expr<'c>> },
     Concat { left: Box<Expr<'c>>, right: Box<Expr<'c>> },
     Conditional { expr: Box<Expr<'c>>, yay: Box<Expr<'c>>, nay: Box<Expr<'c>> },
     GetAttr {
@@ -89,11 +133,103 @@ pub enum ExprValue<'c> {
         arguments: Vec<Expr<'c>>,
         call_type: ExprCallType
     },
-    ImportedFunctionCall { uuid: Uuid, alias: &'c str, arguments: Vec<Expr<'c>> },
-    FunctionCall { name: &'c str, arguments: Vec<(Option<&'c str>, Expr<'c>)> }
+    /// Call of an imported macro.
+    ImportedFunctionCall {
+        /// Target of the `Body::Import` the macro comes from.
+        #[cfg_attr(feature = "serde", serde(with = "super::serde_uuid"))]
+        uuid: Uuid,
+        /// Name the macro is called by, `alias.name` for macros of templates
+        /// imported with the `import` tag.
+        alias: Cow<'c, str>,
+        arguments: Vec<Expr<'c>>
+    },
+    FunctionCall { name: Cow<'c, str>, arguments: Vec<(Option<Cow<'c, str>>, Expr<'c>)> },
+    Filter { name: Cow<'c, str>, expr: Box<Expr<'c>>, arguments: Vec<Expr<'c>> },
+    Test { name: Cow<'c, str>, expr: Box<Expr<'c>>, arguments: Vec<Expr<'c>> },
+}
+
+impl<'c> ExprConstant<'c> {
+    pub fn into_owned(self) -> ExprConstant<'static> {
+        match self {
+            ExprConstant::Str(value) => ExprConstant::Str(owned(value)),
+            ExprConstant::Bool(value) => ExprConstant::Bool(value),
+            ExprConstant::Int(value) => ExprConstant::Int(value),
+            ExprConstant::Float(value) => ExprConstant::Float(value),
+            ExprConstant::Big(value) => ExprConstant::Big(owned(value)),
+            ExprConstant::Null => ExprConstant::Null,
+        }
+    }
+}
+
+impl<'c> ExprValue<'c> {
+    pub fn into_owned(self) -> ExprValue<'static> {
+        match self {
+            ExprValue::Constant(value) => ExprValue::Constant(value.into_owned()),
+            ExprValue::Name(name) => ExprValue::Name(owned(name)),
+            ExprValue::AssignName(name) => ExprValue::AssignName(owned(name)),
+            ExprValue::Array(items) => ExprValue::Array(owned_list(items)),
+            ExprValue::Hash(items) => ExprValue::Hash(
+                items.into_iter().map(|(k, v)| (k.into_owned(), v.into_owned())).collect()
+            ),
+            ExprValue::UnaryOperator { value, expr } => ExprValue::UnaryOperator {
+                value: owned(value),
+                expr: owned_box(expr),
+            },
+            ExprValue::BinaryOperator { value, left, right } => ExprValue::BinaryOperator {
+                value: owned(value),
+                left: owned_box(left),
+                right: owned_box(right),
+            },
+            ExprValue::Concat { left, right } => ExprValue::Concat {
+                left: owned_box(left),
+                right: owned_box(right),
+            },
+            ExprValue::Conditional { expr, yay, nay } => ExprValue::Conditional {
+                expr: owned_box(expr),
+                yay: owned_box(yay),
+                nay: owned_box(nay),
+            },
+            ExprValue::GetAttr { node, arg, arguments, call_type } => ExprValue::GetAttr {
+                node: owned_box(node),
+                arg: owned_box(arg),
+                arguments: owned_list(arguments),
+                call_type: call_type,
+            },
+            ExprValue::ImportedFunctionCall { uuid, alias, arguments } => ExprValue::ImportedFunctionCall {
+                uuid: uuid,
+                alias: owned(alias),
+                arguments: owned_list(arguments),
+            },
+            ExprValue::FunctionCall { name, arguments } => ExprValue::FunctionCall {
+                name: owned(name),
+                arguments: arguments.into_iter()
+                    .map(|(name, value)| (name.map(owned), value.into_owned()))
+                    .collect(),
+            },
+            ExprValue::Filter { name, expr, arguments } => ExprValue::Filter {
+                name: owned(name),
+                expr: owned_box(expr),
+                arguments: owned_list(arguments),
+            },
+            ExprValue::Test { name, expr, arguments } => ExprValue::Test {
+                name: owned(name),
+                expr: owned_box(expr),
+                arguments: owned_list(arguments),
+            },
+        }
+    }
+}
+
+fn owned_box<'c>(expr: Box<Expr<'c>>) -> Box<Expr<'static>> {
+    Box::new(expr.into_owned())
+}
+
+fn owned_list<'c>(items: Vec<Expr<'c>>) -> Vec<Expr<'static>> {
+    items.into_iter().map(Expr::into_owned).collect()
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ExprCallType {
     Any,
     Method,
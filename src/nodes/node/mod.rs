@@ -1,3 +1,12 @@
 pub mod body;
 pub mod expr;
 pub mod module;
+#[cfg(feature = "serde")]
+mod serde_uuid;
+
+use std::borrow::Cow;
+
+/// Returns the string without borrowing it from template source.
+pub fn owned<'c>(value: Cow<'c, str>) -> Cow<'static, str> {
+    Cow::Owned(value.into_owned())
+}
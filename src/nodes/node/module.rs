@@ -1,10 +1,24 @@
 use nodes::body::Body;
+use nodes::expr::Expr;
+use nodes::Block;
 
+/// Root Twig AST node.
+///
+/// Names and text are borrowed from template source, use `into_owned` to
+/// keep the tree after the source is dropped.
+///
+/// With the "serde" feature the tree can be written to JSON and read back.
+/// Nodes of extension tags can not be serialized.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Module<'c> {
     // Sub nodes.
     pub body: Body<'c>,
-    // pub blocks: Vec<Block>,
+    /// Blocks in definition order, including nested blocks.
+    pub blocks: Vec<Block<'c>>,
+    /// Name of the template this one extends, set by the `extends` tag.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parent: Option<Expr<'c>>,
     // pub macros: Vec<Macro>,
     // pub traits: Vec<Trait>,
 
@@ -21,12 +35,21 @@ pub struct Module<'c> {
     // class_end: Body<'c>,
 }
 
-/// Root Twig AST node.
 impl<'c> Module<'c> {
+    /// Converts the module to one that does not borrow template source.
+    pub fn into_owned(self) -> Module<'static> {
+        Module {
+            body: self.body.into_owned(),
+            blocks: self.blocks.into_iter().map(Block::into_owned).collect(),
+            parent: self.parent.map(Expr::into_owned),
+        }
+    }
+
     pub fn new() -> Module<'c> {
         Module {
             body: Body::new(),
-            // blocks: vec![],
+            blocks: vec![],
+            parent: None,
             // macros: vec![],
             // traits: vec![],
 
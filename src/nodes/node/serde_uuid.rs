@@ -0,0 +1,46 @@
+//! Writes node uuids as hyphenated strings.
+
+use serde::{ Serialize, Serializer, Deserialize, Deserializer };
+use serde::de::Error;
+use uuid::Uuid;
+
+pub fn serialize<S: Serializer>(uuid: &Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&uuid.to_hyphenated_string())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Uuid, D::Error> {
+    let value = try!(String::deserialize(deserializer));
+    parse(&value)
+}
+
+fn parse<E: Error>(value: &str) -> Result<Uuid, E> {
+    Uuid::parse_str(value).map_err(|e| E::custom(format!("invalid uuid {:?}: {}", value, e)))
+}
+
+/// Import targets of `Body::Import`.
+pub mod targets {
+    use serde::{ Serialize, Serializer, Deserialize, Deserializer };
+    use std::borrow::Cow;
+    use nodes::body::ImportTarget;
+    use uuid::Uuid;
+
+    pub fn serialize<'c, S: Serializer>(targets: &Vec<(Uuid, Cow<'c, str>, ImportTarget<'c>)>, serializer: S)
+        -> Result<S::Ok, S::Error>
+    {
+        let targets: Vec<_> = targets.iter()
+            .map(|&(ref uuid, ref alias, ref target)| (uuid.to_hyphenated_string(), alias, target))
+            .collect();
+        targets.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, 'c, D: Deserializer<'de>>(deserializer: D)
+        -> Result<Vec<(Uuid, Cow<'c, str>, ImportTarget<'c>)>, D::Error>
+    {
+        let targets: Vec<(String, Cow<'c, str>, ImportTarget<'c>)> = try!(Vec::deserialize(deserializer));
+        let mut result = Vec::with_capacity(targets.len());
+        for (uuid, alias, target) in targets {
+            result.push((try!(super::parse(&uuid)), alias, target));
+        }
+        Ok(result)
+    }
+}
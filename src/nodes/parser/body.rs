@@ -1,7 +1,7 @@
 use nodes::{ Parser, Parse };
 use nodes::body::Body;
 use nodes::expr::Expr;
-use tokens::{ TokenRef, TokenValueRef };
+use tokens::{ TokenRef, TokenValueRef, Span };
 use Expect;
 use error::{ TemplateResult, TemplateError };
 
@@ -25,59 +25,20 @@ pub fn subparse<'p, 'c, D>(parser: &mut Parser<'p, 'c>, test: D)
     -> TemplateResult<Body<'c>>
         where D: Fn(&TokenRef<'c>) -> Option<BlockEnd>
 {
-    let mut maybe_line = None;
     let mut rv = Vec::new();
 
-    while let Some(token) = try!(parser.maybe_current()) {
-        if let None = maybe_line {
-            maybe_line = Some(token.line);
-        }
-        match token.value {
-            TokenValueRef::Text(t) => {
-                try!(parser.next());
-                rv.push(Body::Text { value: t, line: token.line })
-            },
-            TokenValueRef::VarStart => {
-                try!(parser.next());
-                let expr = try!(Expr::parse(parser));
-                try!(parser.expect(TokenValueRef::VarEnd));
-                rv.push(Body::Print { expr: Box::new(expr), line: token.line });
-            },
-            TokenValueRef::BlockStart => {
-                try!(parser.next());
-                let token = try!(parser.current());
-
-                let tag_name = match token.value {
-                    TokenValueRef::Name(n) => n,
-                    _ => return Err(TemplateError::MustStartWithTagName.at(token.line)),
-                };
-
-                if let Some(end) = test(&token) {
-                    if end.drop_needle {
-                        try!(parser.next());
-                    }
-                    return if 1 == rv.len() {
-                        Ok(rv.remove(0))
-                    } else {
-                        Ok(Body::List { items: rv })
-                    }
-                }
-
-                let subparser = match parser.env.handlers.get(tag_name) {
-                    Some(sp) => sp,
-                    None => {
-                        unreachable!("errors when subparser not found not implemented")
-                    }
-                };
-
-                try!(parser.next());
-                let maybe_node = try!(subparser.parse(parser, token));
-                if let Some(node) = maybe_node {
-                     rv.push(node);
-                }
-            },
-            tv => { panic!("not implemented {:?}", tv) },
+    loop {
+        let step = match parser.maybe_current() {
+            Ok(Some(token)) => parse_node(parser, token, &test),
+            Ok(None) => break,
+            Err(e) => Err(e),
         };
+        match step {
+            Ok(Step::Node(Some(node))) => rv.push(node),
+            Ok(Step::Node(None)) => (),
+            Ok(Step::End) => break,
+            Err(e) => try!(parser.recover(e)),
+        }
     }
 
     if rv.len() == 1 {
@@ -86,3 +47,69 @@ pub fn subparse<'p, 'c, D>(parser: &mut Parser<'p, 'c>, test: D)
         Ok(Body::List { items: rv })
     }
 }
+
+/// Outcome of parsing the node at the current token.
+enum Step<'c> {
+    /// Parsed node, tags may produce no node.
+    Node(Option<Body<'c>>),
+    /// The end tag of the body was found.
+    End,
+}
+
+fn parse_node<'p, 'c, D>(parser: &mut Parser<'p, 'c>, token: TokenRef<'c>, test: &D)
+    -> TemplateResult<Step<'c>>
+        where D: Fn(&TokenRef<'c>) -> Option<BlockEnd>
+{
+    Ok(match token.value {
+        TokenValueRef::Text(t) => {
+            try!(parser.next());
+            Step::Node(Some(Body::Text { value: t.into(), line: token.line, column: token.column, span: token.span }))
+        },
+        TokenValueRef::CommentStart => {
+            try!(parser.next());
+            let value = match try!(parser.next()).value {
+                TokenValueRef::Text(t) => t,
+                other => return Err(TemplateError::UnexpectedTokenValue(other.into()).at_token(&token)),
+            };
+            Step::Node(Some(Body::Comment { value: value.into(), line: token.line, column: token.column, span: token.span }))
+        },
+        TokenValueRef::VarStart => {
+            try!(parser.next());
+            let expr = try!(Expr::parse(parser));
+            try!(parser.expect(TokenValueRef::VarEnd));
+            let span = Span::new(token.span.start, parser.end());
+            Step::Node(Some(Body::Print { expr: Box::new(expr), line: token.line, column: token.column, span: span }))
+        },
+        TokenValueRef::BlockStart => {
+            let tag_start = token;
+            try!(parser.next());
+            let token = try!(parser.current());
+
+            let tag_name = match token.value {
+                TokenValueRef::Name(n) => n,
+                _ => return Err(TemplateError::MustStartWithTagName.at_token(&token)),
+            };
+
+            if let Some(end) = test(&token) {
+                if end.drop_needle {
+                    try!(parser.next());
+                }
+                return Ok(Step::End);
+            }
+
+            let subparser = match parser.env.handlers.get(tag_name) {
+                Some(sp) => sp,
+                None => return Err(TemplateError::UnknownTag(tag_name.into()).at_token(&token)),
+            };
+
+            try!(parser.next());
+            let maybe_node = try!(subparser.parse(parser, token));
+            Step::Node(maybe_node.map(|mut node| {
+                let span = Span::new(tag_start.span.start, parser.end());
+                node.set_location(tag_start.line, tag_start.column, span);
+                node
+            }))
+        },
+        other => return Err(TemplateError::UnexpectedTokenValue(other.into()).at_token(&token)),
+    })
+}
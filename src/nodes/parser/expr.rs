@@ -1,10 +1,11 @@
 use nodes::{ Parse, Parser, ImportedFunction };
 use nodes::expr::{ Expr, ExprValue, ExprConstant, ExprCallType };
-use tokens::{ TokenValueRef, ConstRef, ConstNumberRef };
+use tokens::{ TokenRef, TokenValueRef, ConstRef, ConstNumberRef, Span };
 use operator::{ OperatorOptions, OperatorKind, Associativity };
 use error::{ TemplateResult, TemplateError };
 use Expect;
 use std::collections::VecDeque;
+use std::borrow::Cow;
 
 impl<'c> Parse<'c> for Expr<'c> {
     type Output = Expr<'c>;
@@ -24,6 +25,7 @@ pub fn parse_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, min_precedence: u16
 
     let mut expr = try!(get_primary(parser));
     let mut token = try!(parser.current());
+    let start = expr.span.start;
 
     loop {
         if let TokenValueRef::Operator(op_str) = token.value {
@@ -31,6 +33,21 @@ pub fn parse_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, min_precedence: u16
                 if precedence >= min_precedence {
                     try!(parser.next());
 
+                    if op_str == "is" || op_str == "is not" {
+                        expr = try!(parse_test_expression(parser, expr, token.line));
+                        expr = spanned(parser, expr, &token, start);
+                        if op_str == "is not" {
+                            expr = spanned(parser, Expr::new_at(ExprValue::UnaryOperator {
+                                value: "not".into(),
+                                expr: Box::new(expr),
+                            }, token.line), &token, start);
+                        }
+
+                        token = try!(parser.current());
+
+                        continue;
+                    }
+
                     // if callable ...
                         // TODO: Callable.
                     // else
@@ -38,11 +55,11 @@ pub fn parse_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, min_precedence: u16
                         Associativity::Left => precedence + 1,
                         Associativity::Right => precedence,
                     }));
-                    expr = Expr::new_at(ExprValue::BinaryOperator {
-                        value: op_str,
+                    expr = spanned(parser, Expr::new_at(ExprValue::BinaryOperator {
+                        value: op_str.into(),
                         left: Box::new(expr.clone()),
                         right: Box::new(expr1),
-                    }, token.line);
+                    }, token.line), &token, start);
                     // endif
 
                     token = try!(parser.current());
@@ -72,20 +89,22 @@ pub fn get_primary<'p, 'c>(parser: &mut Parser<'p, 'c>)
         if let OperatorOptions { kind: OperatorKind::Unary { .. }, precedence: Some(precedence), .. } = parser.get_operator_options(op_str) {
             try!(parser.next());
             let expr = try!(parse_expression(parser, precedence));
-            let parsed_expr = Expr::new_at(ExprValue::UnaryOperator {
-                value: op_str,
+            let parsed_expr = spanned(parser, Expr::new_at(ExprValue::UnaryOperator {
+                value: op_str.into(),
                 expr: Box::new(expr),
-            }, token.line);
+            }, token.line), &token, token.span.start);
             return parse_postfix_expression(parser, parsed_expr);
         }
     }
 
     if let TokenValueRef::Punctuation('(') = token.value {
         try!(parser.next());
-        let parsed_expr = try!(parse_expression(parser, 0));
+        let mut parsed_expr = try!(parse_expression(parser, 0));
         if let Err(_) = parser.expect(TokenValueRef::Punctuation(')')) {
-            return Err(TemplateError::ParenthesisNotClosed.at(token.line));
+            return Err(TemplateError::ParenthesisNotClosed.at_token(&token));
         }
+        // the span includes parentheses
+        parsed_expr.span = Span::new(token.span.start, parser.end());
         return parse_postfix_expression(parser, parsed_expr);
     }
 
@@ -106,13 +125,13 @@ pub fn get_function_node<'p, 'c>(parser: &mut Parser<'p, 'c>, name: &'c str, lin
             if let Some(ImportedFunction { uuid, alias, .. }) = parser.get_imported_function(name) {
                 return Ok(Expr::new_at(ExprValue::ImportedFunctionCall {
                     uuid: uuid,
-                    alias: alias,
+                    alias: alias.into(),
                     arguments: try!(parse_unnamed_arguments(parser, false))
                 }, line));
             }
 
             return Ok(Expr::new_at(ExprValue::FunctionCall {
-                name: name,
+                name: name.into(),
                 arguments: try!(parse_named_arguments(parser, false))
             }, line));
         }
@@ -157,16 +176,17 @@ pub fn parse_primary_expression<'p, 'c>(parser: &mut Parser<'p, 'c>)
         TokenValueRef::Punctuation('{') => try!(parse_hash_expression(parser)),
         other => return Err(
             TemplateError::UnexpectedTokenValue(other.into())
-                .at(token.line)
+                .at_token(&token)
         ),
     };
+    let expr = spanned(parser, expr, &token, token.span.start);
 
     parse_postfix_expression(parser, expr)
 }
 
 pub fn get_number_expr<'c>(num: ConstNumberRef<'c>, line: usize) -> Expr<'c> {
     Expr::new_at(ExprValue::Constant(match num {
-        ConstNumberRef::Big(v) => ExprConstant::Big(v),
+        ConstNumberRef::Big(v) => ExprConstant::Big(v.into()),
         ConstNumberRef::Float(v) => ExprConstant::Float(v),
         ConstNumberRef::Int(v) => ExprConstant::Int(v),
     }), line)
@@ -185,7 +205,7 @@ pub fn parse_string_expression<'p, 'c>(parser: &mut Parser<'p, 'c>)
 
         if let (true, TokenValueRef::Value(ConstRef::Str(value))) = (next_can_be_string, token.value) {
             try!(parser.next());
-            nodes.push_back(Expr::new_str_constant(value, token.line));
+            nodes.push_back(Expr::new_str_constant(value, token.line).with_span(token.column, token.span));
             next_can_be_string = false;
             continue;
         }
@@ -205,11 +225,11 @@ pub fn parse_string_expression<'p, 'c>(parser: &mut Parser<'p, 'c>)
         .expect("twig bug: expected first node to be string when in parse_string_expression state");
 
     for node in nodes {
-        let line = node.line;
+        let (line, column, span) = (node.line, node.column, expr.span.to(node.span));
         expr = Expr::new_at(
             ExprValue::Concat { left: Box::new(expr), right: Box::new(node) },
             line
-        );
+        ).with_span(column, span);
     }
 
     Ok(expr)
@@ -282,22 +302,22 @@ pub fn parse_hash_expression<'p, 'c>(parser: &mut Parser<'p, 'c>)
         let key = match token.value {
             TokenValueRef::Value(ConstRef::Str(v)) => {
                 try!(parser.next());
-                Expr::new_str_constant(v, token.line)
+                Expr::new_str_constant(v, token.line).with_span(token.column, token.span)
             },
             TokenValueRef::Name(v) => {
                 try!(parser.next());
-                Expr::new_str_constant(v, token.line)
+                Expr::new_str_constant(v, token.line).with_span(token.column, token.span)
             },
             TokenValueRef::Value(ConstRef::Num(num)) => {
                 try!(parser.next());
-                get_number_expr(num, token.line)
+                get_number_expr(num, token.line).with_span(token.column, token.span)
             },
             TokenValueRef::Punctuation('(') => {
                 try!(parse_expression(parser, 0))
             }
             _ => return Err(
                 TemplateError::InvalidHashKey { unexpected: token.value.into() }
-                    .at(token.line)
+                    .at_token(&token)
             ),
         };
 
@@ -342,6 +362,7 @@ pub fn parse_subscript_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, node: Exp
     trace!("parse_subscript_expression");
 
     let mut token = try!(parser.next());
+    let start_token = token.clone();
     let line = token.line;
     let mut arguments = Vec::<Expr<'c>>::new();
     let mut call_type = ExprCallType::Any;
@@ -354,29 +375,41 @@ pub fn parse_subscript_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, node: Exp
                 TokenValueRef::Value(ConstRef::Num(num)) => get_number_expr(num, line),
                 // OMG the hack here is _hilarious_:
                 // TODO: ($token->getType() == Twig_tokens::OPERATOR_TYPE && preg_match(Twig_Lexer::REGEX_NAME, $token->getValue()))
-                _ => return Err(TemplateError::ExpectedNameOrNumber.at(line))
-            };
+                _ => return Err(TemplateError::ExpectedNameOrNumber.at_token(&token))
+            }.with_span(token.column, token.span);
 
             token = try!(parser.current());
             if let TokenValueRef::Punctuation('(') = token.value {
+                // macros of imported templates are called as methods
+                if let (&ExprValue::Name(ref alias), &ExprValue::Constant(ExprConstant::Str(ref name))) = (&node.value, &arg.value) {
+                    if let Some(uuid) = parser.get_imported_template(alias) {
+                        let start = node.span.start;
+                        let call = ExprValue::ImportedFunctionCall {
+                            uuid: uuid,
+                            alias: format!("{}.{}", alias, name).into(),
+                            arguments: try!(parse_unnamed_arguments(parser, false)),
+                        };
+                        return Ok(spanned(parser, Expr::new_at(call, line), &start_token, start));
+                    }
+                }
                 call_type = ExprCallType::Method;
                 arguments = try!(parse_unnamed_arguments(parser, false));
             }
 
-            // TODO: Block of bad code
-
-            unimplemented!()
-
-            //arg
+            arg
         },
         _ => {
             call_type = ExprCallType::Array;
 
-            unimplemented!()
+            let arg = try!(parse_expression(parser, 0));
+            try!(parser.expect(TokenValueRef::Punctuation(']')));
+
+            arg
         }
     };
 
-    Ok(Expr::new_at(
+    let start = node.span.start;
+    Ok(spanned(parser, Expr::new_at(
         ExprValue::GetAttr {
             node: Box::new(node),
             arg: Box::new(arg),
@@ -384,14 +417,57 @@ pub fn parse_subscript_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, node: Exp
             call_type: call_type
         },
         line
-    ))
+    ), &start_token, start))
 }
 
 pub fn parse_filter_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, expr: Expr<'c>)
     -> TemplateResult<Expr<'c>>
 {
     trace!("parse_filter_expression");
-    unimplemented!()
+
+    let token = try!(parser.next());
+    let line = token.line;
+    let name_token = try!(parser.current());
+    let name = try!(parser.expect_name());
+    if !parser.has_filter(name) {
+        return Err(TemplateError::UnknownFilter(name.into()).at_token(&name_token));
+    }
+    let arguments = if try!(parser.test(TokenValueRef::Punctuation('('))) {
+        try!(parse_unnamed_arguments(parser, false))
+    } else {
+        Vec::new()
+    };
+
+    let start = expr.span.start;
+    Ok(spanned(parser, Expr::new_at(ExprValue::Filter {
+        name: name.into(),
+        expr: Box::new(expr),
+        arguments: arguments,
+    }, line), &token, start))
+}
+
+/// Parses test name and arguments after the "is" operator, i.e. `is defined`.
+pub fn parse_test_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, expr: Expr<'c>, line: usize)
+    -> TemplateResult<Expr<'c>>
+{
+    trace!("parse_test_expression");
+
+    let name_token = try!(parser.current());
+    let name = try!(parser.expect_name());
+    if !parser.has_test(name) {
+        return Err(TemplateError::UnknownTest(name.into()).at_token(&name_token));
+    }
+    let arguments = if try!(parser.test(TokenValueRef::Punctuation('('))) {
+        try!(parse_unnamed_arguments(parser, false))
+    } else {
+        Vec::new()
+    };
+
+    Ok(Expr::new_at(ExprValue::Test {
+        name: name.into(),
+        expr: Box::new(expr),
+        arguments: arguments,
+    }, line))
 }
 
 pub fn parse_unnamed_arguments<'p, 'c>(parser: &mut Parser<'p, 'c>, definition: bool)
@@ -426,7 +502,7 @@ pub fn parse_unnamed_arguments<'p, 'c>(parser: &mut Parser<'p, 'c>, definition:
 }
 
 pub fn parse_named_arguments<'p, 'c>(parser: &mut Parser<'p, 'c>, definition: bool)
-    -> TemplateResult<Vec<(Option<&'c str>, Expr<'c>)>>
+    -> TemplateResult<Vec<(Option<Cow<'c, str>>, Expr<'c>)>>
 {
     trace!("parse_named_arguments, definition {:?}", definition);
 
@@ -440,9 +516,10 @@ pub fn parse_named_arguments<'p, 'c>(parser: &mut Parser<'p, 'c>, definition: bo
         }
 
         let (name_expr, token) = if definition {
+            let name_token = try!(parser.current());
             let name = try!(parser.expect_name());
             let token = try!(parser.current());
-            (Expr::new_name(name, token.line), token)
+            (Expr::new_name(name, name_token.line).with_span(name_token.column, name_token.span), token)
         } else {
             (try!(parse_expression(parser, 0)), try!(parser.current()))
         };
@@ -455,7 +532,7 @@ pub fn parse_named_arguments<'p, 'c>(parser: &mut Parser<'p, 'c>, definition: bo
                 other => return Err(
                     TemplateError::ParameterNameMustBeAString {
                         given: format!("{:?}", other)
-                    }.at(token.line)
+                    }.at_token(&token)
                 ),
             };
 
@@ -501,7 +578,9 @@ pub fn parse_conditional_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, mut exp
 {
     trace!("parse_conditional_expression");
 
-    while try!(parser.skip_to_next_if(TokenValueRef::Punctuation('?'))) {
+    let start = expr.span.start;
+    while try!(parser.test(TokenValueRef::Punctuation('?'))) {
+        let token = try!(parser.next());
         let (expr2, expr3) =
             if !try!(parser.skip_to_next_if(TokenValueRef::Punctuation(':'))) {
                 let expr2 = try!(parse_expression(parser, 0));
@@ -513,12 +592,21 @@ pub fn parse_conditional_expression<'p, 'c>(parser: &mut Parser<'p, 'c>, mut exp
             } else {
                 (expr.clone(), try!(parse_expression(parser, 0)))
             };
-        expr = Expr::new_at(ExprValue::Conditional {
+        expr = spanned(parser, Expr::new_at(ExprValue::Conditional {
             expr: Box::new(expr),
             yay: Box::new(expr2),
             nay: Box::new(expr3)
-        }, try!(parser.current()).line);
+        }, token.line), &token, start);
     }
 
     Ok(expr)
 }
+
+/// Places the expression at the token, spanning from `start` to the end of
+/// the last parsed token.
+fn spanned<'p, 'c>(parser: &Parser<'p, 'c>, expr: Expr<'c>, token: &TokenRef<'c>, start: usize) -> Expr<'c> {
+    Expr {
+        line: token.line,
+        .. expr.with_span(token.column, Span::new(start, parser.end()))
+    }
+}
@@ -2,8 +2,10 @@ use std::iter::Peekable;
 use std::collections::HashMap;
 use tokens::{ TokenRef, TokenValueRef, TokenValue, TokenIter };
 use environment::ParsingEnvironment;
-use error::{ TemplateResult, TemplateError, Received };
+use error::{ TemplateResult, TemplateError, Received, At };
 use operator::{ OperatorOptions, OperatorKind };
+use nodes::Block;
+use nodes::expr::Expr;
 use uuid::Uuid;
 
 pub mod body;
@@ -26,13 +28,16 @@ impl<'c> ImportedFunction<'c> {
 }
 
 pub struct ImportedSymbols<'c> {
-    pub functions: HashMap<&'c str, ImportedFunction<'c>>
+    pub functions: HashMap<&'c str, ImportedFunction<'c>>,
+    /// Aliases of imported templates.
+    pub templates: HashMap<&'c str, Uuid>,
 }
 
 impl<'c> ImportedSymbols<'c> {
     pub fn new<'r>() -> ImportedSymbols<'r> {
         ImportedSymbols {
-            functions: HashMap::new()
+            functions: HashMap::new(),
+            templates: HashMap::new(),
         }
     }
 }
@@ -59,6 +64,16 @@ pub struct Parser<'p, 'c: 'p>
     pub tokens: Peekable<&'p mut TokenIter<'p, 'c>>,
     /// Imported symbol stack.
     pub imported_symbols: Vec<ImportedSymbols<'c>>,
+    /// Blocks defined so far.
+    pub blocks: Vec<Block<'c>>,
+    /// Template extended by the parsed one.
+    pub parent: Option<Expr<'c>>,
+    /// Errors collected in recovering mode.
+    pub errors: Vec<At<TemplateError>>,
+    /// Source byte offset after the last consumed token.
+    end: usize,
+    /// Continue parsing after errors.
+    recover: bool,
 }
 
 impl<'p, 'c: 'p> Parser<'p, 'c>
@@ -72,9 +87,57 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
             env: env,
             tokens: tokens.peekable(),
             imported_symbols: vec![ImportedSymbols::new()],
+            blocks: Vec::new(),
+            parent: None,
+            errors: Vec::new(),
+            end: 0,
+            recover: false,
         }
     }
 
+    /// Continue parsing after errors instead of stopping at the first one.
+    ///
+    /// Errors are collected to `errors`, see `recover`.
+    pub fn recovering(mut self) -> Parser<'p, 'c> {
+        self.recover = true;
+        self
+    }
+
+    /// Handles the error of a node that failed to parse.
+    ///
+    /// When not recovering, returns the error back. Otherwise the error is
+    /// collected together with lexer errors found on the way, and tokens are
+    /// skipped up to and including the next `BlockEnd` or `VarEnd`, so parsing
+    /// can continue after the broken tag or print statement.
+    pub fn recover<'r>(&'r mut self, error: At<TemplateError>) -> TemplateResult<()> {
+        if !self.recover {
+            return Err(error);
+        }
+        // parser returns lexer error as soon as it sees one, so the error
+        // at the current position is collected below
+        let at_lexer_error = match self.tokens.peek() {
+            Some(&Err(_)) => true,
+            _ => false,
+        };
+        if !at_lexer_error {
+            self.errors.push(error);
+        }
+        loop {
+            match self.tokens.next() {
+                Some(Ok(token)) => {
+                    self.end = token.span.end;
+                    match token.value {
+                        TokenValueRef::BlockEnd | TokenValueRef::VarEnd => break,
+                        _ => (),
+                    }
+                },
+                Some(Err(e)) => self.errors.push(e),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
     pub fn push_local_scope<'r>(&'r mut self) {
         self.imported_symbols.push(ImportedSymbols::new());
     }
@@ -104,6 +167,32 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
         None
     }
 
+    /// Registers specified alias as imported template, its macros are called
+    /// as methods of the alias.
+    pub fn add_imported_template<'r>(&'r mut self, alias: &'c str) -> Uuid {
+        let uuid = Uuid::new_v4();
+        self.imported_symbols
+            .last_mut().unwrap()
+            .templates
+                .insert(alias, uuid.clone());
+        uuid
+    }
+
+    /// Finds a template that was previously imported in this or parent scope.
+    pub fn get_imported_template<'r>(&'r self, alias: &str) -> Option<Uuid> {
+        for symbols in &self.imported_symbols {
+            if let Some(found) = symbols.templates.get(alias) {
+                return Some(*found);
+            }
+        }
+        None
+    }
+
+    /// Adds parsed block to the module.
+    pub fn set_block<'r>(&'r mut self, block: Block<'c>) {
+        self.blocks.push(block);
+    }
+
     /// Get current token or fail.
     ///
     /// Returns current token, does not modify iterator position.
@@ -144,10 +233,11 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
         };
 
         match self.tokens.next() {
-            None => return Err(TemplateError::UnexpectedEndOfTemplate.at(token.line)),
+            None => return Err(TemplateError::UnexpectedEndOfTemplate.at_token(&token)),
             Some(Err(e)) => return Err(e),
             _ => (),
         };
+        self.end = token.span.end;
 
         Ok(token)
     }
@@ -162,13 +252,17 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
         let (line, skip) = {
             let token = match self.tokens.peek() {
                 Some(&Ok(ref token)) => token,
-                _ => return Err(TemplateError::UnexpectedEndOfTemplate.at(1)),
+                Some(&Err(ref e)) => return Err(e.clone()),
+                None => return Err(TemplateError::UnexpectedEndOfTemplate.at(1)),
             };
             (token.line, token.value == expected)
         };
         if skip {
             match self.tokens.next() {
-                Some(Ok(_)) => Ok(true),
+                Some(Ok(token)) => {
+                    self.end = token.span.end;
+                    Ok(true)
+                },
                 None => return Err(TemplateError::UnexpectedEndOfTemplate.at(line)),
                 Some(Err(e)) => return Err(e),
             }
@@ -188,7 +282,7 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
             } else {
                 Err(
                     TemplateError::ExpectedOtherTokenValue((token.value.into(), expected.into()))
-                        .at(token.line)
+                        .at_token(&token)
                 )
             }
         )
@@ -207,7 +301,7 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
                 _ => Err(
                     TemplateError::ExpectedTokenTypeButReceived(
                         (TokenValue::Name("".into()), Received::Token(token.value.into()))
-                    ).at(token.line)
+                    ).at_token(&token)
                 )
             }
         )
@@ -222,7 +316,7 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
             |token| if token.value == expected {
                 Ok(token.clone())
             } else {
-                Err(error_message.at(token.line))
+                Err(error_message.at_token(&token))
             }
         )
     }
@@ -242,7 +336,10 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
             None => return Err(TemplateError::UnexpectedEndOfTemplate.at(1)),
             Some(&Err(ref e)) => return Err(e.clone()),
         };
-        try!(self.next());
+        // mismatched token is left for error recovery to skip
+        if res.is_ok() {
+            try!(self.next());
+        }
 
         res
     }
@@ -266,9 +363,26 @@ impl<'p, 'c: 'p> Parser<'p, 'c>
         }
     }
 
+    /// Returns source byte offset after the last consumed token.
+    ///
+    /// Node spans start at their first token and end here.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
     /// Returns options structure for specified operator.
     ///
     /// Operator must exist in environment, otherwise panics.
+    /// Returns true if filter with the name exists in environment.
+    pub fn has_filter(&self, name: &str) -> bool {
+        self.env.filters.contains(name)
+    }
+
+    /// Returns true if test with the name exists in environment.
+    pub fn has_test(&self, name: &str) -> bool {
+        self.env.tests.contains(name)
+    }
+
     pub fn get_operator_options<'r>(&'r self, op_str: &'c str) -> OperatorOptions {
         self.env.operators
             .get(op_str)
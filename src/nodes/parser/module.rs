@@ -1,3 +1,4 @@
+use std::mem;
 use nodes::{ Parse, Parser, Module };
 use nodes::body::Body;
 use error::TemplateResult;
@@ -14,6 +15,8 @@ impl<'c> Parse<'c> for Module<'c> {
         let body = try!(Body::parse(parser));
 
         module.body = body;
+        module.blocks = mem::replace(&mut parser.blocks, Vec::new());
+        module.parent = parser.parent.take();
 
         Ok(module)
     }
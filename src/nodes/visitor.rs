@@ -0,0 +1,161 @@
+use std::fmt;
+use nodes::Module;
+use nodes::body::Body;
+use nodes::expr::{ Expr, ExprValue };
+use error::TemplateResult;
+
+/// Visits template nodes after parsing.
+///
+/// Visitors are registered by extensions, and run in priority order, each
+/// over the whole module. A node is entered before its sub nodes and left
+/// after them, and can be rewritten in place by either hook. Sub nodes of
+/// the rewritten node are visited when it is replaced on enter.
+///
+/// Nodes of extension tags are opaque, so their sub nodes are not visited.
+pub trait NodeVisitor: Send + Sync {
+    /// Unique visitor name, it is part of the environment fingerprint.
+    fn name(&self) -> &'static str;
+
+    /// Increment when the visitor starts to rewrite nodes differently, so
+    /// that templates compiled with the old version are not reused.
+    fn version(&self) -> u32 {
+        0
+    }
+
+    /// Visitors with lower priority run first.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    fn enter_module<'c>(&self, _module: &mut Module<'c>) -> TemplateResult<()> {
+        Ok(())
+    }
+
+    fn leave_module<'c>(&self, _module: &mut Module<'c>) -> TemplateResult<()> {
+        Ok(())
+    }
+
+    fn enter_body<'c>(&self, _body: &mut Body<'c>) -> TemplateResult<()> {
+        Ok(())
+    }
+
+    fn leave_body<'c>(&self, _body: &mut Body<'c>) -> TemplateResult<()> {
+        Ok(())
+    }
+
+    fn enter_expr<'c>(&self, _expr: &mut Expr<'c>) -> TemplateResult<()> {
+        Ok(())
+    }
+
+    fn leave_expr<'c>(&self, _expr: &mut Expr<'c>) -> TemplateResult<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Box<NodeVisitor> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NodeVisitor({}, {})", self.name(), self.priority())
+    }
+}
+
+/// Runs the visitor over the module body and all its blocks.
+pub fn traverse_module<'c>(visitor: &NodeVisitor, module: &mut Module<'c>) -> TemplateResult<()> {
+    try!(visitor.enter_module(module));
+    if let Some(ref mut parent) = module.parent {
+        try!(traverse_expr(visitor, parent));
+    }
+    try!(traverse_body(visitor, &mut module.body));
+    for block in &mut module.blocks {
+        try!(traverse_body(visitor, &mut block.body));
+    }
+    visitor.leave_module(module)
+}
+
+/// Runs the visitor over the body node and its sub nodes.
+pub fn traverse_body<'c>(visitor: &NodeVisitor, body: &mut Body<'c>) -> TemplateResult<()> {
+    try!(visitor.enter_body(body));
+    match *body {
+        Body::List { ref mut items } => {
+            for item in items {
+                try!(traverse_body(visitor, item));
+            }
+        },
+        Body::Print { ref mut expr, .. } => try!(traverse_expr(visitor, expr)),
+        Body::Include { ref mut template, ref mut variables, .. } => {
+            try!(traverse_expr(visitor, template));
+            if let Some(ref mut variables) = *variables {
+                try!(traverse_expr(visitor, variables));
+            }
+        },
+        Body::Import { ref mut source, .. } => try!(traverse_expr(visitor, source)),
+        Body::Macro { ref mut body, ref mut arguments, .. } => {
+            for &mut (_, ref mut default) in arguments {
+                try!(traverse_expr(visitor, default));
+            }
+            try!(traverse_body(visitor, body));
+        },
+        Body::Text { .. }
+        | Body::Comment { .. }
+        | Body::Flush { .. }
+        | Body::BlockReference { .. }
+        | Body::Extension { .. } => (),
+    }
+    visitor.leave_body(body)
+}
+
+/// Runs the visitor over the expression and its sub expressions.
+pub fn traverse_expr<'c>(visitor: &NodeVisitor, expr: &mut Expr<'c>) -> TemplateResult<()> {
+    try!(visitor.enter_expr(expr));
+    match expr.value {
+        ExprValue::Array(ref mut items) => {
+            for item in items {
+                try!(traverse_expr(visitor, item));
+            }
+        },
+        ExprValue::Hash(ref mut items) => {
+            for &mut (ref mut key, ref mut value) in items {
+                try!(traverse_expr(visitor, key));
+                try!(traverse_expr(visitor, value));
+            }
+        },
+        ExprValue::UnaryOperator { ref mut expr, .. } => try!(traverse_expr(visitor, expr)),
+        ExprValue::BinaryOperator { ref mut left, ref mut right, .. }
+        | ExprValue::Concat { ref mut left, ref mut right } => {
+            try!(traverse_expr(visitor, left));
+            try!(traverse_expr(visitor, right));
+        },
+        ExprValue::Conditional { ref mut expr, ref mut yay, ref mut nay } => {
+            try!(traverse_expr(visitor, expr));
+            try!(traverse_expr(visitor, yay));
+            try!(traverse_expr(visitor, nay));
+        },
+        ExprValue::GetAttr { ref mut node, ref mut arg, ref mut arguments, .. } => {
+            try!(traverse_expr(visitor, node));
+            try!(traverse_expr(visitor, arg));
+            for argument in arguments {
+                try!(traverse_expr(visitor, argument));
+            }
+        },
+        ExprValue::ImportedFunctionCall { ref mut arguments, .. } => {
+            for argument in arguments {
+                try!(traverse_expr(visitor, argument));
+            }
+        },
+        ExprValue::FunctionCall { ref mut arguments, .. } => {
+            for &mut (_, ref mut argument) in arguments {
+                try!(traverse_expr(visitor, argument));
+            }
+        },
+        ExprValue::Filter { ref mut expr, ref mut arguments, .. }
+        | ExprValue::Test { ref mut expr, ref mut arguments, .. } => {
+            try!(traverse_expr(visitor, expr));
+            for argument in arguments {
+                try!(traverse_expr(visitor, argument));
+            }
+        },
+        ExprValue::Constant(_)
+        | ExprValue::Name(_)
+        | ExprValue::AssignName(_) => (),
+    }
+    visitor.leave_expr(expr)
+}
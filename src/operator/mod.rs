@@ -100,7 +100,7 @@ impl Operator {
     )
         -> Operator
     where
-        F: for<'e> Fn(&'e Value, &'e Value) -> RuntimeResult<Value>
+        F: for<'e> Fn(&'e Value, &'e Value) -> RuntimeResult<Value> + Send + Sync
     {
         Operator {
             options: OperatorOptions::new_binary(chars, precedence, associativity),
@@ -129,7 +129,7 @@ impl Operator {
     )
         -> Operator
     where
-        F: for<'e> Fn(&'e Value, &'e Value) -> RuntimeResult<Value>
+        F: for<'e> Fn(&'e Value, &'e Value) -> RuntimeResult<Value> + Send + Sync
     {
         Operator::new_binary(
             chars,
@@ -146,7 +146,7 @@ impl Operator {
     )
         -> Operator
     where
-        F: for<'e> Fn(&'e Value, &'e Value) -> RuntimeResult<Value>
+        F: for<'e> Fn(&'e Value, &'e Value) -> RuntimeResult<Value> + Send + Sync
     {
         Operator::new_binary(
             chars,
@@ -163,7 +163,7 @@ impl Operator {
     )
         -> Operator
     where
-        F: for<'e> Fn(&'e Value) -> RuntimeResult<Value>
+        F: for<'e> Fn(&'e Value) -> RuntimeResult<Value> + Send + Sync
     {
         Operator {
             options: OperatorOptions::new_unary(chars, precedence),
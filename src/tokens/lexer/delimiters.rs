@@ -1,14 +1,14 @@
-#[derive(Copy, Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Delimiters {
-    pub start: &'static str,
-    pub end: &'static str,
+    pub start: String,
+    pub end: String,
 }
 
 impl Delimiters {
-    pub fn new(start: &'static str, end: &'static str) -> Delimiters {
+    pub fn new<S: Into<String>, E: Into<String>>(start: S, end: E) -> Delimiters {
         Delimiters {
-            start: start,
-            end: end,
+            start: start.into(),
+            end: end.into(),
         }
     }
 }
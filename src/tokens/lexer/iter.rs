@@ -3,12 +3,14 @@ use std::collections::{ VecDeque };
 
 use super::Lexer;
 use error::TemplateResult;
-use tokens::{ TokenRef, TokenValueRef, ConstNumberRef, ConstRef, LexerOptions };
+use tokens::{ TokenRef, TokenValueRef, ConstNumberRef, ConstRef, LexerOptions, Span };
 use std::fmt;
 use Expect;
 use error::{ TemplateError, Received };
 
 const PUNCTUATION: &'static str = "()[]{}?:.,|";
+/// Whitespace trimmed by the line trim modifier, newlines are kept.
+const LINE_WHITESPACE: &'static str = " \t\0\x0B";
 
 /// Iteration state.
 #[derive(Debug, Copy, Clone)]
@@ -18,6 +20,7 @@ pub enum State {
     Var,
     String,
     Interpolation,
+    LineStatement,
 }
 
 /// Block position.
@@ -31,14 +34,15 @@ struct Position<'code> {
     len: usize,
     all_len: usize,
     value: TokenValueRef<'code>,
-    ws_trim: bool,
+    trim: Trim,
+    /// Line statement or line comment prefix.
+    line: bool,
 }
 
 impl<'code> Position<'code> {
     fn from_capture(options: &LexerOptions, c: Captures<'code>) -> Position<'code> {
         let (all_start, all_end) = c.pos(0).expect("twig bug: expected full capture when collecting positions");
         let (first_start, first_end) = c.pos(1).expect("twig bug: expected at least one subcapture (start, end) when collecting positions");
-        let second = c.pos(2);
 
         Position {
             loc: all_start,
@@ -50,14 +54,55 @@ impl<'code> Position<'code> {
                 s if s == options.tag_comment.start => TokenValueRef::CommentStart,
                 _ => unreachable!("twig bug: unexpected capture when collecting positions"),
             },
-            ws_trim: match second {
-                Some(_) => true,
-                _ => false,
+            trim: match c.at(2) {
+                Some(s) if s == options.whitespace_trim => Trim::All,
+                Some(_) => Trim::Line,
+                None => Trim::None,
             },
+            line: false,
+        }
+    }
+
+    /// Line prefix is captured after the indentation, which is trimmed.
+    fn from_line_capture(c: Captures<'code>, value: TokenValueRef<'code>) -> Position<'code> {
+        let (start, end) = c.pos(1).expect("twig bug: expected line prefix capture when collecting positions");
+
+        Position {
+            loc: start,
+            len: end - start,
+            all_len: end - start,
+            value: value,
+            trim: Trim::Line,
+            line: true,
+        }
+    }
+}
+
+/// Whitespace control modifier next to a delimiter, i.e. `{{-` or `~}}`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Trim {
+    None,
+    /// Trims all whitespace.
+    All,
+    /// Trims spaces and tabs, but keeps newlines.
+    Line,
+}
+
+impl Trim {
+    /// Trims whitespace at the end of text preceding the delimiter.
+    fn trim_right(self, text: &str) -> &str {
+        match self {
+            Trim::None => text,
+            Trim::All => text.trim_right(),
+            Trim::Line => trim_line_right(text),
         }
     }
 }
 
+fn trim_line_right(text: &str) -> &str {
+    text.trim_right_matches(|c| LINE_WHITESPACE.contains(c))
+}
+
 /// Twig has different brackets: (, {, [, etc.
 /// The "interpolation" bracket is memorized as `IntStart` and `IntEnd` and looks
 /// like "#{ blah }".
@@ -122,6 +167,8 @@ pub struct TokenIter<'iteration, 'code> {
     end: usize,
     finished: bool,
     is_error: bool,
+    /// Continue lexing after errors.
+    recover: bool,
 
     state: State,
     states: Vec<State>,
@@ -130,6 +177,10 @@ pub struct TokenIter<'iteration, 'code> {
 
     current_var_block_line: Option<usize>,
     line_num: usize,
+    /// Byte where the line of the cursor starts.
+    line_start: usize,
+    /// Characters from the line start to the cursor.
+    column: usize,
 }
 
 impl<'iteration, 'code> Iterator for TokenIter<'iteration, 'code> {
@@ -137,11 +188,11 @@ impl<'iteration, 'code> Iterator for TokenIter<'iteration, 'code> {
 
     fn next(&mut self) -> Option<TemplateResult<TokenRef<'code>>> {
 
-        if self.finished {
-            return None;
-        }
-
+        // tokens buffered before the end are still returned
         if self.tokens.len() == 0 {
+            if self.finished {
+                return None;
+            }
             self.collect_tokens();
         }
 
@@ -166,7 +217,7 @@ impl<'code, T> Expect<(usize, TokenValueRef<'code>)> for T where T: Iterator<Ite
                 return Err(
                     TemplateError::ExpectedTokenTypeButReceived(
                         (expected.into(), Received::Token(token.value.into()))
-                    ).at(token.line)
+                    ).at_token(&token)
                 );
             },
             (Some(error), _) => error,
@@ -180,13 +231,23 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
     /// Create the iterator.
     pub fn new<'caller>(lexer: &'caller Lexer, code: &'code str) -> TokenIter<'caller, 'code> {
         // find all token starts in one go
-        let positions = lexer.matchers.lex_tokens_start.captures_iter(code)
+        let mut positions = lexer.matchers.lex_tokens_start.captures_iter(code)
             .filter_map(|c| match c.is_empty() {
                 true => None,
                 false => Some(Position::from_capture(&lexer.options, c)),
             })
             .collect::<Vec<Position>>();
 
+        if let Some(ref regex) = lexer.matchers.lex_line_statement_start {
+            positions.extend(regex.captures_iter(code)
+                .map(|c| Position::from_line_capture(c, TokenValueRef::BlockStart)));
+        }
+        if let Some(ref regex) = lexer.matchers.lex_line_comment_start {
+            positions.extend(regex.captures_iter(code)
+                .map(|c| Position::from_line_capture(c, TokenValueRef::CommentStart)));
+        }
+        positions.sort_by(|a, b| a.loc.cmp(&b.loc));
+
         let code_len = code.len();
 
         let iter = TokenIter {
@@ -195,6 +256,8 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             cursor: 0,
             current_var_block_line: None,
             line_num: 1,
+            line_start: 0,
+            column: 0,
             end: code_len,
             state: State::Data,
             states: Vec::new(),
@@ -203,12 +266,23 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             positions: positions,
             tokens: VecDeque::new(),
             is_error: false,
+            recover: false,
             finished: false,
         };
 
         iter
     }
 
+    /// Continue lexing after errors instead of ending the stream at the first one.
+    ///
+    /// The offending characters are skipped, unclosed comments and raw blocks
+    /// swallow the rest of the template. All errors are returned in the token
+    /// stream at the place they were found.
+    pub fn recovering(mut self) -> TokenIter<'iteration, 'code> {
+        self.recover = true;
+        self
+    }
+
     /// When we run out of tokens, we call this function to buffer more.
     fn collect_tokens(&mut self) {
         loop {
@@ -229,6 +303,13 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                     _ => (),
                 };
 
+                // line statement can end with the template
+                if let State::LineStatement = self.state {
+                    let end = self.end;
+                    self.push_token(TokenValueRef::BlockEnd, end, end);
+                    self.pop_state();
+                }
+
                 self.finished = true;
                 break;
             }
@@ -245,6 +326,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                 State::Var => self.lex_var(),
                 State::String => self.lex_string(),
                 State::Interpolation => self.lex_interpolation(),
+                State::LineStatement => self.lex_line_statement(),
             }
         }
     }
@@ -257,7 +339,8 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         if self.position == positions_len {
             let loc = self.cursor;
 
-            self.push_token(TokenValueRef::Text(&self.code[loc..]));
+            let end = self.end;
+            self.push_token(TokenValueRef::Text(&self.code[loc..]), loc, end);
             self.cursor = self.end;
 
             return;
@@ -277,17 +360,30 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         let loc = self.cursor;
         let text_content = &self.code[loc .. position.loc];
 
-        self.push_token(
-            if position.ws_trim {
-                TokenValueRef::Text(text_content.trim_right())
-            } else {
-                TokenValueRef::Text(text_content)
-            }
-        );
+        let text = match position.trim {
+            Trim::None if self.lexer.options.lstrip_blocks && position.value != TokenValueRef::VarStart => {
+                self.lstripped(position.loc)
+            },
+            trim => trim.trim_right(text_content),
+        };
+        self.push_token(TokenValueRef::Text(text), loc, loc + text.len());
         self.move_cursor(text_content.len() + position.all_len);
 
+        let delimiter_end = position.loc + position.all_len;
+        if position.line {
+            match position.value {
+                TokenValueRef::BlockStart => {
+                    self.push_token(TokenValueRef::BlockStart, position.loc, delimiter_end);
+                    self.push_state(State::LineStatement);
+                    self.current_var_block_line = Some(self.line_num);
+                },
+                _ => self.lex_line_comment(position.loc),
+            }
+            return;
+        }
+
         match position.value {
-            TokenValueRef::CommentStart => self.lex_comment(),
+            TokenValueRef::CommentStart => self.lex_comment(position.loc),
             TokenValueRef::BlockStart => {
                 let loc = self.cursor;
                 // raw data?
@@ -321,12 +417,12 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                     }
                 }
 
-                self.push_token(TokenValueRef::BlockStart);
+                self.push_token(TokenValueRef::BlockStart, position.loc, delimiter_end);
                 self.push_state(State::Block);
                 self.current_var_block_line = Some(self.line_num);
             },
             TokenValueRef::VarStart => {
-                self.push_token(TokenValueRef::VarStart);
+                self.push_token(TokenValueRef::VarStart, position.loc, delimiter_end);
                 self.push_state(State::Var);
                 self.current_var_block_line = Some(self.line_num);
             },
@@ -343,7 +439,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             if let Some(captures) = self.lexer.matchers.lex_block.captures(&self.code[loc ..]) {
 
                 if let Some((start, end)) = captures.pos(0) {
-                    self.push_token(TokenValueRef::BlockEnd);
+                    self.push_trimmed(TokenValueRef::BlockEnd, loc + start, loc + end);
                     self.move_cursor(end - start);
                     self.pop_state();
 
@@ -366,7 +462,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             if let Some(captures) = self.lexer.matchers.lex_var.captures(&self.code[loc ..]) {
 
                 if let Some((start, end)) = captures.pos(0) {
-                    self.push_token(TokenValueRef::VarEnd);
+                    self.push_trimmed(TokenValueRef::VarEnd, loc + start, loc + end);
                     self.move_cursor(end - start);
                     self.pop_state();
 
@@ -380,6 +476,31 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         self.lex_expression();
     }
 
+    fn lex_line_statement(&mut self) {
+
+        if 0 == self.brackets.len() {
+
+            let loc = self.cursor;
+
+            if let Some(captures) = self.lexer.matchers.lex_line_statement_end.captures(&self.code[loc ..]) {
+
+                match (captures.pos(0), captures.pos(1)) {
+                    (Some((start, end)), Some((newline_start, newline_end))) => {
+                        // block end is the newline, so the line is removed from output
+                        self.push_token(TokenValueRef::BlockEnd, loc + newline_start, loc + newline_end);
+                        self.move_cursor(end - start);
+                        self.pop_state();
+
+                        return;
+                    },
+                    _ => unreachable!("twig bug: captured lex_line_statement_end but no capture data"),
+                }
+            }
+        }
+
+        self.lex_expression();
+    }
+
     fn lex_expression(&mut self) {
 
         // whitespace
@@ -389,16 +510,14 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                 self.move_cursor(end - start);
                 if self.cursor >= self.end {
                     let var_line = self.current_var_block_line;
-                    self.push_error(
-                        TemplateError::Unclosed(
-                            match self.state {
-                                State::Block => "block",
-                                State::Var => "variable",
-                                _ => unreachable!("twig bug: expected state at block or variable, but other state found"),
-                            }.into()
-                        ),
-                        var_line
-                    );
+                    let unclosed = match self.state {
+                        State::Block => "block",
+                        State::Var => "variable",
+                        // line statement is closed at the end of template
+                        State::LineStatement => return,
+                        _ => unreachable!("twig bug: expected state at block or variable, but other state found"),
+                    };
+                    self.push_error(TemplateError::Unclosed(unclosed.into()), var_line);
                     return;
                 }
             } else {
@@ -412,7 +531,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             if let Some((start, end)) = captures.pos(0) {
                 let op_str = self.code[loc + start .. loc + end].trim_right();
 
-                self.push_token(TokenValueRef::Operator(op_str));
+                self.push_trimmed(TokenValueRef::Operator(op_str), loc + start, loc + end);
                 self.move_cursor(end - start);
 
                 return;
@@ -425,7 +544,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         let loc = self.cursor;
         if let Some(captures) = self.lexer.matchers.regex_name.captures(&self.code[loc ..]) {
             if let Some((start, end)) = captures.pos(0) {
-                self.push_token(TokenValueRef::Name(&self.code[loc + start .. loc + end]));
+                self.push_token(TokenValueRef::Name(&self.code[loc + start .. loc + end]), loc + start, loc + end);
                 self.move_cursor(end - start);
 
                 return;
@@ -461,7 +580,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                     }
                 };
 
-                self.push_token(TokenValueRef::Value(ConstRef::Num(twig_number)));
+                self.push_token(TokenValueRef::Value(ConstRef::Num(twig_number)), loc + start, loc + end);
                 self.move_cursor(end - start);
 
                 return;
@@ -490,6 +609,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                                     ),
                                     Some(expect.line_num)
                                 );
+                                self.move_cursor(1);
                                 return;
                             }
                         },
@@ -500,12 +620,13 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                                 ),
                                 Some(line_num)
                             );
+                            self.move_cursor(1);
                             return;
                         }
                     }
                 }
 
-                self.push_token(TokenValueRef::Punctuation(c));
+                self.push_token(TokenValueRef::Punctuation(c), loc, loc + c.len_utf8());
                 self.move_cursor(1);
 
                 return;
@@ -518,7 +639,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             if let Some((start, end)) = captures.pos(0) {
                 self.push_token(TokenValueRef::Value(ConstRef::Str(
                     &self.code[loc + start + 1 .. loc + end - 1]
-                )));
+                )), loc + start, loc + end);
                 self.move_cursor(end - start);
 
                 return;
@@ -537,7 +658,10 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             return;
         }
 
-        let next_char = &self.code[loc .. loc + 1];
+        let next_char = match self.code[loc ..].chars().next() {
+            Some(c) => c,
+            None => unreachable!("twig bug: expected character before the end of template"),
+        };
         let line_num = self.line_num;
         self.push_error(
             TemplateError::UnexpectedCharacter(
@@ -545,6 +669,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             ),
             Some(line_num)
         );
+        self.move_cursor(next_char.len_utf8());
     }
 
     fn lex_string(&mut self) {
@@ -554,7 +679,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         if let Some(captures) = self.lexer.matchers.interpolation_start.captures(&self.code[loc ..]) {
             if let Some((start, end)) = captures.pos(0) {
                 self.brackets.push(Bracket::new(BracketSymbol::IntStart, self.line_num));
-                self.push_token(TokenValueRef::InterpolationStart);
+                self.push_trimmed(TokenValueRef::InterpolationStart, loc + start, loc + end);
                 self.move_cursor(end - start);
                 self.push_state(State::Interpolation);
 
@@ -568,7 +693,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         if part_end > 0 {
             self.push_token(TokenValueRef::Value(ConstRef::Str(
                 &self.code[loc .. loc + part_end]
-            )));
+            )), loc, loc + part_end);
             self.move_cursor(part_end);
 
             return;
@@ -589,6 +714,8 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                         ),
                         Some(other_bracket.line_num)
                     );
+                    self.pop_state();
+                    self.move_cursor(1);
                 },
                 None => unreachable!("twig bug: expected bracket when lexng string end"),
             }
@@ -607,7 +734,7 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             if let Some(captures) = self.lexer.matchers.interpolation_end.captures(&self.code[loc ..]) {
                 if let Some((start, end)) = captures.pos(0) {
                     self.brackets.pop();
-                    self.push_token(TokenValueRef::InterpolationEnd);
+                    self.push_trimmed(TokenValueRef::InterpolationEnd, loc + start, loc + end);
                     self.move_cursor(end - start);
                     self.pop_state();
 
@@ -621,22 +748,51 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         self.lex_expression();
     }
 
-    fn lex_comment(&mut self) {
+    /// Lexes comment contents, the comment delimiter starts at `comment_start`.
+    fn lex_comment(&mut self, comment_start: usize) {
 
         let loc = self.cursor;
         let maybe_found = self.lexer.matchers.lex_comment.find(&self.code[loc ..]);
 
         match maybe_found {
-            Some((_, end)) => {
+            Some((start, end)) => {
+                if self.lexer.options.comments {
+                    // comment start spans the whole comment
+                    let comment_end = loc + self.code[loc .. loc + end].trim_right().len();
+                    self.push_token(TokenValueRef::CommentStart, comment_start, comment_end);
+                    // comment text is pushed even if empty, it always follows comment start
+                    let token = self.token_at(TokenValueRef::Text(&self.code[loc .. loc + start]), loc, loc + start);
+                    self.tokens.push_back(Ok(token));
+                }
                 self.move_cursor(end);
             },
             None => {
                 let line_num = self.line_num;
                 self.push_error(TemplateError::UnclosedComment, Some(line_num));
+                self.skip_to_end();
             }
         };
     }
 
+    /// Lexes line comment contents up to the end of line, the comment
+    /// prefix starts at `comment_start`.
+    fn lex_line_comment(&mut self, comment_start: usize) {
+
+        let loc = self.cursor;
+        let line_end = match self.code[loc ..].find('\n') {
+            Some(newline) => loc + newline + 1,
+            None => self.end,
+        };
+
+        if self.lexer.options.comments {
+            let text = self.code[loc .. line_end].trim_right_matches(|c| c == '\n' || c == '\r');
+            self.push_token(TokenValueRef::CommentStart, comment_start, loc + text.len());
+            let token = self.token_at(TokenValueRef::Text(text), loc, loc + text.len());
+            self.tokens.push_back(Ok(token));
+        }
+        self.move_cursor(line_end - loc);
+    }
+
     fn lex_raw_data(&mut self, tag: &'code str) {
         let loc = self.cursor;
         let maybe_captures = {
@@ -649,19 +805,18 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
 
         match maybe_captures {
             Some(captures) => {
-                let maybe_full = captures.pos(0);
-                let maybe_end = captures.at(1);
-
-                match (maybe_full, maybe_end) {
-                    (Some((start, end)), Some(end_text)) => {
-                        let mut text = &self.code[loc..loc + start];
-                        self.move_cursor(end - start);
+                let trim = match captures.at(1) {
+                    Some(s) if s == self.lexer.options.whitespace_trim => Trim::All,
+                    Some(_) => Trim::Line,
+                    None => Trim::None,
+                };
 
-                        if end_text.contains("-") {
-                            text = text.trim_right()
-                        }
+                match captures.pos(0) {
+                    Some((start, end)) => {
+                        let text = trim.trim_right(&self.code[loc..loc + start]);
 
-                        self.push_token(TokenValueRef::Text(text));
+                        self.push_token(TokenValueRef::Text(text), loc, loc + text.len());
+                        self.move_cursor(end);
                     },
                     _ => unreachable!("twig bug: captured lex_raw_data but no capture data"),
                 }
@@ -674,11 +829,26 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                     ),
                     Some(line_num)
                 );
+                self.skip_to_end();
             }
         };
     }
 
-    fn push_token(&mut self, token_value: TokenValueRef<'code>) {
+    /// Returns text from the cursor to the tag at `tag_start`, without spaces
+    /// and tabs before the tag if nothing else precedes it on the line.
+    fn lstripped(&self, tag_start: usize) -> &'code str {
+        let stripped = trim_line_right(&self.code[.. tag_start]);
+
+        if stripped.is_empty() || stripped.ends_with('\n') {
+            let end = if stripped.len() > self.cursor { stripped.len() } else { self.cursor };
+            &self.code[self.cursor .. end]
+        } else {
+            &self.code[self.cursor .. tag_start]
+        }
+    }
+
+    /// Pushes token lexed from `start..end` bytes of the source.
+    fn push_token(&mut self, token_value: TokenValueRef<'code>, start: usize, end: usize) {
         // do not push empty text tokens
         if let TokenValueRef::Text(ref text) = token_value {
             if text.len() == 0 {
@@ -686,7 +856,51 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
             }
         }
 
-        self.tokens.push_back(Ok(TokenRef { value: token_value, line: self.line_num }));
+        let token = self.token_at(token_value, start, end);
+        self.tokens.push_back(Ok(token));
+    }
+
+    /// Pushes token lexed from `start..end` bytes, without surrounding whitespace.
+    fn push_trimmed(&mut self, token_value: TokenValueRef<'code>, start: usize, end: usize) {
+        let text = &self.code[start .. end];
+        let trimmed_start = start + text.len() - text.trim_left().len();
+        let trimmed_end = start + text.trim_right().len();
+
+        self.push_token(token_value, trimmed_start, trimmed_end);
+    }
+
+    /// Creates the token, the token can start ahead of the cursor.
+    fn token_at(&self, token_value: TokenValueRef<'code>, start: usize, end: usize) -> TokenRef<'code> {
+        // only the code between the cursor and the token is scanned
+        let (line, column) = if start >= self.cursor {
+            let ahead = &self.code[self.cursor .. start];
+            match ahead.rfind('\n') {
+                Some(newline) => (
+                    self.line_num + ahead.matches('\n').count(),
+                    ahead[newline + 1 ..].chars().count()
+                ),
+                None => (self.line_num, self.column + ahead.chars().count()),
+            }
+        } else if start >= self.line_start {
+            (self.line_num, self.column - self.code[start .. self.cursor].chars().count())
+        } else {
+            let behind = &self.code[start .. self.cursor];
+            let line_start = match self.code[.. start].rfind('\n') {
+                Some(newline) => newline + 1,
+                None => 0,
+            };
+            (
+                self.line_num - behind.matches('\n').count(),
+                self.code[line_start .. start].chars().count()
+            )
+        };
+
+        TokenRef {
+            value: token_value,
+            line: line,
+            column: column + 1,
+            span: Span::new(start, end),
+        }
     }
 
     fn push_error(&mut self, message: TemplateError, line_num: Option<usize>) {
@@ -696,7 +910,13 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
                 None => unreachable!("twig bug: error should not be pushed without a line number"),
             })
         ));
-        self.is_error = true;
+        self.is_error = !self.recover;
+    }
+
+    /// Skips the rest of the template after an error that can not be recovered from.
+    fn skip_to_end(&mut self) {
+        let rest = self.end - self.cursor;
+        self.move_cursor(rest);
     }
 
     fn push_state(&mut self, state: State) {
@@ -719,9 +939,13 @@ impl<'iteration, 'code> TokenIter<'iteration, 'code> {
         self.cursor += offset;
 
         let mut lines = 0;
-        for c in self.code[prev_loc .. self.cursor].chars() {
+        for (i, c) in self.code[prev_loc .. self.cursor].char_indices() {
             if c == '\n' {
                 lines += 1;
+                self.line_start = prev_loc + i + 1;
+                self.column = 0;
+            } else {
+                self.column += 1;
             }
         }
 
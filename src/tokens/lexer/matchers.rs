@@ -3,6 +3,9 @@ use regex::{ Regex, quote };
 
 use tokens::LexerOptions;
 
+/// Whitespace trimmed by the line trim modifier, newlines are kept.
+const LINE_WHITESPACE: &'static str = r#"[ \t\x00\x0B]"#;
+
 pub struct Matchers {
     pub whitespace: Regex,
     pub regex_name: Regex,
@@ -18,6 +21,9 @@ pub struct Matchers {
     pub lex_block_raw: Regex,
     pub lex_block_line: Regex,
     pub lex_tokens_start: Regex,
+    pub lex_line_statement_start: Option<Regex>,
+    pub lex_line_statement_end: Regex,
+    pub lex_line_comment_start: Option<Regex>,
     pub interpolation_start: Regex,
     pub interpolation_end: Regex,
 }
@@ -27,6 +33,12 @@ impl Matchers {
         options: &LexerOptions,
         operators: &HashSet<&'static str>
     ) -> Matchers {
+        // newline after block tags and comments
+        let block_newline = match options.trim_blocks {
+            true => r#"\n?"#,
+            false => "",
+        };
+
         Matchers {
             whitespace: {
                 Regex::new(
@@ -57,9 +69,12 @@ impl Matchers {
             lex_var: {
                 Regex::new(
                     &format!(
-                        r#"\A(?:\s*{}{}\s*|\s*{})"#,
+                        r#"\A\s*(?:{}{}\s*|{}{}{}*|{})"#,
                         &quote(&options.whitespace_trim),
                         &quote(&options.tag_variable.end),
+                        &quote(&options.whitespace_line_trim),
+                        &quote(&options.tag_variable.end),
+                        LINE_WHITESPACE,
                         &quote(&options.tag_variable.end)
                     )
                 ).ok().expect("Failed to init lex_var")
@@ -67,22 +82,29 @@ impl Matchers {
             lex_block: {
                 Regex::new(
                     &format!(
-                        r#"\A\s*(?:{}{}\s*|\s*{})\n?"#,
+                        r#"\A\s*(?:{}{}\s*|{}{}{}*|{}{})"#,
                         &quote(&options.whitespace_trim),
                         &quote(&options.tag_block.end),
-                        &quote(&options.tag_block.end)
+                        &quote(&options.whitespace_line_trim),
+                        &quote(&options.tag_block.end),
+                        LINE_WHITESPACE,
+                        &quote(&options.tag_block.end),
+                        block_newline
                     )
                 ).ok().expect("Failed to init lex_block")
             },
             lex_raw_data: {
                 Regex::new(
                     &format!(
-                        r#"(?s)({}{}|{})\s*(?:endraw)\s*(?:{}{}\s*|\s*{})"#,
+                        r#"(?s){}({}|{})?\s*(?:endraw)\s*(?:{}{}\s*|{}{}{}*|{})"#,
                         &quote(&options.tag_block.start),
                         &quote(&options.whitespace_trim),
-                        &quote(&options.tag_block.start),
+                        &quote(&options.whitespace_line_trim),
                         &quote(&options.whitespace_trim),
                         &quote(&options.tag_block.end),
+                        &quote(&options.whitespace_line_trim),
+                        &quote(&options.tag_block.end),
+                        LINE_WHITESPACE,
                         &quote(&options.tag_block.end)
                     )
                 ).ok().expect("Failed to init lex_raw_data")
@@ -90,12 +112,15 @@ impl Matchers {
             lex_verbatim_data: {
                 Regex::new(
                     &format!(
-                        r#"(?s)({}{}|{})\s*(?:endverbatim)\s*(?:{}{}\s*|\s*{})"#,
+                        r#"(?s){}({}|{})?\s*(?:endverbatim)\s*(?:{}{}\s*|{}{}{}*|{})"#,
                         &quote(&options.tag_block.start),
                         &quote(&options.whitespace_trim),
-                        &quote(&options.tag_block.start),
+                        &quote(&options.whitespace_line_trim),
                         &quote(&options.whitespace_trim),
                         &quote(&options.tag_block.end),
+                        &quote(&options.whitespace_line_trim),
+                        &quote(&options.tag_block.end),
+                        LINE_WHITESPACE,
                         &quote(&options.tag_block.end)
                     )
                 ).ok().expect("Failed to init lex_verbatim_data")
@@ -106,19 +131,26 @@ impl Matchers {
             lex_comment: {
                 Regex::new(
                     &format!(
-                        r#"(?s)(?:{}{}\s*|{})\n?"#,
+                        r#"(?s)(?:{}{}\s*|{}{}{}*|{}{})"#,
                         &quote(&options.whitespace_trim),
                         &quote(&options.tag_comment.end),
-                        &quote(&options.tag_comment.end)
+                        &quote(&options.whitespace_line_trim),
+                        &quote(&options.tag_comment.end),
+                        LINE_WHITESPACE,
+                        &quote(&options.tag_comment.end),
+                        block_newline
                     )
                 ).ok().expect("Failed to init lex_comment")
             },
             lex_block_raw: {
                 Regex::new(
                     &format!(
-                        r#"\A(?s)\s*(raw|verbatim)\s*(?:{}{}\s*|\s*{})"#,
+                        r#"\A(?s)\s*(raw|verbatim)\s*(?:{}{}\s*|{}{}{}*|{})"#,
                         &quote(&options.whitespace_trim),
                         &quote(&options.tag_block.end),
+                        &quote(&options.whitespace_line_trim),
+                        &quote(&options.tag_block.end),
+                        LINE_WHITESPACE,
                         &quote(&options.tag_block.end)
                     )
                 ).ok().expect("Failed to init lex_block_raw")
@@ -134,14 +166,41 @@ impl Matchers {
             lex_tokens_start: {
                 Regex::new(
                     &format!(
-                        r#"(?s)({}|{}|{})({})?"#,
+                        r#"(?s)({}|{}|{})({}|{})?"#,
                         &quote(&options.tag_variable.start),
                         &quote(&options.tag_block.start),
                         &quote(&options.tag_comment.start),
-                        &quote(&options.whitespace_trim)
+                        &quote(&options.whitespace_trim),
+                        &quote(&options.whitespace_line_trim)
                     )
                 ).ok().expect("Failed to init lex_tokens_start")
             },
+            lex_line_statement_start: options.line_statement_prefix.as_ref().map(|prefix| {
+                Regex::new(
+                    &format!(
+                        r#"(?m)^{}*({})"#,
+                        LINE_WHITESPACE,
+                        &quote(prefix)
+                    )
+                ).ok().expect("Failed to init lex_line_statement_start")
+            }),
+            lex_line_statement_end: {
+                Regex::new(
+                    &format!(
+                        r#"\A{}*(\r?\n)"#,
+                        LINE_WHITESPACE
+                    )
+                ).ok().expect("Failed to init lex_line_statement_end")
+            },
+            lex_line_comment_start: options.line_comment_prefix.as_ref().map(|prefix| {
+                Regex::new(
+                    &format!(
+                        r#"(?m)^{}*({})"#,
+                        LINE_WHITESPACE,
+                        &quote(prefix)
+                    )
+                ).ok().expect("Failed to init lex_line_comment_start")
+            }),
             interpolation_start: {
                 Regex::new(
                     &format!(
@@ -3,9 +3,9 @@ use environment::LexingEnvironment;
 use tokens::{ LexerOptions, TokenIter };
 use self::matchers::Matchers;
 
-mod delimiters;
 mod matchers;
 
+pub mod delimiters;
 pub mod options;
 pub mod iter;
 
@@ -18,20 +18,23 @@ pub struct Lexer {
 impl Lexer {
 
     /// Creates a new lexer with specified options and operator list.
+    ///
+    /// Matchers are built from the delimiters of the options, which should be
+    /// checked with `LexerOptions::validate`.
     pub fn new(options: LexerOptions, operators: &HashSet<&'static str>) -> Lexer {
         Lexer {
-            options: options,
             matchers: Matchers::new(
                 &options,
                 operators
             ),
+            options: options,
         }
     }
 
-    /// Initialize default lexer with default options.
+    /// Initialize lexer with options of the environment.
     pub fn default(env: &LexingEnvironment) -> Lexer {
         Lexer::new(
-            LexerOptions::default(),
+            env.options.clone(),
             &env.operators
         )
     }
@@ -1,13 +1,32 @@
+use error::{ Result, EngineError };
 use super::delimiters::Delimiters;
 
 /// Lexer options.
-#[derive(Copy, Clone)]
+#[derive(Clone, Debug)]
 pub struct LexerOptions {
     pub tag_comment: Delimiters,
     pub tag_block: Delimiters,
     pub tag_variable: Delimiters,
-    pub whitespace_trim: &'static str,
+    /// Modifier next to a delimiter that trims all whitespace on that side, `-`.
+    pub whitespace_trim: String,
+    /// Modifier next to a delimiter that trims spaces and tabs on that side,
+    /// but keeps newlines, `~`.
+    pub whitespace_line_trim: String,
+    /// Removes the first newline after a block tag or comment, as Twig does
+    /// by default.
+    pub trim_blocks: bool,
+    /// Strips spaces and tabs from the start of a line up to a block tag
+    /// or comment.
+    pub lstrip_blocks: bool,
     pub interpolation: Delimiters,
+    /// Lines starting with this prefix are lexed as block tags, i.e.
+    /// `%% block content` is the same as `{% block content %}`.
+    pub line_statement_prefix: Option<String>,
+    /// Lines starting with this prefix are comments.
+    pub line_comment_prefix: Option<String>,
+    /// Emits comments as `CommentStart` token followed by `Text` token with
+    /// comment contents, instead of skipping them.
+    pub comments: bool,
 }
 
 impl LexerOptions {
@@ -16,8 +35,78 @@ impl LexerOptions {
             tag_comment: Delimiters::new("{#", "#}"),
             tag_block: Delimiters::new("{%", "%}"),
             tag_variable: Delimiters::new("{{", "}}"),
-            whitespace_trim: "-",
+            whitespace_trim: "-".into(),
+            whitespace_line_trim: "~".into(),
+            trim_blocks: true,
+            lstrip_blocks: false,
             interpolation: Delimiters::new("#{", "}"),
+            line_statement_prefix: None,
+            line_comment_prefix: None,
+            comments: false,
         }
     }
+
+    /// Checks that delimiters are not empty, and that tag start delimiters
+    /// and whitespace modifiers can be told apart.
+    ///
+    /// Tag start can not be the same as, or a prefix of, another tag start
+    /// or line prefix, i.e. `[` and `[[` would both match at `[[`.
+    pub fn validate(&self) -> Result<()> {
+        let mut starts = vec![
+            ("tag_comment.start", &self.tag_comment.start),
+            ("tag_block.start", &self.tag_block.start),
+            ("tag_variable.start", &self.tag_variable.start),
+        ];
+        if let Some(ref prefix) = self.line_statement_prefix {
+            starts.push(("line_statement_prefix", prefix));
+        }
+        if let Some(ref prefix) = self.line_comment_prefix {
+            starts.push(("line_comment_prefix", prefix));
+        }
+
+        let mut delimiters = vec![
+            ("tag_comment.start", &self.tag_comment.start),
+            ("tag_comment.end", &self.tag_comment.end),
+            ("tag_block.start", &self.tag_block.start),
+            ("tag_block.end", &self.tag_block.end),
+            ("tag_variable.start", &self.tag_variable.start),
+            ("tag_variable.end", &self.tag_variable.end),
+            ("whitespace_trim", &self.whitespace_trim),
+            ("whitespace_line_trim", &self.whitespace_line_trim),
+            ("interpolation.start", &self.interpolation.start),
+            ("interpolation.end", &self.interpolation.end),
+        ];
+        delimiters.extend(starts[3 ..].iter().cloned());
+        for &(name, value) in &delimiters {
+            if value.trim().is_empty() {
+                return Err(EngineError::EmptyDelimiter { name: name.into() }.into());
+            }
+        }
+
+        try!(check_prefixes(&starts));
+        try!(check_prefixes(&[
+            ("whitespace_trim", &self.whitespace_trim),
+            ("whitespace_line_trim", &self.whitespace_line_trim),
+        ]));
+
+        Ok(())
+    }
+}
+
+/// Fails if any of delimiters is the same as, or a prefix of, another one.
+fn check_prefixes(delimiters: &[(&str, &String)]) -> Result<()> {
+    for (i, &(name, value)) in delimiters.iter().enumerate() {
+        for &(other, other_value) in &delimiters[i + 1 ..] {
+            if value.starts_with(&other_value[..]) || other_value.starts_with(&value[..]) {
+                return Err(EngineError::DelimiterConflict {
+                    name: name.into(),
+                    value: value.clone(),
+                    other: other.into(),
+                    other_value: other_value.clone(),
+                }.into());
+            }
+        }
+    }
+
+    Ok(())
 }
@@ -18,23 +18,23 @@ Hello
 And chopping it into tokens like these:
 
 ```text
-Ok(TokenRef { value: Text("Hello\n"), line: 1 })
-Ok(TokenRef { value: BlockStart, line: 2 })
-Ok(TokenRef { value: Name("if"), line: 2 })
-Ok(TokenRef { value: Name("world"), line: 2 })
-Ok(TokenRef { value: BlockEnd, line: 2 })
-Ok(TokenRef { value: Text("    world\n"), line: 3 })
-Ok(TokenRef { value: BlockStart, line: 4 })
-Ok(TokenRef { value: Name("else"), line: 4 })
-Ok(TokenRef { value: BlockEnd, line: 4 })
-Ok(TokenRef { value: Text("    "), line: 5 })
-Ok(TokenRef { value: VarStart, line: 5 })
-Ok(TokenRef { value: Name("other"), line: 5 })
-Ok(TokenRef { value: VarEnd, line: 5 })
-Ok(TokenRef { value: Text("\n"), line: 5 })
-Ok(TokenRef { value: BlockStart, line: 6 })
-Ok(TokenRef { value: Name("endif"), line: 6 })
-Ok(TokenRef { value: BlockEnd, line: 6 })
+Ok(TokenRef { value: Text("Hello\n"), line: 1, column: 1, span: Span { start: 0, end: 6 } })
+Ok(TokenRef { value: BlockStart, line: 2, column: 1, span: Span { start: 6, end: 8 } })
+Ok(TokenRef { value: Name("if"), line: 2, column: 4, span: Span { start: 9, end: 11 } })
+Ok(TokenRef { value: Name("world"), line: 2, column: 7, span: Span { start: 12, end: 17 } })
+Ok(TokenRef { value: BlockEnd, line: 2, column: 13, span: Span { start: 18, end: 20 } })
+Ok(TokenRef { value: Text("    world\n"), line: 3, column: 1, span: Span { start: 21, end: 31 } })
+Ok(TokenRef { value: BlockStart, line: 4, column: 1, span: Span { start: 31, end: 33 } })
+Ok(TokenRef { value: Name("else"), line: 4, column: 4, span: Span { start: 34, end: 38 } })
+Ok(TokenRef { value: BlockEnd, line: 4, column: 9, span: Span { start: 39, end: 41 } })
+Ok(TokenRef { value: Text("    "), line: 5, column: 1, span: Span { start: 42, end: 46 } })
+Ok(TokenRef { value: VarStart, line: 5, column: 5, span: Span { start: 46, end: 48 } })
+Ok(TokenRef { value: Name("other"), line: 5, column: 8, span: Span { start: 49, end: 54 } })
+Ok(TokenRef { value: VarEnd, line: 5, column: 14, span: Span { start: 55, end: 57 } })
+Ok(TokenRef { value: Text("\n"), line: 5, column: 16, span: Span { start: 57, end: 58 } })
+Ok(TokenRef { value: BlockStart, line: 6, column: 1, span: Span { start: 58, end: 60 } })
+Ok(TokenRef { value: Name("endif"), line: 6, column: 4, span: Span { start: 61, end: 66 } })
+Ok(TokenRef { value: BlockEnd, line: 6, column: 10, span: Span { start: 67, end: 69 } })
 ```
 
 Example code for this:
@@ -63,7 +63,7 @@ mod token;
 mod lexer;
 
 pub use self::token::{
-    TokenRef,
+    TokenRef, Span,
     TokenValue, TokenValueRef,
     Const, ConstRef,
     ConstNumber, ConstNumberRef,
@@ -71,3 +71,4 @@ pub use self::token::{
 pub use self::lexer::Lexer;
 pub use self::lexer::iter::TokenIter;
 pub use self::lexer::options::LexerOptions;
+pub use self::lexer::delimiters::Delimiters;
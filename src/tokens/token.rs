@@ -1,10 +1,41 @@
 use std::fmt;
+use error::Location;
 
 /// Lexer output token, lexer's output and parser's input.
 #[derive(Debug, Clone)]
 pub struct TokenRef<'a> {
     pub value: TokenValueRef<'a>,
     pub line: usize,
+    /// Column of the first token character, starting from 1.
+    pub column: usize,
+    /// Bytes of template source the token was lexed from.
+    pub span: Span,
+}
+
+impl<'a> TokenRef<'a> {
+    /// Returns token position for error reporting.
+    pub fn location(&self) -> Location {
+        Location::spanning(self.line, self.column, self.span)
+    }
+}
+
+/// Byte range `start..end` in template source.
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start: start, end: end }
+    }
+
+    /// Returns span from the start of this span to the end of other.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
 }
 
 /// Token value.
@@ -1,7 +1,6 @@
 use std::fmt;
 use std::cmp::Ordering;
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::sync::{ Arc, RwLock };
 use std::collections::HashMap;
 use error::{ RuntimeError, RuntimeResult, CastError, CastTarget };
 
@@ -28,15 +27,19 @@ impl fmt::Debug for HashKey {
 }
 
 /// Represents Twig runtime value.
+///
+/// Values are `Send + Sync`, so compiled templates and their constants can be
+/// shared between rendering threads.
 pub enum Value {
     Null,
+    Bool(bool),
     Int(i64),
     Float(f64),
     Str(String),
     Array(Vec<Value>),
     Hash(HashMap<HashKey, Value>),
-    Obj(Rc<RefCell<Object>>),
-    Func(Rc<for<'r> Fn(&'r [Value]) -> Option<Value> >),
+    Obj(Arc<RwLock<Object>>),
+    Func(Arc<for<'r> Fn(&'r [Value]) -> Option<Value> + Send + Sync>),
 }
 
 impl<'a> From<HashMap<&'a str, &'a str>> for Value {
@@ -61,10 +64,41 @@ impl From<HashMap<String, String>> for Value {
     }
 }
 
+impl<'a> From<&'a str> for Value {
+    fn from(value: &'a str) -> Value {
+        Value::Str(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::Str(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Value {
+        Value::Int(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Bool(value)
+    }
+}
+
 impl Clone for Value {
     fn clone(&self) -> Value {
         match *self {
             Value::Null => Value::Null,
+            Value::Bool(v) => Value::Bool(v),
             Value::Int(ref v) => Value::Int(v.clone()),
             Value::Float(ref v) => Value::Float(v.clone()),
             Value::Str(ref v) => Value::Str(v.clone()),
@@ -80,6 +114,7 @@ impl PartialEq for Value {
     fn eq(&self, other: &Value) -> bool {
         match (self, other) {
             (&Value::Null, &Value::Null) => true,
+            (&Value::Bool(ref a), &Value::Bool(ref b)) => a.eq(b),
             (&Value::Int(ref a), &Value::Int(ref b)) => a.eq(b),
             (&Value::Float(ref a), &Value::Float(ref b)) => a.eq(b),
             (&Value::Str(ref a), &Value::Str(ref b)) => a.eq(b),
@@ -97,6 +132,7 @@ impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
         match (self, other) {
             (&Value::Null, &Value::Null) => Some(Ordering::Equal),
+            (&Value::Bool(ref a), &Value::Bool(ref b)) => a.partial_cmp(b),
             (&Value::Int(ref a), &Value::Int(ref b)) => a.partial_cmp(b),
             (&Value::Float(ref a), &Value::Float(ref b)) => a.partial_cmp(b),
             (&Value::Str(ref a), &Value::Str(ref b)) => a.partial_cmp(b),
@@ -112,6 +148,7 @@ impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Value::Null => write!(f, "null"),
+            Value::Bool(ref v) => write!(f, "{}", v),
             Value::Int(ref v) => write!(f, "{}", v),
             Value::Float(ref v) => write!(f, "{}", v),
             Value::Str(ref v) => write!(f, "{:?}", ops::to_string_limited(v)),
@@ -145,6 +182,23 @@ impl fmt::Debug for Value {
 }
 
 impl Value {
+    /// Returns true if this value passes as true in conditions.
+    ///
+    /// Follows PHP rules: null, false, zero, empty string, "0" and empty
+    /// arrays are false.
+    pub fn is_truthy(&self) -> bool {
+        match *self {
+            Value::Null => false,
+            Value::Bool(v) => v,
+            Value::Int(v) => v != 0,
+            Value::Float(v) => v != 0.0,
+            Value::Str(ref v) => !(v.is_empty() || v == "0"),
+            Value::Array(ref v) => !v.is_empty(),
+            Value::Hash(ref v) => !v.is_empty(),
+            Value::Obj(_) | Value::Func(_) => true,
+        }
+    }
+
     /// If possible, returns this value represented as integer.
     pub fn int(self) -> RuntimeResult<i64> {
         Ok(match self {
@@ -152,6 +206,7 @@ impl Value {
                 target: CastTarget::Int,
                 reason: CastError::Null,
             }),
+            Value::Bool(v) => v as i64,
             Value::Int(v) => v,
             Value::Float(v) => return ops::float_to_int(v),
             Value::Str(v) => {
@@ -182,7 +237,7 @@ impl Value {
 }
 
 /// Twig object abstraction.
-pub trait Object {
+pub trait Object: Send + Sync {
     fn property_error(&self, name: &str) -> RuntimeError {
         RuntimeError::ObjectHasNoProperty(name.into())
     }
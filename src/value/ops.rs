@@ -146,7 +146,7 @@ pub fn to_string_limited(v: &str) -> String {
 }
 
 pub fn double_fits_long(v: f64) -> bool {
-    v > i64::MAX as f64 || v < i64::MIN as f64
+    v < i64::MAX as f64 && v >= i64::MIN as f64
 }
 
 #[cfg(test)]
@@ -238,6 +238,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn converts_floats_in_range_to_int() {
+        assert_eq!(float_to_int(2.7).unwrap(), 2);
+        assert_eq!(float_to_int(-2.7).unwrap(), -2);
+        assert_eq!(
+            &format!("{}", float_to_int(1e20).unwrap_err()),
+            &format!("{}", RuntimeError::ImpossibleCast {
+                target: CastTarget::Int,
+                reason: CastError::FloatRange(1e20),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_floats_at_upper_int_boundary() {
+        assert!(!double_fits_long(9223372036854775808.0));
+        assert!(double_fits_long(-9223372036854775808.0));
+        assert!(float_to_int(9223372036854775808.0).is_err());
+        assert_eq!(float_to_int(-9223372036854775808.0).unwrap(), i64::MIN);
+    }
+
     fn assert_floats_equal(a: f64, b: f64) {
         if a.is_nan() && b.is_nan() {
             return;
@@ -0,0 +1,47 @@
+use super::support;
+
+#[test]
+fn renders_cached_template_more_than_once() {
+    let twig = support::engine(vec![
+        ("index.twig", "hello"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+}
+
+#[test]
+fn recompiles_changed_template() {
+    let mut twig = support::engine(vec![
+        ("index.twig", "hello"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+    twig.loader_mut().set_template("index.twig", "world");
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "world");
+}
+
+#[test]
+fn recompiles_template_when_included_template_changes() {
+    let mut twig = support::engine(vec![
+        ("index.twig", r#"[{% include "row.twig" %}]"#),
+        ("row.twig", "a"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "[a]");
+    twig.loader_mut().set_template("row.twig", "b");
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "[b]");
+    twig.loader_mut().set_template("index.twig", r#"({% include "row.twig" %})"#);
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "(b)");
+}
+
+#[test]
+fn recompiles_templates_after_clearing_cache() {
+    let twig = support::engine(vec![
+        ("index.twig", "hello"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+    twig.clear_cache();
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+}
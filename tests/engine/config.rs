@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use twig::environment::{ Autoescape, Config, Environment, OPTIMIZE_NONE };
+use twig::error::{ Error, RuntimeError };
+use twig::loader::ArrayLoader;
+use twig::Engine;
+use super::support;
+
+fn engine(config: Config, templates: Vec<(&'static str, &'static str)>) -> Engine<ArrayLoader> {
+    Engine::new(support::loader(templates), Environment::new(config))
+}
+
+fn data(items: Vec<(&'static str, &'static str)>) -> HashMap<String, String> {
+    items.into_iter()
+        .map(|(k, v)| (k.into(), v.into()))
+        .collect()
+}
+
+#[test]
+fn builder_keeps_defaults_for_options_not_set() {
+    let config = Config::builder()
+        .debug(true)
+        .charset("ISO-8859-1")
+        .build();
+
+    assert!(config.debug);
+    assert_eq!(config.charset, "ISO-8859-1");
+    assert!(!config.strict_variables);
+    assert!(config.auto_reload);
+    assert!(config.cache.is_none());
+}
+
+#[test]
+fn escapes_printed_values_as_html_by_default() {
+    let twig = support::engine(vec![
+        ("index.twig", "<b>{{ name }}</b>"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", data(vec![("name", "<i>&</i>")])).unwrap(), "<b>&lt;i&gt;&amp;&lt;/i&gt;</b>");
+}
+
+#[test]
+fn does_not_autoescape_raw_and_escape_filter_output() {
+    let twig = support::engine(vec![
+        ("index.twig", "{{ b|raw }} {{ b|escape }} {{ b|e('url') }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", data(vec![("b", "<b>")])).unwrap(), "<b> &lt;b&gt; %3Cb%3E");
+}
+
+#[test]
+fn escapes_included_output_once_in_concatenation() {
+    let twig = support::engine(vec![
+        ("index.twig", "{{ '<p>' ~ include('b.twig') }}|{{ b ~ include('b.twig') }}"),
+        ("b.twig", "{{ b }}"),
+    ]);
+
+    assert_eq!(
+        twig.get("index.twig", data(vec![("b", "<b>")])).unwrap(),
+        "<p>&lt;b&gt;|&lt;b&gt;&amp;lt;b&amp;gt;"
+    );
+}
+
+#[test]
+fn does_not_escape_when_autoescape_is_disabled() {
+    let twig = engine(Config::builder().autoescape(Autoescape::Disabled).build(), vec![
+        ("index.twig", "{{ name }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", data(vec![("name", "<i>")])).unwrap(), "<i>");
+}
+
+#[test]
+fn escapes_with_configured_strategy() {
+    let twig = engine(Config::builder().autoescape(Autoescape::Strategy("url".into())).build(), vec![
+        ("index.twig", "?q={{ q }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", data(vec![("q", "a b&c")])).unwrap(), "?q=a%20b%26c");
+}
+
+#[test]
+fn picks_escaping_strategy_with_callback() {
+    let config = Config::builder()
+        .autoescape_callback(|name| if name.starts_with("js/") { Some("js".into()) } else { None })
+        .build();
+    let twig = engine(config, vec![
+        ("index.twig", "{{ v }}|{% include 'js/x.twig' %}"),
+        ("js/x.twig", "{{ v }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", data(vec![("v", "'")])).unwrap(), "'|\\x27");
+}
+
+#[test]
+fn fails_on_unknown_escaping_strategy() {
+    let twig = engine(Config::builder().autoescape(Autoescape::Strategy("nope".into())).build(), vec![
+        ("index.twig", "{{ v }}"),
+    ]);
+
+    match twig.get("index.twig", support::empty_data()) {
+        Err(Error::Engine(_)) => (),
+        other => panic!("expected engine error, got {:?}", other),
+    }
+}
+
+#[test]
+fn dump_is_available_only_in_debug_mode() {
+    let template = vec![("index.twig", "{{ dump(v) }}")];
+
+    let twig = engine(Config::builder().debug(true).autoescape(Autoescape::Disabled).build(), template.clone());
+    assert_eq!(twig.get("index.twig", data(vec![("v", "a")])).unwrap(), "\"a\"");
+
+    let twig = engine(Config::default(), template);
+    assert!(twig.get("index.twig", data(vec![("v", "a")])).is_err());
+}
+
+#[test]
+fn fails_on_undefined_variable_in_strict_mode() {
+    let template = vec![("index.twig", "[{{ missing }}]")];
+
+    let twig = engine(Config::default(), template.clone());
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "[]");
+
+    let twig = engine(Config::builder().strict_variables(true).build(), template);
+    match twig.get("index.twig", support::empty_data()) {
+        Err(Error::Runtime(ref e)) => match e.message {
+            RuntimeError::UndefinedVariable { ref name, .. } => assert_eq!(name, "missing"),
+            ref other => panic!("expected undefined variable, got {:?}", other),
+        },
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn keeps_compiled_template_without_auto_reload() {
+    let mut twig = engine(Config::builder().auto_reload(false).build(), vec![
+        ("index.twig", "hello"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+    twig.loader_mut().set_template("index.twig", "world");
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+}
+
+#[test]
+fn renders_same_output_without_optimizations() {
+    let template = vec![("index.twig", "a{{ v ? 'b' : '' }}c{{ v }}d")];
+
+    let optimized = engine(Config::default(), template.clone());
+    let plain = engine(Config::builder().optimizations(OPTIMIZE_NONE).build(), template);
+
+    for v in vec!["", "x"] {
+        assert_eq!(
+            optimized.get("index.twig", data(vec![("v", v)])).unwrap(),
+            plain.get("index.twig", data(vec![("v", v)])).unwrap()
+        );
+    }
+}
+
+#[test]
+fn encodes_output_in_latin1_charset() {
+    let twig = engine(Config::builder().charset("ISO-8859-1").build(), vec![
+        ("index.twig", "\u{e9}\u{20ac}"),
+    ]);
+
+    let mut output = Vec::new();
+    twig.render_to("index.twig", support::empty_data(), &mut output).unwrap();
+    assert_eq!(output, vec![0xe9, b'?']);
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "\u{e9}\u{20ac}");
+}
+
+#[test]
+fn fails_on_unsupported_charset() {
+    let twig = engine(Config::builder().charset("KOI8-R").build(), vec![
+        ("index.twig", "a"),
+    ]);
+
+    assert!(twig.render_to("index.twig", support::empty_data(), &mut Vec::new()).is_err());
+}
+
+#[test]
+fn reads_options_from_string_map() {
+    let config = Config::from_hashmap(vec![
+        ("debug", "true"),
+        ("strict_variables", "1"),
+        ("auto_reload", "false"),
+        ("autoescape", "false"),
+        ("optimizations", "0"),
+    ].into_iter().map(|(k, v)| (k.into(), v.into())).collect());
+
+    assert!(config.debug);
+    assert!(config.strict_variables);
+    assert!(!config.auto_reload);
+    assert_eq!(config.optimizations, OPTIMIZE_NONE);
+    match config.autoescape {
+        Autoescape::Disabled => (),
+        ref other => panic!("expected disabled autoescape, got {:?}", other),
+    }
+}
@@ -0,0 +1,139 @@
+use little::Instruction;
+use twig::environment::Environment;
+use twig::extension::Extension;
+use twig::function::Function;
+use twig::nodes::{ Parser, TokenParser, TokenParserExtension };
+use twig::nodes::body::{ Body, ExtensionNode };
+use twig::nodes::body_parser::{ subparse, BlockEnd };
+use twig::nodes::expr::Expr;
+use twig::nodes::expr_parser::parse_expression;
+use twig::instructions::{ Compile, call, compile_operand };
+use twig::tokens::{ TokenRef, TokenValueRef };
+use twig::value::Value;
+use twig::error::TemplateResult;
+use twig::mold::Staging;
+use twig::Engine;
+use super::support;
+
+/// Node that outputs its body twice.
+#[derive(Debug)]
+struct Twice<'c> {
+    body: Body<'c>,
+}
+
+impl<'c> Compile<'c> for Twice<'c> {
+    fn compile<'r>(&'r self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<()> {
+        try!(self.body.compile(stage));
+        self.body.compile(stage)
+    }
+}
+
+impl<'c> ExtensionNode<'c> for Twice<'c> {
+    fn into_owned(self: Box<Self>) -> Box<ExtensionNode<'static>> {
+        Box::new(Twice { body: self.body.into_owned() })
+    }
+}
+
+struct TwiceParser;
+
+impl TokenParserExtension for TwiceParser {
+    fn get_tag(&self) -> &'static str {
+        "twice"
+    }
+
+    fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
+        -> TemplateResult<Option<Body<'c>>>
+    {
+        try!(parser.expect(TokenValueRef::BlockEnd));
+        let body = try!(subparse(parser, |token| match token.value {
+            TokenValueRef::Name("endtwice") => Some(BlockEnd { drop_needle: true }),
+            _ => None,
+        }));
+        try!(parser.expect(TokenValueRef::BlockEnd));
+
+        Ok(Some(Body::Extension { node: Box::new(Twice { body: body }), line: token.line, column: token.column, span: token.span }))
+    }
+}
+
+/// Node that outputs its expression through the "shout" runtime call.
+#[derive(Debug)]
+struct Shout<'c> {
+    expr: Expr<'c>,
+}
+
+impl<'c> Compile<'c> for Shout<'c> {
+    fn compile<'r>(&'r self, stage: &'r mut Staging<'c, Value>) -> TemplateResult<()> {
+        let value = try!(compile_operand(&self.expr, stage));
+        let shouted = call(stage, "Shout", "shout", vec![value]);
+        stage.instr(Instruction::Output { location: shouted.result().expect("call has result") });
+        shouted.finalize(stage)
+    }
+}
+
+impl<'c> ExtensionNode<'c> for Shout<'c> {
+    fn into_owned(self: Box<Self>) -> Box<ExtensionNode<'static>> {
+        Box::new(Shout { expr: self.expr.into_owned() })
+    }
+}
+
+struct ShoutParser;
+
+impl TokenParserExtension for ShoutParser {
+    fn get_tag(&self) -> &'static str {
+        "shout"
+    }
+
+    fn parse<'p, 'c>(&self, parser: &mut Parser<'p, 'c>, token: TokenRef<'c>)
+        -> TemplateResult<Option<Body<'c>>>
+    {
+        let expr = try!(parse_expression(parser, 0));
+        try!(parser.expect(TokenValueRef::BlockEnd));
+
+        Ok(Some(Body::Extension { node: Box::new(Shout { expr: expr }), line: token.line, column: token.column, span: token.span }))
+    }
+}
+
+struct Tags;
+
+impl Extension for Tags {
+    fn name(&self) -> &'static str {
+        "tags"
+    }
+
+    fn token_parsers(&self) -> Vec<TokenParser> {
+        vec![TokenParser::new(TwiceParser), TokenParser::new(ShoutParser)]
+    }
+
+    fn functions(&self) -> Vec<Function> {
+        vec![
+            Function::new_dynamic("shout", |args| Ok(Value::Str(format!("{}!", args[0].to_string().to_uppercase())))),
+        ]
+    }
+}
+
+fn engine(template: &'static str) -> Engine<::twig::loader::ArrayLoader> {
+    let mut env = Environment::default();
+    env.add_extension(Box::new(Tags));
+    Engine::new(support::loader(vec![("index.twig", template)]), env)
+}
+
+#[test]
+fn compiles_extension_node_body() {
+    let twig = engine("[{% twice %}a{{ 'b' }}{% endtwice %}]");
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "[abab]");
+}
+
+#[test]
+fn compiles_extension_node_expression() {
+    let twig = engine("{% shout 'hey' ~ ' you' %}");
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "HEY YOU!");
+}
+
+#[test]
+fn nests_extension_nodes() {
+    let twig = engine("{% twice %}{% shout 'a' %}{% endtwice %}");
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "A!A!");
+}
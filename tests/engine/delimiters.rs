@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use twig::environment::{ Autoescape, Config, ConfigBuilder, Environment };
+use twig::error::{ Error, EngineError, ErrorKind };
+use twig::format::format;
+use super::support;
+
+fn builder() -> ConfigBuilder {
+    Config::builder()
+        .autoescape(Autoescape::Disabled)
+        .tag_variable("[[", "]]")
+        .tag_block("[%", "%]")
+        .tag_comment("[#", "#]")
+}
+
+fn init_error(config: Config) -> Error {
+    match Environment::new(config).try_init_all() {
+        Ok(_) => panic!("expected invalid delimiters"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn renders_with_custom_delimiters() {
+    let twig = ::twig::Engine::new(
+        support::loader(vec![
+            ("index.twig", "[# hi #]{{ vue }} [% block a %][[ {a: [x]}.a[0] ~ '!' ]][% endblock %]"),
+        ]),
+        Environment::new(builder().build())
+    );
+
+    let mut data = HashMap::new();
+    data.insert("x", "a");
+
+    assert_eq!(twig.get("index.twig", data).unwrap(), "{{ vue }} a!");
+}
+
+#[test]
+fn evaluates_expressions_with_custom_delimiters() {
+    let twig = ::twig::Engine::new(
+        support::loader(vec![]),
+        Environment::new(builder().build())
+    );
+
+    let mut data = HashMap::new();
+    data.insert("x", "2");
+
+    assert_eq!(twig.eval_expression("x ~ 3", data).unwrap().to_string(), "23");
+    assert_eq!(twig.eval_expression("'{{ x }}'", support::empty_data()).unwrap().to_string(), "{{ x }}");
+}
+
+#[test]
+fn reads_delimiters_from_hashmap() {
+    let mut map = HashMap::new();
+    map.insert("tag_variable".to_string(), "<< >>".to_string());
+    map.insert("tag_block".to_string(), "<% %>".to_string());
+
+    let config = Config::from_hashmap(map);
+
+    assert_eq!(config.lexer.tag_variable.start, "<<");
+    assert_eq!(config.lexer.tag_block.end, "%>");
+    assert_eq!(config.lexer.tag_comment.start, "{#");
+}
+
+#[test]
+fn rejects_conflicting_delimiters() {
+    let error = init_error(builder().tag_block("[[%", "%]]").build());
+
+    assert_eq!(error.kind(), ErrorKind::Config);
+    assert_eq!(
+        error.to_string(),
+        r#"Delimiter "tag_block.start" ("[[%") is prefixed by delimiter "tag_variable.start" ("[[")"#
+    );
+
+    match init_error(builder().tag_comment("[[", "]]").build()) {
+        Error::Engine(ref e) => match e.err {
+            EngineError::DelimiterConflict { ref name, ref other, .. } => {
+                assert_eq!((&name[..], &other[..]), ("tag_comment.start", "tag_variable.start"));
+            },
+            ref other => panic!("expected delimiter conflict, got {:?}", other),
+        },
+        other => panic!("expected engine error, got {:?}", other),
+    }
+}
+
+#[test]
+fn rejects_empty_delimiters() {
+    let error = init_error(builder().tag_variable("", "]]").build());
+
+    assert_eq!(error.to_string(), r#"Delimiter "tag_variable.start" is empty"#);
+}
+
+#[test]
+fn formats_with_custom_delimiters() {
+    let env = Environment::new(builder().build()).init_all();
+
+    assert_eq!(
+        format(&env, "[#a#][%block a%][[b|default(1)]][%endblock%]").unwrap(),
+        "[# a #][% block a %][[ b|default(1) ]][% endblock %]\n"
+    );
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use twig::environment::{ Config, Environment };
+use twig::error::{ Error, Style };
+use twig::Engine;
+use super::support;
+
+fn error(template: &'static str, strict_variables: bool) -> Error {
+    let config = Config::builder().strict_variables(strict_variables).build();
+    let twig = Engine::new(support::loader(vec![("index.twig", template)]), Environment::new(config));
+    let mut data = HashMap::new();
+    data.insert("name".to_string(), "Ann".to_string());
+    match twig.get("index.twig", data) {
+        Err(e) => e,
+        Ok(output) => panic!("expected error, got {:?}", output),
+    }
+}
+
+#[test]
+fn renders_syntax_error_with_source_snippet() {
+    let source = "a\nb\nc\n{{ x|nope }}\nd";
+    let rendered = error(source, false).diagnostic("index.twig").unwrap().render(source, Style::Plain);
+
+    assert_eq!(rendered, [
+        "error: Unknown \"nope\" filter",
+        " --> index.twig:4:6",
+        "  |",
+        "2 | b",
+        "3 | c",
+        "4 | {{ x|nope }}",
+        "  |      ^^^^",
+        "",
+    ].join("\n"));
+}
+
+#[test]
+fn renders_runtime_error_with_suggestion() {
+    let source = "Hello, {{ nmae }}!";
+    let rendered = error(source, true).diagnostic("index.twig").unwrap().render(source, Style::Plain);
+
+    assert_eq!(rendered, [
+        "error: Variable \"nmae\" does not exist in \"index.twig\" at line 1, available keys: \"name\"",
+        " --> index.twig:1:11",
+        "  |",
+        "1 | Hello, {{ nmae }}!",
+        "  |           ^^^^",
+        "  = help: did you mean \"name\"?",
+        "",
+    ].join("\n"));
+}
+
+#[test]
+fn renders_notes_and_multiline_spans() {
+    let source = "{% block a %}\n{{ name ~\n  missing }}\n{% endblock %}";
+    let rendered = error(source, true).diagnostic("index.twig").unwrap()
+        .with_note("strict variables are enabled")
+        .render(source, Style::Plain);
+
+    assert_eq!(rendered, [
+        "error: Variable \"missing\" does not exist in \"index.twig\" at line 3, available keys: \"name\"",
+        " --> index.twig:3:3",
+        "  |",
+        "1 | {% block a %}",
+        "2 | {{ name ~",
+        "3 |   missing }}",
+        "  |   ^^^^^^^",
+        "  = note: strict variables are enabled",
+        "",
+    ].join("\n"));
+}
+
+#[test]
+fn renders_line_without_carets_when_column_is_unknown() {
+    let source = "a\n{{ 'b }}";
+    let rendered = error(source, false).diagnostic("index.twig").unwrap().render(source, Style::Plain);
+
+    assert_eq!(rendered, [
+        "error: Unexpected character \"'\"",
+        " --> index.twig:2",
+        "  |",
+        "1 | a",
+        "2 | {{ 'b }}",
+        "",
+    ].join("\n"));
+}
+
+#[test]
+fn renders_ansi_colors() {
+    let source = "{{ x|nope }}";
+    let rendered = error(source, false).diagnostic("index.twig").unwrap().render(source, Style::Ansi);
+
+    assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m: \x1b[1mUnknown \"nope\" filter\x1b[0m\n"));
+    assert!(rendered.contains("\x1b[1;34m|\x1b[0m      \x1b[1;31m^^^^\x1b[0m\n"));
+}
+
+#[test]
+fn points_runtime_error_to_included_template() {
+    let twig = support::engine(vec![
+        ("index.twig", "a\n{% include 'part.twig' %}"),
+        ("part.twig", "\n{{ 1 / 0 }}"),
+    ]);
+    let diagnostic = twig.get("index.twig", support::empty_data()).unwrap_err()
+        .diagnostic("index.twig").unwrap();
+
+    assert_eq!(diagnostic.template, "part.twig");
+    assert_eq!(diagnostic.render("\n{{ 1 / 0 }}", Style::Plain), [
+        "error: Division by zero",
+        " --> part.twig:2:6",
+        "  |",
+        "1 | ",
+        "2 | {{ 1 / 0 }}",
+        "  |    ^^^^^",
+        "",
+    ].join("\n"));
+}
@@ -0,0 +1,92 @@
+use std::env;
+use std::fs;
+use std::path::{ Path, PathBuf };
+use twig::environment::{ Config, Environment };
+use twig::loader::ArrayLoader;
+use twig::Engine;
+use super::support;
+
+fn cache_dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("twig-disk-cache-{}-{}", test, ::std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn engine(dir: &Path, templates: Vec<(&'static str, &'static str)>) -> Engine<ArrayLoader> {
+    let mut config = Config::default();
+    config.cache = Some(dir.to_path_buf());
+    Engine::new(support::loader(templates), Environment::new(config))
+}
+
+fn cached_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+#[test]
+fn stores_compiled_template_in_cache_dir() {
+    let dir = cache_dir("stores");
+    let twig = engine(&dir, vec![("index.twig", "hello")]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "hello");
+    assert_eq!(cached_files(&dir).len(), 1);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reuses_entry_for_same_source() {
+    let dir = cache_dir("reuses");
+
+    let first = engine(&dir, vec![("a.twig", "hello")]);
+    assert_eq!(first.get("a.twig", support::empty_data()).unwrap(), "hello");
+
+    let second = engine(&dir, vec![("b.twig", "hello"), ("c.twig", "world")]);
+    assert_eq!(second.get("b.twig", support::empty_data()).unwrap(), "hello");
+    assert_eq!(cached_files(&dir).len(), 1);
+
+    assert_eq!(second.get("c.twig", support::empty_data()).unwrap(), "world");
+    assert_eq!(cached_files(&dir).len(), 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn recompiles_invalid_entry() {
+    let dir = cache_dir("invalid");
+
+    let first = engine(&dir, vec![("index.twig", "hello")]);
+    assert_eq!(first.get("index.twig", support::empty_data()).unwrap(), "hello");
+
+    let files = cached_files(&dir);
+    fs::write(&files[0], b"garbage").unwrap();
+
+    let second = engine(&dir, vec![("index.twig", "hello")]);
+    assert_eq!(second.get("index.twig", support::empty_data()).unwrap(), "hello");
+    assert!(fs::read(&files[0]).unwrap() != b"garbage");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fails_if_cache_dir_is_not_writable() {
+    let dir = cache_dir("unwritable");
+    fs::write(&dir, b"not a directory").unwrap();
+
+    let twig = engine(&dir, vec![("index.twig", "hello")]);
+    let err = twig.get("index.twig", support::empty_data()).unwrap_err();
+    assert!(err.to_string().starts_with("Failed to write compiled template"), "{}", err);
+
+    fs::remove_file(&dir).unwrap();
+}
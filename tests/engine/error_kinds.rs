@@ -0,0 +1,42 @@
+use std::error::Error as StdError;
+use std::path::PathBuf;
+use twig::error::{ Error, ErrorKind, EngineError };
+use super::support;
+
+fn kind_of(template: &'static str) -> ErrorKind {
+    let twig = support::engine(vec![("index.twig", template)]);
+    twig.get("index.twig", support::empty_data()).unwrap_err().kind()
+}
+
+#[test]
+fn tells_error_kinds_apart() {
+    let twig = support::engine(vec![]);
+
+    assert_eq!(twig.get("missing.twig", support::empty_data()).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(kind_of("{{ a + }}"), ErrorKind::Syntax);
+    assert_eq!(kind_of("{% block a %}{% endblock b %}"), ErrorKind::Syntax);
+    assert_eq!(kind_of("{{ missing() }}"), ErrorKind::Syntax);
+    assert_eq!(kind_of("{{ 1 / 0 }}"), ErrorKind::Runtime);
+    assert_eq!(kind_of("{{ 'abc' * 2 }}"), ErrorKind::Cast);
+}
+
+#[test]
+fn source_follows_cause() {
+    let error: Error = EngineError::CacheWriteFailed { path: PathBuf::from("cache"), reason: "disk full".into() }
+        .caused_by(EngineError::OutputFailed { reason: "closed".into() })
+        .into();
+
+    let source = error.source().expect("error has source");
+    assert_eq!(source.to_string(), "Failed to write template output: closed");
+    assert!(source.source().is_none());
+}
+
+#[test]
+fn converts_to_boxed_std_error() {
+    fn render() -> Result<String, Box<StdError>> {
+        let twig = support::engine(vec![("index.twig", "{{ 1 / 0 }}")]);
+        Ok(try!(twig.get("index.twig", support::empty_data())))
+    }
+
+    assert!(render().unwrap_err().to_string().starts_with("Division by zero"));
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use twig::value::{ Value, HashKey };
+use super::support;
+
+fn user(age: i64, roles: Vec<&str>) -> Value {
+    let mut user = HashMap::new();
+    user.insert(HashKey::Str("age".into()), Value::Int(age));
+    user.insert(HashKey::Str("roles".into()), Value::Array(
+        roles.into_iter().map(|r| Value::Str(r.into())).collect()
+    ));
+
+    let mut data = HashMap::new();
+    data.insert(HashKey::Str("user".into()), Value::Hash(user));
+    Value::Hash(data)
+}
+
+#[test]
+fn evaluates_expression_against_context() {
+    let twig = support::engine(vec![]);
+    let rule = "user.age >= 18 and 'admin' in user.roles";
+
+    assert_eq!(twig.eval_expression(rule, user(30, vec!["user", "admin"])).unwrap(), Value::Bool(true));
+    assert_eq!(twig.eval_expression(rule, user(16, vec!["admin"])).unwrap(), Value::Bool(false));
+    assert_eq!(twig.eval_expression(rule, user(30, vec!["user"])).unwrap(), Value::Bool(false));
+}
+
+#[test]
+fn evaluates_compiled_expression_many_times() {
+    let twig = support::engine(vec![]);
+    let expression = twig.compile_expression("user.roles[0] ~ ':' ~ (user.age + 1)").unwrap();
+
+    assert_eq!(expression.source(), "user.roles[0] ~ ':' ~ (user.age + 1)");
+    assert_eq!(expression.evaluate(user(1, vec!["a"])).unwrap(), Value::Str("a:2".into()));
+    assert_eq!(expression.evaluate(user(2, vec!["b"])).unwrap(), Value::Str("b:3".into()));
+}
+
+#[test]
+fn evaluates_collections_and_conditionals() {
+    let twig = support::engine(vec![]);
+
+    assert_eq!(
+        twig.eval_expression("[1, 2 * 3, missing ?: 'none']", support::empty_data()).unwrap(),
+        Value::Array(vec![Value::Int(1), Value::Int(6), Value::Str("none".into())])
+    );
+    assert_eq!(
+        twig.eval_expression("{ 'a': 1 }.a > 0 ? 'yes' : 'no'", support::empty_data()).unwrap(),
+        Value::Str("yes".into())
+    );
+    assert_eq!(twig.eval_expression("not missing or missing.name", support::empty_data()).unwrap(), Value::Bool(true));
+}
+
+#[test]
+fn uses_expression_operators_in_templates() {
+    let twig = support::engine(vec![
+        ("index.twig", "{{ user.age > 18 ? 'adult' : 'minor' }} {{ 7 // 2 }} {{ 'x' ~ (1 + 1) }} {{ 3 in 1..3 }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", user(20, vec![])).unwrap(), "adult 3 x2 1");
+}
+
+#[test]
+fn fails_on_invalid_expression() {
+    let twig = support::engine(vec![]);
+
+    assert!(twig.compile_expression("1 +").is_err());
+    assert!(twig.compile_expression("1 2").is_err());
+    assert_eq!(
+        twig.eval_expression("1 / 0", support::empty_data()).unwrap_err().to_string(),
+        "Division by zero"
+    );
+}
@@ -0,0 +1,151 @@
+use twig::environment::Environment;
+use twig::extension::Extension;
+use twig::function::{ Function, Filter, Test };
+use twig::global::Global;
+use twig::operator::Operator;
+use twig::value::Value;
+use twig::error::{ Error, EngineError };
+use twig::Engine;
+use super::support;
+
+/// Extension with configuration, like a router with a base path.
+struct Router {
+    base: String,
+}
+
+impl Extension for Router {
+    fn name(&self) -> &'static str {
+        "router"
+    }
+
+    fn functions(&self) -> Vec<Function> {
+        let base = self.base.clone();
+        vec![
+            Function::new_dynamic("path", move |args| Ok(Value::Str(format!("{}/{}", base, args[0])))),
+        ]
+    }
+
+    fn filters(&self) -> Vec<Filter> {
+        vec![
+            Filter::new_dynamic("slug", |args| Ok(Value::Str(args[0].to_string().to_lowercase().replace(" ", "-")))),
+        ]
+    }
+
+    fn tests(&self) -> Vec<Test> {
+        vec![
+            Test::new_dynamic("absolute", |args| Ok(args[0].to_string().starts_with("/"))),
+        ]
+    }
+
+    fn operators(&self) -> Vec<Operator> {
+        vec![
+            Operator::new_binary_left("/?", 30, |left, right| Ok(Value::Str(format!("{}?{}", left, right)))),
+        ]
+    }
+
+    fn globals(&self) -> Vec<(String, Global)> {
+        vec![
+            ("base".into(), Global::Value(Value::Str(self.base.clone()))),
+        ]
+    }
+}
+
+/// Extension that overrides the router global, so it has to be initialized later.
+struct Admin;
+
+impl Extension for Admin {
+    fn name(&self) -> &'static str {
+        "admin"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["router"]
+    }
+
+    fn globals(&self) -> Vec<(String, Global)> {
+        vec![
+            ("base".into(), Global::Value(Value::Str("/admin".into()))),
+        ]
+    }
+}
+
+struct Cyclic(&'static str, &'static str);
+
+impl Extension for Cyclic {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![self.1]
+    }
+}
+
+fn engine(env: Environment, template: &'static str) -> Engine<::twig::loader::ArrayLoader> {
+    Engine::new(support::loader(vec![("index.twig", template)]), env)
+}
+
+#[test]
+fn extension_items_are_available_in_templates() {
+    let mut env = Environment::default();
+    env.add_extension(Box::new(Router { base: "/app".into() }));
+
+    let twig = engine(env, "{{ path('Home Page'|slug) }}|{{ base is absolute ? 'yes' : 'no' }}|{{ 'a' /? 'b' }}");
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "/app/home-page|yes|a?b");
+}
+
+#[test]
+fn dependencies_are_initialized_first() {
+    let mut env = Environment::default();
+    env.add_extension(Box::new(Admin));
+    env.add_extension(Box::new(Router { base: "/app".into() }));
+
+    let twig = engine(env, "{{ base }}");
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "/admin");
+}
+
+#[test]
+fn fails_on_missing_dependency() {
+    let mut env = Environment::default();
+    env.add_extension(Box::new(Admin));
+
+    match env.try_init_all() {
+        Err(Error::Engine(ref e)) => match e.err {
+            EngineError::ExtensionDependencyMissing { ref extension, ref dependency } => {
+                assert_eq!(extension, "admin");
+                assert_eq!(dependency, "router");
+            },
+            ref other => panic!("expected missing dependency, got {:?}", other),
+        },
+        Err(other) => panic!("expected engine error, got {:?}", other),
+        Ok(_) => panic!("expected missing dependency"),
+    }
+}
+
+#[test]
+fn fails_on_dependency_cycle() {
+    let mut env = Environment::default();
+    env.add_extension(Box::new(Cyclic("a", "b")));
+    env.add_extension(Box::new(Cyclic("b", "a")));
+
+    match env.try_init_all() {
+        Err(Error::Engine(ref e)) => match e.err {
+            EngineError::ExtensionDependencyCycle { .. } => (),
+            ref other => panic!("expected dependency cycle, got {:?}", other),
+        },
+        Err(other) => panic!("expected engine error, got {:?}", other),
+        Ok(_) => panic!("expected dependency cycle"),
+    }
+}
+
+#[test]
+fn unknown_filter_is_a_template_error() {
+    let twig = engine(Environment::default(), "{{ 'a'|slug }}");
+
+    match twig.get("index.twig", support::empty_data()) {
+        Err(Error::Template(ref e)) => assert_eq!(e.to_string().contains("Unknown \"slug\" filter"), true),
+        other => panic!("expected template error, got {:?}", other),
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use twig::environment::{ Config, Environment };
+use twig::loader::ArrayLoader;
+use twig::value::Value;
+use twig::Engine;
+use super::support;
+
+fn engine(env: Environment, templates: Vec<(&'static str, &'static str)>) -> Engine<ArrayLoader> {
+    Engine::new(support::loader(templates), env)
+}
+
+#[test]
+fn globals_are_visible_in_templates_and_includes() {
+    let mut env = Environment::default();
+    env.add_global("site", "example.com");
+
+    let twig = engine(env, vec![
+        ("index.twig", "{{ site }}|{% include 'row.twig' %}|{{ include('row.twig') }}"),
+        ("row.twig", "{{ site }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "example.com|example.com|example.com");
+}
+
+#[test]
+fn context_shadows_globals() {
+    let mut env = Environment::default();
+    env.add_global("site", "example.com");
+
+    let twig = engine(env, vec![
+        ("index.twig", "{{ site }}"),
+    ]);
+
+    let mut data = HashMap::new();
+    data.insert("site".to_string(), "other.com".to_string());
+    assert_eq!(twig.get("index.twig", data).unwrap(), "other.com");
+}
+
+#[test]
+fn lazy_global_is_evaluated_once_per_render() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let mut env = Environment::default();
+    env.add_lazy_global("user", move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Value::Str("ann".into())
+    });
+
+    let twig = engine(env, vec![
+        ("index.twig", "{{ user }}{{ user }}{% include 'row.twig' %}"),
+        ("row.twig", "{{ user }}"),
+        ("unused.twig", "{{ user is defined ? 'yes' : 'no' }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "annannann");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "annannann");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(twig.get("unused.twig", support::empty_data()).unwrap(), "yes");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn globals_are_defined_in_strict_mode() {
+    let mut env = Environment::new(Config::builder().strict_variables(true).build());
+    env.add_global("site", "example.com");
+
+    let twig = engine(env, vec![
+        ("index.twig", "{{ site }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "example.com");
+}
+
+#[test]
+fn globals_are_visible_in_expressions() {
+    let mut env = Environment::default();
+    env.add_global("limit", 10);
+
+    let twig = engine(env, vec![]);
+
+    assert_eq!(twig.eval_expression("limit * 2", support::empty_data()).unwrap(), Value::Int(20));
+}
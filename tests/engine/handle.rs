@@ -0,0 +1,73 @@
+use super::support;
+
+fn engine() -> ::twig::Engine<::twig::loader::ArrayLoader> {
+    support::engine(vec![
+        ("pages/email.twig", "<{% block subject %}Hello{% endblock %}|{% block body %}[{% include './row.twig' %}]{% endblock %}>"),
+        ("pages/row.twig", "row"),
+    ])
+}
+
+#[test]
+fn renders_loaded_template_many_times() {
+    let twig = engine();
+    let template = twig.load("pages//email.twig").unwrap();
+
+    assert_eq!(template.source_name(), "pages/email.twig");
+    assert_eq!(template.render(support::empty_data()).unwrap(), "<Hello|[row]>");
+    assert_eq!(template.render(support::empty_data()).unwrap(), "<Hello|[row]>");
+}
+
+#[test]
+fn renders_single_block() {
+    let twig = engine();
+    let template = twig.load("pages/email.twig").unwrap();
+
+    assert_eq!(template.render_block("subject", support::empty_data()).unwrap(), "Hello");
+    assert_eq!(template.render_block("body", support::empty_data()).unwrap(), "[row]");
+}
+
+#[test]
+fn lists_blocks() {
+    let twig = engine();
+    let template = twig.load("pages/email.twig").unwrap();
+
+    assert!(template.has_block("subject"));
+    assert!(!template.has_block("footer"));
+    assert_eq!(template.block_names(), vec!["subject", "body"]);
+}
+
+#[test]
+fn fails_to_render_missing_block() {
+    let twig = engine();
+    let template = twig.load("pages/email.twig").unwrap();
+
+    assert_eq!(
+        template.render_block("footer", support::empty_data()).unwrap_err().to_string(),
+        r#"Block "footer" is not defined in template "pages/email.twig""#
+    );
+}
+
+#[test]
+fn renders_blocks_of_extended_templates() {
+    let twig = support::engine(vec![
+        ("base.twig", "{% block a %}A{% block b %}B{% endblock %}{% endblock %}"),
+        ("pages/c.twig", "{% extends '../base.twig' %}{% block b %}C{% endblock %}{% block d %}D{% endblock %}"),
+    ]);
+    let template = twig.load("pages/c.twig").unwrap();
+
+    let mut names = template.block_names();
+    names.sort();
+
+    assert!(template.has_block("a"));
+    assert_eq!(names, vec!["a", "b", "d"]);
+    assert_eq!(template.render_block("a", support::empty_data()).unwrap(), "AC");
+    assert_eq!(template.render_block("b", support::empty_data()).unwrap(), "C");
+    assert_eq!(template.render_block("d", support::empty_data()).unwrap(), "D");
+}
+
+#[test]
+fn fails_to_load_missing_template() {
+    let twig = engine();
+
+    assert!(twig.load("missing.twig").is_err());
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use twig::environment::{ Autoescape, Config, ConfigBuilder, Environment };
+use twig::error::{ Error, EngineError };
+use twig::format::format;
+use super::support;
+
+fn builder() -> ConfigBuilder {
+    Config::builder()
+        .autoescape(Autoescape::Disabled)
+        .line_statement_prefix("%%")
+        .line_comment_prefix("##")
+}
+
+fn render(config: Config, template: &'static str) -> String {
+    let twig = ::twig::Engine::new(
+        support::loader(vec![("index.twig", template)]),
+        Environment::new(config)
+    );
+
+    let mut data = HashMap::new();
+    data.insert("name", "a");
+
+    twig.get("index.twig", data).unwrap()
+}
+
+#[test]
+fn renders_line_statements_and_comments() {
+    let template = "## generated file\nname = {{ name }}\n  %% block options\noption = [\n  %% block values\n    1,\n  %% endblock\n]\n%% endblock";
+
+    assert_eq!(render(builder().build(), template), "name = a\noption = [\n    1,\n]\n");
+}
+
+#[test]
+fn keeps_prefixes_that_do_not_start_a_line() {
+    assert_eq!(
+        render(builder().build(), "a %% b ## c\n{{ '%%' ~ name }}"),
+        "a %% b ## c\n%%a"
+    );
+    assert_eq!(render(Config::builder().build(), "%% b\n## c"), "%% b\n## c");
+}
+
+#[test]
+fn reads_line_prefixes_from_hashmap() {
+    let mut map = HashMap::new();
+    map.insert("line_statement_prefix".to_string(), "#".to_string());
+    map.insert("line_comment_prefix".to_string(), "//".to_string());
+
+    let config = Config::from_hashmap(map);
+
+    assert_eq!(config.lexer.line_statement_prefix, Some("#".to_string()));
+    assert_eq!(render(config, "// note\n# block a\n{{ name }}\n# endblock\n"), "a\n");
+}
+
+#[test]
+fn rejects_line_prefix_conflicting_with_tags() {
+    match Environment::new(builder().line_comment_prefix("{").build()).try_init_all() {
+        Err(Error::Engine(ref e)) => match e.err {
+            EngineError::DelimiterConflict { ref name, ref other, .. } => {
+                assert_eq!((&name[..], &other[..]), ("tag_comment.start", "line_comment_prefix"));
+            },
+            ref other => panic!("expected delimiter conflict, got {:?}", other),
+        },
+        Err(other) => panic!("expected engine error, got {:?}", other),
+        Ok(_) => panic!("expected conflicting line prefix"),
+    }
+}
+
+#[test]
+fn formats_line_statements_as_tags() {
+    let env = Environment::new(builder().build()).init_all();
+
+    assert_eq!(
+        format(&env, "## note\n%% block a\nb\n%% endblock\n").unwrap(),
+        "{# note #}\n{% block a %}\nb\n{% endblock %}\n"
+    );
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use super::support;
+
+#[test]
+fn calls_macro_of_the_same_template() {
+    let twig = support::engine(vec![
+        ("index.twig", r#"{% import _self as m %}{% macro hi(name) %}Hi {{ name }}{% endmacro %}{{ m.hi("<b>") }}"#),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "Hi &lt;b&gt;");
+}
+
+#[test]
+fn passes_only_arguments_to_macro() {
+    let twig = support::engine(vec![
+        ("index.twig", r#"{% from "m.twig" import args %}{{ args(1, 2, 3) }}|{{ args() }}"#),
+        ("m.twig", r#"{% macro args(a, b = "x") %}{{ a }},{{ b }},{{ varargs[0] }}{{ x }}{% endmacro %}"#),
+    ]);
+    let mut data = HashMap::new();
+    data.insert("x", "!");
+
+    assert_eq!(twig.get("index.twig", data).unwrap(), "1,2,3|,x,");
+}
+
+#[test]
+fn fails_on_missing_macro() {
+    let twig = support::engine(vec![
+        ("index.twig", r#"{% from "m.twig" import missing %}{{ missing() }}"#),
+        ("m.twig", ""),
+    ]);
+
+    assert_eq!(
+        twig.get("index.twig", support::empty_data()).unwrap_err().to_string(),
+        r#"Macro "missing" is not defined in template "m.twig""#
+    );
+}
+
+#[test]
+fn fails_on_missing_imported_template() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"{% import "./m.twig" as m %}"#),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/index.twig", support::empty_data()).unwrap_err().to_string(),
+        r#"Template "pages/m.twig" was not found"#
+    );
+}
@@ -0,0 +1,23 @@
+mod support;
+
+mod relative_names;
+mod cache;
+mod disk_cache;
+mod render_to;
+mod threads;
+mod handle;
+mod strings;
+mod expressions;
+mod config;
+mod strict_variables;
+mod globals;
+mod extensions;
+mod custom_nodes;
+mod node_visitors;
+mod diagnostics;
+mod stack_traces;
+mod macros;
+mod error_kinds;
+mod delimiters;
+mod whitespace;
+mod line_statements;
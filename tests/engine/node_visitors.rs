@@ -0,0 +1,208 @@
+use std::sync::Mutex;
+use twig::environment::Environment;
+use twig::extension::Extension;
+use twig::nodes::NodeVisitor;
+use twig::nodes::body::Body;
+use twig::tokens::Span;
+use twig::nodes::expr::{ Expr, ExprValue, ExprConstant };
+use twig::error::{ Error, TemplateError, TemplateResult };
+use twig::Engine;
+use super::support;
+
+/// Replaces text "from" with text "to".
+struct ReplaceText {
+    from: &'static str,
+    to: &'static str,
+    priority: i32,
+}
+
+impl NodeVisitor for ReplaceText {
+    fn name(&self) -> &'static str {
+        "replace_text"
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn enter_body<'c>(&self, body: &mut Body<'c>) -> TemplateResult<()> {
+        if let Body::Text { ref mut value, .. } = *body {
+            if *value == self.from {
+                *value = self.to.into();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces "secret" variable with a constant.
+struct HideSecret;
+
+impl NodeVisitor for HideSecret {
+    fn name(&self) -> &'static str {
+        "hide_secret"
+    }
+
+    fn enter_expr<'c>(&self, expr: &mut Expr<'c>) -> TemplateResult<()> {
+        let secret = match expr.value {
+            ExprValue::Name(ref name) => name == "secret",
+            _ => false,
+        };
+        if secret {
+            expr.value = ExprValue::Constant(ExprConstant::Str("***".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Rejects calls to the "dump" function, like a sandbox would.
+struct NoDump;
+
+impl NodeVisitor for NoDump {
+    fn name(&self) -> &'static str {
+        "no_dump"
+    }
+
+    fn leave_expr<'c>(&self, expr: &mut Expr<'c>) -> TemplateResult<()> {
+        match expr.value {
+            ExprValue::FunctionCall { ref name, .. } if name == "dump" => Err(TemplateError::Unexpected("dump".into()).at(expr.line)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Records the order in which nodes are entered and left.
+struct Recorder {
+    log: Mutex<Vec<String>>,
+}
+
+impl NodeVisitor for Recorder {
+    fn name(&self) -> &'static str {
+        "recorder"
+    }
+
+    fn enter_body<'c>(&self, body: &mut Body<'c>) -> TemplateResult<()> {
+        if let Body::Print { .. } = *body {
+            self.log.lock().unwrap().push("enter print".into());
+        }
+        Ok(())
+    }
+
+    fn leave_body<'c>(&self, body: &mut Body<'c>) -> TemplateResult<()> {
+        if let Body::Print { .. } = *body {
+            self.log.lock().unwrap().push("leave print".into());
+        }
+        Ok(())
+    }
+
+    fn enter_expr<'c>(&self, expr: &mut Expr<'c>) -> TemplateResult<()> {
+        self.log.lock().unwrap().push(format!("enter {:?}", expr.value));
+        Ok(())
+    }
+}
+
+struct Visitors(Vec<(&'static str, &'static str, i32)>);
+
+impl Extension for Visitors {
+    fn name(&self) -> &'static str {
+        "visitors"
+    }
+
+    fn node_visitors(&self) -> Vec<Box<NodeVisitor>> {
+        let mut visitors: Vec<Box<NodeVisitor>> = vec![Box::new(HideSecret), Box::new(NoDump)];
+        for &(from, to, priority) in &self.0 {
+            visitors.push(Box::new(ReplaceText { from: from, to: to, priority: priority }));
+        }
+        visitors
+    }
+}
+
+/// Registers only one of two visitors with the same priority.
+struct OneVisitor(bool);
+
+impl Extension for OneVisitor {
+    fn name(&self) -> &'static str {
+        "one_visitor"
+    }
+
+    fn node_visitors(&self) -> Vec<Box<NodeVisitor>> {
+        match self.0 {
+            true => vec![Box::new(HideSecret)],
+            false => vec![Box::new(NoDump)],
+        }
+    }
+}
+
+fn engine(replacements: Vec<(&'static str, &'static str, i32)>, template: &'static str) -> Engine<::twig::loader::ArrayLoader> {
+    let mut env = Environment::default();
+    env.add_extension(Box::new(Visitors(replacements)));
+    Engine::new(support::loader(vec![("index.twig", template)]), env)
+}
+
+#[test]
+fn rewrites_expressions() {
+    let twig = engine(vec![], "{{ secret }}-{{ [secret ~ '!'][0] }}");
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "***-***!");
+}
+
+#[test]
+fn rewrites_nodes_in_blocks() {
+    let twig = engine(vec![("a", "b", 0)], "a{% block x %}a{% endblock %}");
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "bb");
+}
+
+#[test]
+fn runs_visitors_in_priority_order() {
+    let template = "a";
+
+    let twig = engine(vec![("b", "c", 10), ("a", "b", -10)], template);
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "c");
+
+    let twig = engine(vec![("b", "c", -10), ("a", "b", 10)], template);
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "b");
+}
+
+#[test]
+fn fails_when_visitor_rejects_node() {
+    let twig = engine(vec![], "\n{{ dump(1) }}");
+
+    match twig.get("index.twig", support::empty_data()) {
+        Err(Error::Template(ref e)) => assert_eq!(e.loc.line, 2),
+        other => panic!("expected template error, got {:?}", other),
+    }
+}
+
+#[test]
+fn enters_nodes_before_sub_nodes() {
+    let recorder = Recorder { log: Mutex::new(Vec::new()) };
+    let mut expr = Expr::new_at(ExprValue::Concat {
+        left: Box::new(Expr::new_name("a", 1)),
+        right: Box::new(Expr::new_int_constant(1, 1)),
+    }, 1);
+    let mut body = Body::Print { expr: Box::new(expr.clone()), line: 1, column: 0, span: Span::default() };
+
+    ::twig::nodes::traverse_body(&recorder, &mut body).unwrap();
+    ::twig::nodes::traverse_expr(&recorder, &mut expr).unwrap();
+
+    let log = recorder.log.lock().unwrap();
+    assert_eq!(log[0], "enter print");
+    assert!(log[1].starts_with("enter Concat"));
+    assert_eq!(log[2], "enter Name(\"a\")");
+    assert_eq!(log[3], "enter Constant(Int(1))");
+    assert_eq!(log[4], "leave print");
+    assert_eq!(log.len(), 8);
+}
+
+#[test]
+fn visitor_names_are_part_of_fingerprint() {
+    let fingerprint = |hide_secret| {
+        let mut env = Environment::default();
+        env.add_extension(Box::new(OneVisitor(hide_secret)));
+        env.init_all().fingerprint
+    };
+
+    assert!(fingerprint(true) != fingerprint(false));
+    assert_eq!(fingerprint(true), fingerprint(true));
+}
@@ -0,0 +1,132 @@
+use super::support;
+
+#[test]
+fn includes_template_relative_to_current() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"[{% include "./partials/row.twig" %}]"#),
+        ("pages/partials/row.twig", r#"row {% include "../../footer.twig" %}"#),
+        ("footer.twig", "footer"),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/index.twig", support::empty_data()).unwrap(),
+        "[row footer]"
+    );
+}
+
+#[test]
+fn includes_absolute_name_from_root() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"{% include "footer.twig" %}"#),
+        ("footer.twig", "footer"),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/index.twig", support::empty_data()).unwrap(),
+        "footer"
+    );
+}
+
+#[test]
+fn fails_to_include_template_outside_of_root() {
+    let twig = support::engine(vec![
+        ("index.twig", r#"{% include "../footer.twig" %}"#),
+    ]);
+
+    let err = twig.get("index.twig", support::empty_data()).unwrap_err();
+    assert_eq!(
+        format!("{}", err),
+        r#"Template "../footer.twig" referenced from "index.twig" points outside of the template root"#
+    );
+}
+
+#[test]
+fn ignores_missing_relative_template() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"a{% include "./missing.twig" ignore missing %}b"#),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/index.twig", support::empty_data()).unwrap(),
+        "ab"
+    );
+}
+
+#[test]
+fn reports_missing_template_by_resolved_name() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"{% include "./missing.twig" %}"#),
+    ]);
+
+    let err = twig.get("pages/index.twig", support::empty_data()).unwrap_err();
+    assert_eq!(
+        format!("{}", err),
+        r#"Template "pages/missing.twig" was not found"#
+    );
+}
+
+#[test]
+fn extends_template_relative_to_current() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"{% extends "../base.twig" %}ignored{% block title %}Index{% endblock %}"#),
+        ("base.twig", r#"<h1>{% block title %}Base{% endblock %}</h1>{% include "./footer.twig" %}"#),
+        ("footer.twig", "footer"),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/index.twig", support::empty_data()).unwrap(),
+        "<h1>Index</h1>footer"
+    );
+}
+
+#[test]
+fn uses_blocks_of_the_most_derived_template() {
+    let twig = support::engine(vec![
+        ("pages/a.twig", r#"{% extends "./b.twig" %}{% block x %}A{% endblock %}"#),
+        ("pages/b.twig", r#"{% extends "../c.twig" %}{% block x %}B{% endblock %}{% block y %}B{% include "./row.twig" %}{% endblock %}"#),
+        ("pages/row.twig", "row"),
+        ("c.twig", "{% block x %}C{% endblock %}{% block y %}C{% endblock %}{% block z %}C{% endblock %}"),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/a.twig", support::empty_data()).unwrap(),
+        "ABrowC"
+    );
+}
+
+#[test]
+fn fails_when_template_extends_itself() {
+    let twig = support::engine(vec![
+        ("a.twig", r#"{% extends "./b.twig" %}"#),
+        ("b.twig", r#"{% extends "a.twig" %}"#),
+    ]);
+
+    let err = twig.get("a.twig", support::empty_data()).unwrap_err();
+    assert_eq!(format!("{}", err), r#"Template "a.twig" extends itself"#);
+}
+
+#[test]
+fn imports_template_relative_to_current() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"{% import "../macros/forms.twig" as forms %}{{ forms.input("q") }}"#),
+        ("macros/forms.twig", r#"{% macro input(name, type = "text") %}<input name="{{ name }}" type="{{ type }}">{% endmacro %}"#),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/index.twig", support::empty_data()).unwrap(),
+        r#"<input name="q" type="text">"#
+    );
+}
+
+#[test]
+fn imports_macros_relative_to_current() {
+    let twig = support::engine(vec![
+        ("pages/index.twig", r#"{% from "./forms.twig" import input as field %}{% block body %}[{{ field("q", "search") }}]{% endblock %}"#),
+        ("pages/forms.twig", r#"{% macro input(name, type) %}{{ name }}:{{ type }}{% endmacro %}"#),
+    ]);
+
+    assert_eq!(
+        twig.get("pages/index.twig", support::empty_data()).unwrap(),
+        "[q:search]"
+    );
+}
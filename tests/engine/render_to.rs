@@ -0,0 +1,109 @@
+use std::io::{ self, Write };
+use super::support;
+
+/// Writer that remembers what was written before each flush.
+struct Recorder {
+    written: Vec<u8>,
+    flushed: Vec<String>,
+}
+
+impl Recorder {
+    fn new() -> Recorder {
+        Recorder { written: Vec::new(), flushed: Vec::new() }
+    }
+}
+
+impl Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushed.push(String::from_utf8_lossy(&self.written).into_owned());
+        Ok(())
+    }
+}
+
+struct Broken;
+
+impl Write for Broken {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writes_output_to_writer() {
+    let twig = support::engine(vec![
+        ("index.twig", r#"a{% include "row.twig" %}c"#),
+        ("row.twig", "b"),
+    ]);
+
+    let mut output = Vec::new();
+    twig.render_to("index.twig", support::empty_data(), &mut output).unwrap();
+    assert_eq!(output, b"abc");
+}
+
+#[test]
+fn flush_tag_flushes_writer() {
+    let twig = support::engine(vec![
+        ("index.twig", "header{% flush %}body{% flush %}{% flush %}footer"),
+    ]);
+
+    let mut output = Recorder::new();
+    twig.render_to("index.twig", support::empty_data(), &mut output).unwrap();
+    assert_eq!(output.written, b"headerbodyfooter");
+    assert_eq!(output.flushed, vec!["header", "headerbody", "headerbody", "headerbodyfooter"]);
+}
+
+#[test]
+fn flush_in_included_template_does_not_change_output() {
+    let twig = support::engine(vec![
+        ("index.twig", r#"a{% include "row.twig" %}c"#),
+        ("row.twig", "b{% flush %}b"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "abbc");
+}
+
+#[test]
+fn flush_in_included_template_flushes_writer() {
+    let twig = support::engine(vec![
+        ("a", "x{% include 'b' %}y"),
+        ("b", "1{% flush %}2"),
+    ]);
+
+    let mut output = Recorder::new();
+    twig.render_to("a", support::empty_data(), &mut output).unwrap();
+    assert_eq!(output.written, b"x12y");
+    assert_eq!(output.flushed, vec!["x1", "x12y"]);
+}
+
+#[test]
+fn flush_in_printed_macro_and_include_function_flushes_writer() {
+    let twig = support::engine(vec![
+        ("a", "{% import 'm' as m %}x{{ m.b() }}{{ include('c') }}{{ include('c')|raw }}"),
+        ("m", "{% macro b() %}1{% flush %}2{% endmacro %}"),
+        ("c", "c{% flush %}"),
+    ]);
+
+    let mut output = Recorder::new();
+    twig.render_to("a", support::empty_data(), &mut output).unwrap();
+    assert_eq!(output.written, b"x12cc");
+    assert_eq!(output.flushed, vec!["x1", "x12c", "x12cc"]);
+}
+
+#[test]
+fn reports_write_errors() {
+    let twig = support::engine(vec![
+        ("index.twig", "hello"),
+    ]);
+
+    let err = twig.render_to("index.twig", support::empty_data(), &mut Broken).unwrap_err();
+    assert_eq!(err.to_string(), "Failed to write template output: disk full");
+}
@@ -0,0 +1,130 @@
+use twig::environment::{ Config, Environment };
+use twig::error::{ Error, TraceEntry };
+use twig::loader::ArrayLoader;
+use twig::Engine;
+use super::support;
+
+fn engine(templates: Vec<(&'static str, &'static str)>) -> Engine<ArrayLoader> {
+    let config = Config::builder().strict_variables(true).build();
+    Engine::new(support::loader(templates), Environment::new(config))
+}
+
+fn describe(trace: &[TraceEntry]) -> Vec<String> {
+    trace.iter()
+        .map(|entry| match *entry {
+            TraceEntry::SourceFile { ref target } => format!("source {}", target),
+            TraceEntry::Block { ref name } => format!("block {}", name),
+            TraceEntry::Macro { ref name } => format!("macro {}", name),
+            TraceEntry::Operator { ref target, .. } => format!("operator {}", target),
+            TraceEntry::Position { from } => format!("at {}:{}", from.line, from.column.unwrap_or(0)),
+        })
+        .collect()
+}
+
+#[test]
+fn traces_includes_and_blocks() {
+    let twig = engine(vec![
+        ("page.twig", "<h1>\n  {% include 'part.twig' %}"),
+        ("part.twig", "{% block content %}\n{{ missing }}{% endblock %}"),
+    ]);
+
+    match twig.get("page.twig", support::empty_data()) {
+        Err(Error::Runtime(e)) => {
+            assert_eq!(describe(&e.stack_trace), vec![
+                "source page.twig",
+                "at 2:3",
+                "source part.twig",
+                "at 1:1",
+                "block content",
+                "at 2:4",
+            ]);
+            assert_eq!(
+                e.to_string(),
+                "Variable \"missing\" does not exist in \"part.twig\" at line 2\n\
+                 stack trace:\n   \
+                 0: part.twig:2:4 in block \"content\"\n   \
+                 1: part.twig:1:1\n   \
+                 2: page.twig:2:3"
+            );
+        },
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn traces_overridden_blocks_to_extending_template() {
+    let twig = engine(vec![
+        ("b.twig", "{% block a %}{% endblock %}"),
+        ("c.twig", "{% extends 'b.twig' %}{% block a %}\n{{ 1 // 0 }}{% endblock %}"),
+    ]);
+
+    match twig.get("c.twig", support::empty_data()) {
+        Err(Error::Runtime(e)) => {
+            assert_eq!(describe(&e.stack_trace), vec![
+                "source c.twig",
+                "at 1:12",
+                "source b.twig",
+                "at 1:1",
+                "source c.twig",
+                "block a",
+                "at 2:6",
+            ]);
+            assert!(e.to_string().starts_with("Division by zero"), "{}", e);
+            assert!(e.to_string().contains("0: c.twig:2:6 in block \"a\""), "{}", e);
+        },
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn traces_operators_in_included_templates_and_macros() {
+    let twig = engine(vec![
+        ("page.twig", "{% import 'forms.twig' as forms %}\n{% include 'part.twig' %}{{ forms.input() }}"),
+        ("part.twig", "\n  {{ 1 / 0 }}"),
+        ("forms.twig", "{% macro input() %}\n{{ 2 % 0 }}{% endmacro %}"),
+    ]);
+
+    match twig.get("page.twig", support::empty_data()) {
+        Err(Error::Runtime(e)) => assert_eq!(describe(&e.stack_trace), vec![
+            "source page.twig",
+            "at 2:1",
+            "source part.twig",
+            "at 2:8",
+        ]),
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+
+    match twig.render_str("{% import 'forms.twig' as forms %}{{ forms.input() }}", support::empty_data()) {
+        Err(Error::Runtime(e)) => {
+            let trace = describe(&e.stack_trace);
+            assert_eq!(&trace[trace.len() - 3..], &["source forms.twig", "macro input", "at 2:6"]);
+        },
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn traces_blocks_rendered_from_handle() {
+    let twig = engine(vec![
+        ("index.twig", "{% block title %}{{ 1 / 0 }}{% endblock %}"),
+    ]);
+    let template = twig.load("index.twig").unwrap();
+
+    match template.render_block("title", support::empty_data()) {
+        Err(Error::Runtime(e)) => assert_eq!(
+            describe(&e.stack_trace),
+            vec!["source index.twig", "block title", "at 1:23"]
+        ),
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn error_without_trace_displays_message_only() {
+    let twig = engine(vec![]);
+
+    assert_eq!(
+        twig.eval_expression("1 / 0", support::empty_data()).unwrap_err().to_string(),
+        "Division by zero"
+    );
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use twig::environment::{ Config, Environment };
+use twig::error::{ Error, RuntimeError, TraceEntry };
+use twig::loader::ArrayLoader;
+use twig::Engine;
+use super::support;
+
+fn engine(strict_variables: bool, templates: Vec<(&'static str, &'static str)>) -> Engine<ArrayLoader> {
+    let config = Config::builder().strict_variables(strict_variables).build();
+    Engine::new(support::loader(templates), Environment::new(config))
+}
+
+fn data() -> HashMap<String, String> {
+    let mut data = HashMap::new();
+    data.insert("name".into(), "Ann".into());
+    data.insert("empty".into(), "".into());
+    data
+}
+
+fn runtime_error(result: Result<String, Error>) -> RuntimeError {
+    match result {
+        Err(Error::Runtime(e)) => e.message,
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn reports_undefined_variable_with_template_line_and_keys() {
+    let twig = engine(true, vec![
+        ("index.twig", "a\n{{ name }}{{ missing }}"),
+    ]);
+
+    match runtime_error(twig.get("index.twig", data())) {
+        RuntimeError::UndefinedVariable { ref name, ref template, line, ref available } => {
+            assert_eq!(name, "missing");
+            assert_eq!(template, "index.twig");
+            assert_eq!(line, 2);
+            assert_eq!(available, &vec!["empty".to_string(), "name".to_string()]);
+        },
+        other => panic!("expected undefined variable, got {:?}", other),
+    }
+}
+
+#[test]
+fn reports_undefined_attribute_with_template_line_and_keys() {
+    let twig = engine(true, vec![
+        ("index.twig", "{{ {a: 1, b: 2}.c }}"),
+    ]);
+
+    let error = runtime_error(twig.get("index.twig", data()));
+    assert_eq!(
+        error.to_string(),
+        r#"Attribute "c" does not exist in "index.twig" at line 1, available keys: "a", "b""#
+    );
+}
+
+#[test]
+fn traces_position_of_undefined_value() {
+    let twig = engine(true, vec![
+        ("index.twig", "{{ name }}\n  {{ name ~ user.email }}"),
+    ]);
+
+    match twig.get("index.twig", data()) {
+        Err(Error::Runtime(e)) => match e.stack_trace[..] {
+            [TraceEntry::SourceFile { ref target }, TraceEntry::Position { from }] => {
+                assert_eq!(target, "index.twig");
+                assert_eq!((from.line, from.column), (2, Some(13)));
+                assert_eq!(from.span.map(|s| (s.start, s.end)), Some((23, 27)));
+            },
+            ref other => panic!("expected position trace, got {:?}", other),
+        },
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn renders_null_for_undefined_values_without_strict_mode() {
+    let twig = engine(false, vec![
+        ("index.twig", "[{{ missing }}|{{ missing.a.b }}|{{ name.a }}]"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", data()).unwrap(), "[||]");
+}
+
+#[test]
+fn defined_test_does_not_fail_in_either_mode() {
+    for strict in vec![false, true] {
+        let twig = engine(strict, vec![
+            ("index.twig", "{{ name is defined ? 1 : 0 }}{{ missing is defined ? 1 : 0 }}{{ missing.a is defined ? 1 : 0 }}{{ {a: null}.a is defined ? 1 : 0 }}{{ missing is not defined ? 1 : 0 }}"),
+        ]);
+
+        assert_eq!(twig.get("index.twig", data()).unwrap(), "10011");
+    }
+}
+
+#[test]
+fn default_filter_does_not_fail_in_either_mode() {
+    for strict in vec![false, true] {
+        let twig = engine(strict, vec![
+            ("index.twig", "{{ missing|default('x') }}|{{ missing.a.b|default('y') }}|{{ empty|default('z') }}|{{ name|default('n') }}|{{ missing|default }}"),
+        ]);
+
+        assert_eq!(twig.get("index.twig", data()).unwrap(), "x|y|z|Ann|");
+    }
+}
+
+#[test]
+fn short_circuit_guards_undefined_variable() {
+    let twig = engine(true, vec![
+        ("index.twig", "{{ missing is defined and missing.a ? 1 : 0 }}"),
+    ]);
+
+    assert_eq!(twig.get("index.twig", data()).unwrap(), "0");
+}
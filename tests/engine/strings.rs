@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use super::support;
+
+#[test]
+fn renders_template_from_string() {
+    let twig = support::engine(vec![]);
+
+    assert_eq!(twig.render_str("hello", support::empty_data()).unwrap(), "hello");
+    assert_eq!(twig.render_str("world", support::empty_data()).unwrap(), "world");
+}
+
+#[test]
+fn template_from_string_can_include_loader_templates() {
+    let twig = support::engine(vec![
+        ("row.twig", "row"),
+    ]);
+
+    assert_eq!(
+        twig.render_str(r#"[{% include "row.twig" %}]"#, support::empty_data()).unwrap(),
+        "[row]"
+    );
+}
+
+#[test]
+fn includes_template_from_string() {
+    let twig = support::engine(vec![
+        ("index.twig", r#"[{% include template_from_string("inline") %}]"#),
+    ]);
+
+    assert_eq!(twig.get("index.twig", support::empty_data()).unwrap(), "[inline]");
+}
+
+#[test]
+fn template_from_string_returns_synthetic_name() {
+    let twig = support::engine(vec![]);
+
+    let name = twig.render_str(r#"{{ template_from_string("a") }}"#, support::empty_data()).unwrap();
+    assert!(name.starts_with("__string_template__"), "{}", name);
+    assert_eq!(twig.get(&name, support::empty_data()).unwrap(), "a");
+}
+
+#[test]
+fn nested_function_calls_pass_results() {
+    let twig = support::engine(vec![]);
+
+    assert_eq!(
+        twig.render_str(r#"{{ include(template_from_string("nested")) }}"#, support::empty_data()).unwrap(),
+        "nested"
+    );
+}
+
+#[test]
+fn include_function_passes_current_context() {
+    let twig = support::engine(vec![]);
+    let mut data = HashMap::new();
+    data.insert("x", "a");
+
+    assert_eq!(
+        twig.render_str(r#"{{ include(template_from_string("hi {{ x }}{{ y }}"), {y: "!"}) }}"#, data.clone()).unwrap(),
+        "hi a!"
+    );
+    assert_eq!(
+        twig.render_str(r#"{{ include(template_from_string("hi {{ x }}{{ y }}"), {y: "!"}, false) }}"#, data).unwrap(),
+        "hi !"
+    );
+}
+
+#[test]
+fn fails_on_unknown_function() {
+    let twig = support::engine(vec![
+        ("index.twig", "{{ missing() }}"),
+    ]);
+
+    assert_eq!(
+        twig.get("index.twig", support::empty_data()).unwrap_err().to_string(),
+        r#"Unknown function "missing" called in template "index.twig""#
+    );
+}
+
+#[test]
+fn template_from_string_reports_syntax_errors() {
+    let twig = support::engine(vec![
+        ("print.twig", r#"{{ template_from_string("{% block %}") }}"#),
+    ]);
+
+    let error = twig.get("print.twig", support::empty_data()).unwrap_err();
+    assert_eq!(error.kind(), ::twig::error::ErrorKind::Syntax, "{}", error);
+}
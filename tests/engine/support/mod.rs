@@ -0,0 +1,22 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use twig::environment::Environment;
+use twig::loader::ArrayLoader;
+use twig::Engine;
+
+pub fn engine(templates: Vec<(&'static str, &'static str)>) -> Engine<ArrayLoader> {
+    Engine::new(loader(templates), Environment::default())
+}
+
+pub fn loader(templates: Vec<(&'static str, &'static str)>) -> ArrayLoader {
+    ArrayLoader::new(
+        templates.into_iter()
+            .map(|(name, source)| (name.into(), source.into()))
+            .collect()
+    )
+}
+
+pub fn empty_data() -> HashMap<String, String> {
+    HashMap::new()
+}
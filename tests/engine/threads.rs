@@ -0,0 +1,39 @@
+use std::sync::Arc;
+use std::thread;
+use twig::Engine;
+use twig::loader::ArrayLoader;
+use twig::value::Value;
+use super::support;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn engine_and_values_are_send_and_sync() {
+    assert_send_sync::<Engine<ArrayLoader>>();
+    assert_send_sync::<Value>();
+}
+
+#[test]
+fn renders_from_multiple_threads() {
+    let twig = Arc::new(support::engine(vec![
+        ("index.twig", r#"[{% include "row.twig" %}]"#),
+        ("row.twig", "row"),
+    ]));
+
+    let workers: Vec<_> = (0..8)
+        .map(|_| {
+            let twig = twig.clone();
+            thread::spawn(move || {
+                (0..20)
+                    .map(|_| twig.get("index.twig", support::empty_data()).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        for output in worker.join().unwrap() {
+            assert_eq!(output, "[row]");
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use twig::environment::{ Autoescape, Config, ConfigBuilder, Environment };
+use twig::error::{ Error, EngineError };
+use super::support;
+
+fn builder() -> ConfigBuilder {
+    Config::builder()
+        .autoescape(Autoescape::Disabled)
+}
+
+fn render(config: Config, template: &'static str) -> String {
+    let twig = ::twig::Engine::new(
+        support::loader(vec![("index.twig", template)]),
+        Environment::new(config)
+    );
+
+    let mut data = HashMap::new();
+    data.insert("name", "a");
+
+    twig.get("index.twig", data).unwrap()
+}
+
+#[test]
+fn trims_all_whitespace_with_dash() {
+    assert_eq!(
+        render(builder().build(), "<p>\n  {{- name -}}\n</p>\n  {%- block x -%}\n  b\n{%- endblock %} {#- c -#} d"),
+        "<p>a</p>bd"
+    );
+}
+
+#[test]
+fn trims_whitespace_on_the_same_line_with_tilde() {
+    assert_eq!(
+        render(builder().build(), "<p>\n  {{~ name ~}}  \n</p>\n\t{%~ block x ~%}  \nb\n{%~ endblock %} {#~ c ~#} d"),
+        "<p>\na\n</p>\n\nb\nd"
+    );
+}
+
+#[test]
+fn keeps_newline_after_blocks_without_trim_blocks() {
+    let template = "list:\n{% block a %}\n  - {{ name }}\n{% endblock %}\n{# end #}\ndone";
+
+    assert_eq!(
+        render(builder().build(), template),
+        "list:\n  - a\ndone"
+    );
+    assert_eq!(
+        render(builder().trim_blocks(false).build(), template),
+        "list:\n\n  - a\n\n\ndone"
+    );
+}
+
+#[test]
+fn strips_indentation_before_blocks_with_lstrip_blocks() {
+    let template = "list:\n  {% block a %}\n  - {{ name }}\n  {% endblock %}\n  {# end #}\n  {{ name }} {% block b %}b{% endblock %}";
+
+    assert_eq!(
+        render(builder().build(), template),
+        "list:\n    - a\n      a b"
+    );
+    assert_eq!(
+        render(builder().lstrip_blocks(true).build(), template),
+        "list:\n  - a\n  a b"
+    );
+}
+
+#[test]
+fn reads_whitespace_options_from_hashmap() {
+    let mut map = HashMap::new();
+    map.insert("trim_blocks".to_string(), "false".to_string());
+    map.insert("lstrip_blocks".to_string(), "true".to_string());
+    map.insert("whitespace_line_trim".to_string(), "+".to_string());
+
+    let config = Config::from_hashmap(map);
+
+    assert!(!config.lexer.trim_blocks);
+    assert!(config.lexer.lstrip_blocks);
+    assert_eq!(render(config, "a  {{+ name +}}  b"), "aab");
+}
+
+#[test]
+fn rejects_conflicting_whitespace_modifiers() {
+    match Environment::new(builder().whitespace_line_trim("-").build()).try_init_all() {
+        Err(Error::Engine(ref e)) => match e.err {
+            EngineError::DelimiterConflict { ref name, ref other, .. } => {
+                assert_eq!((&name[..], &other[..]), ("whitespace_trim", "whitespace_line_trim"));
+            },
+            ref other => panic!("expected delimiter conflict, got {:?}", other),
+        },
+        Err(other) => panic!("expected engine error, got {:?}", other),
+        Ok(_) => panic!("expected conflicting whitespace modifiers"),
+    }
+}
@@ -0,0 +1,4 @@
+extern crate twig;
+extern crate little;
+
+mod engine;
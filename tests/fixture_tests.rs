@@ -37,15 +37,20 @@ fn fixtures() {
         };
         print_fixture_start(&message).unwrap();
 
-        let mut twig = Engine::new(ArrayLoader::new(
+        let twig = Engine::new(ArrayLoader::new(
             vec![("index.twig".into(), fixture.template.expect("fixture must contain main template"))]
                 .into_iter()
                 .chain(fixture.templates.into_iter())
                 .collect()
         ), match fixture.config {
             Some(config) => Environment::new(Config::from_hashmap(
-                match serde_json::from_str(&config) {
-                    Ok(map) => map,
+                match serde_json::from_str::<HashMap<String, serde_json::Value>>(&config) {
+                    Ok(map) => map.into_iter()
+                        .map(|(k, v)| match v {
+                            serde_json::Value::String(s) => (k, s),
+                            v => (k, v.to_string()),
+                        })
+                        .collect(),
                     Err(e) => panic!("failed to deserialize template config: {:#?}", e),
                 }
             )),
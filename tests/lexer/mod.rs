@@ -75,6 +75,25 @@ fn test_long_comments() {
     expect_end(_s);
 }
 
+#[test]
+fn test_kept_comments() {
+    let template = "{# a #}{##}b";
+
+    let env = CompiledEnvironment::default();
+    let lexer = Lexer::new(
+        LexerOptions { comments: true, .. LexerOptions::default() },
+        &env.lexing.operators
+    );
+    let mut _s = lexer.tokens(&template);
+
+    _s = expect(_s, TokenValueRef::CommentStart);
+    _s = expect(_s, TokenValueRef::Text(" a "));
+    _s = expect(_s, TokenValueRef::CommentStart);
+    _s = expect(_s, TokenValueRef::Text(""));
+    _s = expect(_s, TokenValueRef::Text("b"));
+    expect_end(_s);
+}
+
 #[test]
 fn test_raw() {
     let template = [
@@ -101,6 +120,38 @@ fn test_raw_trim() {
     expect(_s, TokenValueRef::Text("aaa"));
 }
 
+#[test]
+fn test_raw_line_trim() {
+    let template = [
+        "{% raw %}aaa \n {%~ endraw ~%} \nb",
+    ].concat();
+
+    let env = CompiledEnvironment::default();
+    let lexer = Lexer::default(&env.lexing);
+    let mut _s = lexer.tokens(&template);
+
+    _s = expect(_s, TokenValueRef::Text("aaa \n"));
+    _s = expect(_s, TokenValueRef::Text("\nb"));
+    expect_end(_s);
+}
+
+#[test]
+fn test_whitespace_line_trim() {
+    let template = "a \n\t{{~ b ~}} \n c\n  {#~ d ~#}\t\n";
+
+    let env = CompiledEnvironment::default();
+    let lexer = Lexer::default(&env.lexing);
+    let mut _s = lexer.tokens(&template);
+
+    _s = expect(_s, TokenValueRef::Text("a \n"));
+    _s = expect(_s, TokenValueRef::VarStart);
+    _s = expect(_s, TokenValueRef::Name("b"));
+    _s = expect(_s, TokenValueRef::VarEnd);
+    _s = expect(_s, TokenValueRef::Text("\n c\n"));
+    _s = expect(_s, TokenValueRef::Text("\n"));
+    expect_end(_s);
+}
+
 #[test]
 fn test_verbatim() {
     let template = [
@@ -376,6 +427,72 @@ bar
     expect_error(_s, "Unclosed \"block\" at line 3");
 }
 
+#[test]
+fn test_line_statements_and_comments() {
+    let template = "a\n  %% block b\n## note {{ c }}\nd\n%% endblock";
+
+    let env = CompiledEnvironment::default();
+    let lexer = Lexer::new(
+        LexerOptions {
+            line_statement_prefix: Some("%%".into()),
+            line_comment_prefix: Some("##".into()),
+            .. LexerOptions::default()
+        },
+        &env.lexing.operators
+    );
+    let mut _s = lexer.tokens(&template);
+
+    _s = expect(_s, TokenValueRef::Text("a\n"));
+    _s = expect_with_line(_s, TokenValueRef::BlockStart, 2);
+    _s = expect_with_line(_s, TokenValueRef::Name("block"), 2);
+    _s = expect_with_line(_s, TokenValueRef::Name("b"), 2);
+    _s = expect_with_line(_s, TokenValueRef::BlockEnd, 2);
+    _s = expect_with_line(_s, TokenValueRef::Text("d\n"), 4);
+    _s = expect_with_line(_s, TokenValueRef::BlockStart, 5);
+    _s = expect_with_line(_s, TokenValueRef::Name("endblock"), 5);
+    _s = expect_with_line(_s, TokenValueRef::BlockEnd, 5);
+    expect_end(_s);
+}
+
+#[test]
+fn test_token_columns_and_spans() {
+    let template = "{{ foo }}\n  {%- if a == \"é\" %}";
+    let env = CompiledEnvironment::default();
+    let lexer = Lexer::default(&env.lexing);
+
+    let positions: Vec<_> = lexer.tokens(&template)
+        .map(|t| t.unwrap())
+        .map(|t| (t.line, t.column, &template[t.span.start .. t.span.end]))
+        .collect();
+
+    assert_eq!(positions, vec![
+        (1, 1, "{{"),
+        (1, 4, "foo"),
+        (1, 8, "}}"),
+        (2, 3, "{%-"),
+        (2, 7, "if"),
+        (2, 10, "a"),
+        (2, 12, "=="),
+        (2, 15, "\"é\""),
+        (2, 19, "%}"),
+    ]);
+}
+
+#[test]
+fn test_token_columns_on_long_line() {
+    let template = repeat("{{ é }}").take(20000).collect::<String>();
+    let env = CompiledEnvironment::default();
+    let lexer = Lexer::default(&env.lexing);
+
+    let last = lexer.tokens(&template)
+        .map(|t| t.unwrap())
+        .last()
+        .unwrap();
+
+    assert_eq!(last.value, TokenValueRef::VarEnd);
+    assert_eq!((last.line, last.column), (1, 20000 * 7 - 1));
+}
+
 fn count_token(template: &'static str, token_value: TokenValueRef) -> u32 {
     let env = CompiledEnvironment::default();
     let lexer = Lexer::default(&env.lexing);
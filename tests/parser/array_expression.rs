@@ -6,7 +6,7 @@ use twig::nodes::expr::Expr;
 #[test]
 fn test_array_expression() {
     for (template, expected) in get_tests_for_array() {
-        let module = support::expect_parsed(template);
+        let module = support::expect_parsed_without_spans(template);
         assert_eq!(module.body.expect_print(), &expected);
     }
 }
@@ -0,0 +1,33 @@
+extern crate twig;
+
+use super::support;
+
+#[test]
+fn collects_blocks_in_definition_order() {
+    let module = support::expect_parsed(
+        "{% block head %}a{% block title %}b{% endblock %}{% endblock head %}{% block short 'c' %}"
+    );
+
+    let names: Vec<_> = module.blocks.iter().map(|b| b.name.as_ref()).collect();
+    assert_eq!(names, vec!["title", "head", "short"]);
+}
+
+#[test]
+#[should_panic(
+    expected = r#"The block "title" has already been defined line 1 at line 2"#
+)]
+fn block_can_not_be_defined_twice() {
+    support::unwrap_or_display(
+        support::maybe_parsed("{% block title %}{% endblock %}\n{% block title %}{% endblock %}")
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = r#"Expected endblock for block "title" (but "head" given) at line 1"#
+)]
+fn endblock_name_must_match() {
+    support::unwrap_or_display(
+        support::maybe_parsed("{% block title %}{% endblock head %}")
+    );
+}
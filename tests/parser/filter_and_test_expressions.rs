@@ -0,0 +1,36 @@
+extern crate twig;
+
+use super::support;
+use twig::nodes::expr::{ Expr, ExprValue };
+
+#[test]
+fn test_filter_expression() {
+    let module = support::expect_parsed_without_spans(r#"{{ a|default("b") }}"#);
+    assert_eq!(module.body.expect_print(), &Expr::new_at(ExprValue::Filter {
+        name: "default".into(),
+        expr: Box::new(Expr::new_name("a", 1)),
+        arguments: vec![Expr::new_str_constant("b", 1)],
+    }, 1));
+}
+
+#[test]
+fn test_test_expression() {
+    let module = support::expect_parsed_without_spans(r#"{{ a is defined }}"#);
+    assert_eq!(module.body.expect_print(), &Expr::new_at(ExprValue::Test {
+        name: "defined".into(),
+        expr: Box::new(Expr::new_name("a", 1)),
+        arguments: vec![],
+    }, 1));
+}
+
+#[test]
+fn test_negated_test_expression() {
+    let module = support::expect_parsed_without_spans(r#"{{ a.b is not null }}"#);
+    match module.body.expect_print().value {
+        ExprValue::UnaryOperator { ref value, ref expr } if value == "not" => match expr.value {
+            ExprValue::Test { ref name, .. } if name == "null" => (),
+            ref other => panic!("expected test, got {:?}", other),
+        },
+        ref other => panic!("expected negation, got {:?}", other),
+    }
+}
@@ -0,0 +1,107 @@
+use twig::environment::Environment;
+use twig::format::format;
+
+fn formatted(template: &str) -> String {
+    let env = Environment::default().init_all();
+    format(&env, template).unwrap()
+}
+
+#[test]
+fn normalizes_expressions() {
+    for (template, expected) in vec![
+        ("{{a+b*c}}", "{{ a + b * c }}"),
+        ("{{ ((a + b)) * c }}", "{{ (a + b) * c }}"),
+        ("{{ not (a and b) or c }}", "{{ not (a and b) or c }}"),
+        ("{{ a is not defined }}", "{{ a is not defined }}"),
+        ("{{ 1..3 }}", "{{ 1..3 }}"),
+        ("{{ a ? b : c }}", "{{ a ? b : c }}"),
+        ("{{foo.bar(1,2)['baz']|default( \"x\" )}}", "{{ foo.bar(1, 2)['baz']|default('x') }}"),
+        ("{{ { a: [1,2], \"b\": 3.5, 4: null } }}", "{{ {'a': [1, 2], 'b': 3.5, 4: null} }}"),
+        ("{{ \"it's\" }}", "{{ \"it's\" }}"),
+        ("{{ \"a #{b ~ 'c'} d\" }}", "{{ \"a #{b ~ 'c'} d\" }}"),
+    ] {
+        assert_eq!(formatted(template), expected);
+    }
+}
+
+#[test]
+fn normalizes_tags() {
+    assert_eq!(
+        formatted("{%include 'a.twig' ignore  missing with {b: 1} only%}"),
+        "{% include 'a.twig' ignore missing with {'b': 1} only %}\n"
+    );
+    assert_eq!(
+        formatted("{% from _self import foo, bar as baz %}{% macro foo(a,b) %}{% endmacro %}"),
+        "{% from _self import foo, bar as baz %}{% macro foo(a, b) %}{% endmacro %}\n"
+    );
+    assert_eq!(
+        formatted("{%import './forms.twig' as  forms%}{{forms.input( 'a' )}}"),
+        "{% import './forms.twig' as forms %}{{ forms.input('a') }}"
+    );
+    assert_eq!(
+        formatted("{%extends  '../base.twig'%}\n{% block a %}{% endblock %}"),
+        "{% extends '../base.twig' %}\n{% block a %}{% endblock %}\n"
+    );
+}
+
+#[test]
+fn indents_nested_blocks() {
+    assert_eq!(
+        formatted("<div>\n  {% block a %}\n{% block b %}\n{{ x }}\n{% endblock %}\n      {% endblock %}\n</div>"),
+        "<div>\n  {% block a %}\n      {% block b %}\n          {{ x }}\n      {% endblock %}\n  {% endblock %}\n</div>"
+    );
+}
+
+#[test]
+fn keeps_comments() {
+    assert_eq!(
+        formatted("{#hello#}\n{{ a }}{#- bye -#}"),
+        "{# hello #}\n{{ a }}{#- bye -#}"
+    );
+}
+
+#[test]
+fn keeps_whitespace_modifiers() {
+    for (template, expected) in vec![
+        ("a {{- b -}} c", "a {{- b -}} c"),
+        ("a\n  {{~ b ~}}  \n c", "a\n  {{~ b ~}}  \n c"),
+        ("x {%- include 'a' ~%}\n  y", "x {%- include 'a' ~%}\n  y"),
+        ("{#- c -#}\n\nd", "{#- c -#}\n\nd"),
+        ("{%- block a -%}\n    {{ x }}\n{%- endblock ~%}\n\ne", "{%- block a -%}\n    {{ x }}\n{%- endblock ~%}\n\ne"),
+        ("a {% block t '}' -%} b", "a {% block t %}{{ '}' }}{% endblock -%} b"),
+        ("{% extends 'base' -%}\n\n{% block a %}{% endblock %}", "{% extends 'base' -%}\n\n{% block a %}{% endblock %}\n"),
+        ("{% macro m() -%}\n  x\n{%- endmacro %}", "{% macro m() -%}\n  x\n{%- endmacro %}\n"),
+    ] {
+        let once = formatted(template);
+        assert_eq!(once, expected);
+        assert_eq!(formatted(&once), once);
+    }
+}
+
+#[test]
+fn keeps_verbatim_text_literal() {
+    for (template, expected) in vec![
+        ("a{% verbatim %}{{ x }}{% endverbatim %}b", "a{% verbatim %}{{ x }}{% endverbatim %}b"),
+        ("{%raw%}\n{% if %}\n{%endraw%}\n{{ y }}", "{% verbatim %}\n{% if %}\n{% endverbatim %}\n{{ y }}"),
+        ("{% verbatim %}plain{% endverbatim %}", "plain"),
+    ] {
+        let once = formatted(template);
+        assert_eq!(once, expected);
+        assert_eq!(formatted(&once), once);
+    }
+}
+
+#[test]
+fn formatting_twice_gives_the_same_output() {
+    let once = formatted(r##"
+<html>
+    {#   title   #}
+    <title>{% block title "Hello" ~ name %}</title>
+    {%block body%}
+    {% block content %}{{ items|default([])  }}{%endblock%}
+        {{ "#{a}b"  }}
+    {% endblock %}
+</html>"##);
+
+    assert_eq!(formatted(&once), once);
+}
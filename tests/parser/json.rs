@@ -0,0 +1,93 @@
+use serde::Deserialize;
+use serde_json;
+use twig::environment::Environment;
+use twig::instructions::compile;
+use twig::nodes::Module;
+use twig::nodes::body::{ Body, ExtensionNode };
+use twig::instructions::Compile;
+use twig::mold::Staging;
+use twig::value::Value;
+use twig::error::TemplateResult;
+use twig::tokens::Span;
+use twig::nodes::expr::{ Expr, ExprValue };
+use super::support;
+
+/// Extension node that compiles to nothing.
+#[derive(Debug)]
+struct Opaque;
+
+impl<'c> Compile<'c> for Opaque {
+    fn compile<'r>(&'r self, _stage: &'r mut Staging<'c, Value>) -> TemplateResult<()> {
+        Ok(())
+    }
+}
+
+impl<'c> ExtensionNode<'c> for Opaque {
+    fn into_owned(self: Box<Self>) -> Box<ExtensionNode<'static>> {
+        Box::new(Opaque)
+    }
+}
+
+const TEMPLATE: &'static str = "a\n\"b\"\n{{ c.d(1, 2.5)|default('e') ~ f(g = null) }}{% block h %}{{ i is defined ? [j] : {k: true} }}{% endblock %}";
+
+#[test]
+fn writes_node_types_and_lines() {
+    let module = support::expect_parsed("a\n{{ b }}");
+    let value = serde_json::to_value(&module).unwrap();
+
+    assert_eq!(value["body"]["List"]["items"][0]["Text"]["value"], "a\n");
+    assert_eq!(value["body"]["List"]["items"][1]["Print"]["line"], 2);
+    assert_eq!(value["body"]["List"]["items"][1]["Print"]["expr"]["value"]["Name"], "b");
+}
+
+#[test]
+fn reads_written_module() {
+    let module = support::expect_parsed(TEMPLATE);
+    let json = serde_json::to_string(&module).unwrap();
+
+    let loaded: Module = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    assert_eq!(loaded.blocks[0].name, "h");
+    match loaded.body.expect_list()[0] {
+        Body::Text { ref value, line, .. } => {
+            assert_eq!(value, "a\n\"b\"\n");
+            assert_eq!(line, 1);
+        },
+        ref other => panic!("expected text, got {:?}", other),
+    }
+}
+
+#[test]
+fn compiles_loaded_module() {
+    let env = Environment::default().init_all();
+    let json = serde_json::to_string(&support::expect_parsed(TEMPLATE)).unwrap();
+
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let loaded = Module::deserialize(&value).unwrap();
+
+    assert!(compile(&env, &loaded).is_ok());
+}
+
+#[test]
+fn reads_expression() {
+    let value = serde_json::from_str::<serde_json::Value>(
+        r#"{"line": 3, "value": {"Concat": {"left": {"line": 3, "value": {"Name": "a"}}, "right": {"line": 3, "value": {"Constant": {"Str": "\n"}}}}}}"#
+    ).unwrap();
+
+    assert_eq!(Expr::deserialize(&value).unwrap(), Expr::new_at(ExprValue::Concat {
+        left: Box::new(Expr::new_name("a", 3)),
+        right: Box::new(Expr::new_str_constant("\n", 3)),
+    }, 3));
+}
+
+#[test]
+fn fails_to_write_extension_nodes() {
+    let body = Body::List { items: vec![
+        Body::Extension { node: Box::new(Opaque), line: 1, column: 1, span: Span::default() },
+    ] };
+
+    let error = serde_json::to_string(&body).unwrap_err();
+
+    assert!(error.to_string().contains("Extension"), "unexpected error {}", error);
+}
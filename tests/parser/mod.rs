@@ -4,3 +4,11 @@ mod can_only_assign_to_names;
 mod array_expression;
 mod string_expression;
 mod named_and_default_args;
+mod blocks;
+mod filter_and_test_expressions;
+#[cfg(feature = "serde")]
+mod json;
+mod owned;
+mod format;
+mod spans;
+mod recovery;
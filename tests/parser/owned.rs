@@ -0,0 +1,62 @@
+use std::thread;
+use twig::environment::Environment;
+use twig::instructions::compile;
+use twig::tokens::Lexer;
+use twig::nodes::{ Parser, Parse, Module, NodeVisitor, traverse_module };
+use twig::nodes::body::Body;
+use twig::error::TemplateResult;
+
+/// Parses template from a buffer that is dropped before returning.
+fn parse_owned(source: String) -> Module<'static> {
+    let env = Environment::default().init_all();
+    let lexer = Lexer::default(&env.lexing);
+    let mut tokens = lexer.tokens(&source);
+    let module = Module::parse(&mut Parser::new(&env.parsing, &mut tokens)).unwrap();
+    module.into_owned()
+}
+
+struct Upper;
+
+impl NodeVisitor for Upper {
+    fn name(&self) -> &'static str {
+        "upper"
+    }
+
+    fn enter_body<'c>(&self, body: &mut Body<'c>) -> TemplateResult<()> {
+        if let Body::Text { ref mut value, .. } = *body {
+            *value = value.to_uppercase().into();
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn keeps_module_after_source_is_dropped() {
+    let module = parse_owned("a{{ b ~ 'c' }}{% block d %}e{% endblock %}".into());
+
+    assert_eq!(module.blocks[0].name, "d");
+    assert_eq!(module.body.expect_list().len(), 3);
+}
+
+#[test]
+fn sends_owned_module_to_another_thread() {
+    let module = parse_owned("a{{ b }}".into());
+
+    let module = thread::spawn(move || {
+        let mut module = module;
+        traverse_module(&Upper, &mut module).unwrap();
+        module
+    }).join().unwrap();
+
+    match module.body.expect_list()[0] {
+        Body::Text { ref value, .. } => assert_eq!(value, "A"),
+        ref other => panic!("expected text, got {:?}", other),
+    }
+}
+
+#[test]
+fn compiles_owned_module() {
+    let env = Environment::default().init_all();
+
+    assert!(compile(&env, &parse_owned("a{{ b|default('c') }}".into())).is_ok());
+}
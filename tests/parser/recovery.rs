@@ -0,0 +1,62 @@
+use twig::environment::Environment;
+use twig::tokens::Lexer;
+use twig::nodes::{ parse_recovering, Module };
+use twig::nodes::body::Body;
+use twig::nodes::expr::ExprValue;
+
+fn recovered(template: &'static str) -> (Module<'static>, Vec<(usize, String)>) {
+    let env = Environment::default().init_all();
+    let lexer = Lexer::default(&env.lexing);
+    let mut tokens = lexer.tokens(template).recovering();
+    let (module, errors) = parse_recovering(&env.parsing, &mut tokens);
+    let errors = errors.into_iter()
+        .map(|e| (e.loc.line, e.err.to_string()))
+        .collect();
+    (module.into_owned(), errors)
+}
+
+fn printed_names(module: &Module) -> Vec<String> {
+    match module.body {
+        Body::List { ref items } => items.iter()
+            .filter_map(|item| match *item {
+                Body::Print { ref expr, .. } => match expr.value {
+                    ExprValue::Name(ref name) => Some(name.to_string()),
+                    ref other => panic!("expected name, got {:?}", other),
+                },
+                _ => None,
+            })
+            .collect(),
+        _ => panic!("expected list, got {:?}", module.body),
+    }
+}
+
+#[test]
+fn collects_parser_errors_and_keeps_valid_nodes() {
+    let (module, errors) = recovered("{{ a }}\n{{ b + }}\n{% nope %}\n{{ c }}\n{% block %}{{ d }}");
+
+    assert_eq!(errors, vec![
+        (2, "Unexpected token \"end of print statement\"".to_string()),
+        (3, "Unknown \"nope\" tag".to_string()),
+        (5, "Expected \"name\" but received \"end of statement block\"".to_string()),
+    ]);
+    assert_eq!(printed_names(&module), vec!["a", "c", "d"]);
+}
+
+#[test]
+fn collects_lexer_errors() {
+    let (module, errors) = recovered("{{ a ] }}\n{{ b ' c }}\n{{ d }}\n{# open");
+
+    assert_eq!(errors, vec![
+        (1, "Unexpected \"]\"".to_string()),
+        (2, "Unexpected character \"'\"".to_string()),
+        (4, "Unclosed comment".to_string()),
+    ]);
+    assert_eq!(printed_names(&module), vec!["d"]);
+}
+
+#[test]
+fn valid_template_has_no_errors() {
+    let (_, errors) = recovered("{% block a %}{{ x|default(1) }}{% endblock %}");
+
+    assert_eq!(errors, vec![]);
+}
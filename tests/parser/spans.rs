@@ -0,0 +1,68 @@
+use super::support;
+use twig::nodes::body::Body;
+use twig::nodes::expr::{ Expr, ExprValue };
+
+fn source<'t>(template: &'t str, expr: &Expr) -> &'t str {
+    &template[expr.span.start .. expr.span.end]
+}
+
+#[test]
+fn expressions_span_their_source() {
+    let template = "{{ a.b(1) ~ (c + 2)|default('d') }}";
+    let module = support::expect_parsed(template);
+    let expr = module.body.expect_print();
+
+    assert_eq!(source(template, expr), "a.b(1) ~ (c + 2)|default('d')");
+    assert_eq!((expr.line, expr.column), (1, 11));
+
+    match expr.value {
+        ExprValue::BinaryOperator { ref left, ref right, .. } => {
+            assert_eq!(source(template, left), "a.b(1)");
+            assert_eq!(left.column, 5);
+            assert_eq!(source(template, right), "(c + 2)|default('d')");
+            assert_eq!(right.column, 20);
+            match right.value {
+                ExprValue::Filter { ref expr, ref arguments, .. } => {
+                    assert_eq!(source(template, expr), "(c + 2)");
+                    assert_eq!(source(template, &arguments[0]), "'d'");
+                },
+                ref other => panic!("expected filter, got {:?}", other),
+            }
+        },
+        ref other => panic!("expected binary operator, got {:?}", other),
+    }
+}
+
+#[test]
+fn body_nodes_span_whole_tags() {
+    let template = "Hi {{ name }}\n  {% block title %}{{ x }}{% endblock %}";
+    let module = support::expect_parsed(template);
+    let spans: Vec<_> = module.body.expect_list().iter()
+        .map(|node| node.location().unwrap())
+        .map(|loc| (loc.line, loc.column.unwrap(), &template[loc.span.unwrap().start .. loc.span.unwrap().end]))
+        .collect();
+
+    assert_eq!(spans, vec![
+        (1, 1, "Hi "),
+        (1, 4, "{{ name }}"),
+        (1, 14, "\n  "),
+        (2, 3, "{% block title %}{{ x }}{% endblock %}"),
+    ]);
+
+    match module.body.expect_list()[3] {
+        Body::BlockReference { ref name, .. } => assert_eq!(name, "title"),
+        ref other => panic!("expected block reference, got {:?}", other),
+    }
+}
+
+#[test]
+fn errors_point_at_the_token() {
+    match support::maybe_parsed("{{ a }}\n{{ b|nope }}") {
+        Err(e) => {
+            assert_eq!(e.loc.line, 2);
+            assert_eq!(e.loc.column, Some(6));
+            assert_eq!(e.loc.span.map(|s| (s.start, s.end)), Some((13, 17)));
+        },
+        Ok(_) => panic!("expected unknown filter error"),
+    }
+}
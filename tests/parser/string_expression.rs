@@ -16,7 +16,7 @@ fn test_string_expression_does_not_concatenate_two_consecutive_strings() {
 #[test]
 fn test_string_expression() {
     for (template, expected) in get_tests_for_string() {
-        let module = support::expect_parsed(template);
+        let module = support::expect_parsed_without_spans(template);
         assert_eq!(module.body.expect_print(), &expected);
     }
 }
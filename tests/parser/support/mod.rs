@@ -2,8 +2,9 @@
 
 use std::fmt;
 use twig::environment::Environment;
-use twig::tokens::Lexer;
-use twig::nodes::{ Parser, Parse, Module };
+use twig::tokens::{ Lexer, Span };
+use twig::nodes::{ Parser, Parse, Module, NodeVisitor, traverse_module };
+use twig::nodes::expr::Expr;
 use twig::error::TemplateResult;
 
 pub fn maybe_parsed(template: &'static str) -> TemplateResult<Module> {
@@ -21,6 +22,28 @@ pub fn expect_parsed(template: &'static str) -> Module {
     }
 }
 
+/// Parses the template and resets expression columns and spans, so it can be
+/// compared to expressions created by hand.
+pub fn expect_parsed_without_spans(template: &'static str) -> Module {
+    let mut module = expect_parsed(template);
+    traverse_module(&WithoutSpans, &mut module).unwrap();
+    module
+}
+
+struct WithoutSpans;
+
+impl NodeVisitor for WithoutSpans {
+    fn name(&self) -> &'static str {
+        "without_spans"
+    }
+
+    fn enter_expr<'c>(&self, expr: &mut Expr<'c>) -> TemplateResult<()> {
+        expr.column = 0;
+        expr.span = Span::default();
+        Ok(())
+    }
+}
+
 pub fn unwrap_or_display<T, E: fmt::Display>(value: Result<T, E>) {
     match value {
         Ok(_) => (),
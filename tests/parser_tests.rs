@@ -1,3 +1,5 @@
 extern crate twig;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(feature = "serde")] extern crate serde_json;
 
 mod parser;